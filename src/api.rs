@@ -0,0 +1,487 @@
+use crate::auth::{AuthMode, Session, SharedSession, Token};
+use crate::cookie::{unix_now, CookieJar};
+use crate::proto;
+use ehttp::streaming::Part;
+use log::info;
+use poll_promise::Promise;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex};
+
+/// 分页加载全部数据时每页的数量
+const ALL_PAGE_SIZE: usize = 100;
+
+/// 请求错误
+#[derive(Clone, Debug)]
+pub enum ApiError {
+    /// 网络错误，请求没有到达服务器或没有收到回复
+    Transport(String),
+    /// HTTP 状态码不是 2xx
+    HttpStatus { status: u16, body: String },
+    /// 回复内容解析失败
+    Decode { path: String, err: String },
+    /// 服务器返回的错误码(code != 0)
+    Server { code: i32, msg: String },
+    /// 登录过期(401/10086)
+    SessionExpired,
+}
+
+impl ApiError {
+    /// 是否值得原样重试
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ApiError::Transport(_) => true,
+            ApiError::HttpStatus { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Transport(err) => {
+                if err.is_empty() {
+                    write!(f, "Request failed")
+                } else {
+                    write!(f, "{}", err)
+                }
+            }
+            ApiError::HttpStatus { status, body } => {
+                if body.is_empty() {
+                    write!(f, "status:{}", status)
+                } else {
+                    write!(f, "status:{}\nerror:        {}", status, body)
+                }
+            }
+            ApiError::Decode { path, err } => write!(f, "{}: json decode: {}", path, err),
+            ApiError::Server { code, msg } => write!(f, "code:{} ({})", code, msg),
+            ApiError::SessionExpired => write!(f, "Session expired"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// 异步请求句柄，完成后得到对应接口的回复
+pub type ApiPromise<T> = Promise<Result<T, ApiError>>;
+
+/// 请求是否还未完成
+pub fn is_pending<T: Send + 'static>(promise: &Option<ApiPromise<T>>) -> bool {
+    promise
+        .as_ref()
+        .map_or(false, |promise| promise.ready().is_none())
+}
+
+/// npipe 后台接口
+#[derive(Clone)]
+pub struct ApiClient {
+    api_url: String,
+    session: SharedSession,
+    // token 过期时用于重新获取
+    refresh_login: Option<proto::LoginReq>,
+    ctx: Option<egui::Context>,
+    session_expired: Arc<Mutex<bool>>,
+}
+
+impl ApiClient {
+    pub fn new(api_url: impl Into<String>) -> Self {
+        Self {
+            api_url: api_url.into(),
+            session: SharedSession::default(),
+            refresh_login: None,
+            ctx: None,
+            session_expired: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    /// 请求时携带的 cookies 和 token，回复中的 Set-Cookie 也写入其中(web 端 cookie 由浏览器管理)
+    pub fn with_session(mut self, session: SharedSession) -> Self {
+        self.session = session;
+        self
+    }
+
+    /// Bearer token 过期(401/10086)时用这个用户名密码重新获取，并重试请求
+    pub fn with_token_refresh(mut self, req: proto::LoginReq) -> Self {
+        self.refresh_login = Some(req);
+        self
+    }
+
+    /// 请求完成时唤醒UI线程
+    pub fn with_context(mut self, ctx: egui::Context) -> Self {
+        self.ctx = Some(ctx);
+        self
+    }
+
+    /// 登录过期(401/10086)时置位
+    pub fn with_session_flag(mut self, session_expired: Arc<Mutex<bool>>) -> Self {
+        self.session_expired = session_expired;
+        self
+    }
+
+    /// 按认证方式登录，成功返回会话。API key 方式时 `req.password` 为 key，只检查是否可用
+    pub fn login(&self, mode: AuthMode, req: &proto::LoginReq) -> ApiPromise<Session> {
+        let mut api = self.clone();
+        api.refresh_login = None;
+        match mode {
+            AuthMode::Cookie => {
+                let url = self.url("login");
+                api.fetch("login", to_json(req), move |response| {
+                    decode_json::<proto::GeneralResponse>("login", response)?;
+                    Ok(Session {
+                        cookies: CookieJar::from_response(
+                            &url,
+                            set_cookies(&response.headers),
+                            unix_now(),
+                        ),
+                        token: None,
+                    })
+                })
+            }
+            AuthMode::Bearer => api.fetch("token", to_json(req), |response| {
+                decode_token(response).map(Session::with_token)
+            }),
+            AuthMode::ApiKey => {
+                let session = Session::with_token(Token::ApiKey(req.password.clone()));
+                api.session = session.clone().shared();
+                api.fetch("test_auth", Vec::new(), move |_| Ok(session))
+            }
+        }
+    }
+
+    pub fn logout(&self) -> ApiPromise<proto::GeneralResponse> {
+        self.request("logout", Vec::new())
+    }
+
+    pub fn test_auth(&self) -> ApiPromise<proto::GeneralResponse> {
+        self.request("test_auth", Vec::new())
+    }
+
+    pub fn player_list(
+        &self,
+        req: &proto::PlayerListRequest,
+    ) -> ApiPromise<proto::PlayerListResponse> {
+        self.request("player_list", to_json(req))
+    }
+
+    /// 依次加载每一页，得到全部玩家
+    pub fn all_players(&self) -> ApiPromise<Vec<proto::PlayerListItem>> {
+        let (sender, promise) = Promise::new();
+        self.player_pages(0, Vec::new(), sender);
+        promise
+    }
+
+    fn player_pages(
+        &self,
+        page_number: usize,
+        mut players: Vec<proto::PlayerListItem>,
+        sender: poll_promise::Sender<Result<Vec<proto::PlayerListItem>, ApiError>>,
+    ) {
+        let req = proto::PlayerListRequest {
+            page_number,
+            page_size: ALL_PAGE_SIZE,
+            ..Default::default()
+        };
+        let api = self.clone();
+        let decode = |response: &ehttp::Response| {
+            decode_json::<proto::PlayerListResponse>("player_list", response)
+        };
+        self.send(
+            "player_list".into(),
+            to_json(&req),
+            decode,
+            move |result| match result {
+                Ok(response) => {
+                    let done = response.players.is_empty()
+                        || players.len() + response.players.len() >= response.total_count;
+                    players.extend(response.players);
+                    if done {
+                        sender.send(Ok(players));
+                    } else {
+                        api.player_pages(page_number + 1, players, sender);
+                    }
+                }
+                Err(err) => sender.send(Err(err)),
+            },
+        );
+    }
+
+    pub fn add_player(&self, req: &proto::PlayerAddReq) -> ApiPromise<proto::GeneralResponse> {
+        self.request("add_player", to_json(req))
+    }
+
+    pub fn update_player(
+        &self,
+        req: &proto::PlayerUpdateReq,
+    ) -> ApiPromise<proto::GeneralResponse> {
+        self.request("update_player", to_json(req))
+    }
+
+    pub fn remove_player(
+        &self,
+        req: &proto::PlayerRemoveReq,
+    ) -> ApiPromise<proto::GeneralResponse> {
+        self.request("remove_player", to_json(req))
+    }
+
+    pub fn tunnel_list(
+        &self,
+        req: &proto::TunnelListRequest,
+    ) -> ApiPromise<proto::TunnelListResponse> {
+        self.request("tunnel_list", to_json(req))
+    }
+
+    pub fn add_tunnel(&self, req: &proto::TunnelAddReq) -> ApiPromise<proto::GeneralResponse> {
+        self.request("add_tunnel", to_json(req))
+    }
+
+    pub fn update_tunnel(
+        &self,
+        req: &proto::TunnelUpdateReq,
+    ) -> ApiPromise<proto::GeneralResponse> {
+        self.request("update_tunnel", to_json(req))
+    }
+
+    pub fn remove_tunnel(
+        &self,
+        req: &proto::TunnelRemoveReq,
+    ) -> ApiPromise<proto::GeneralResponse> {
+        self.request("remove_tunnel", to_json(req))
+    }
+
+    pub fn tunnel_stats(
+        &self,
+        req: &proto::TrafficStatsRequest,
+    ) -> ApiPromise<proto::TrafficStatsResponse> {
+        self.request("tunnel_stats", to_json(req))
+    }
+
+    pub fn player_stats(
+        &self,
+        req: &proto::TrafficStatsRequest,
+    ) -> ApiPromise<proto::TrafficStatsResponse> {
+        self.request("player_stats", to_json(req))
+    }
+
+    pub fn traffic_series(
+        &self,
+        req: &proto::TrafficSeriesRequest,
+    ) -> ApiPromise<proto::TrafficSeriesResponse> {
+        self.request("traffic_series", to_json(req))
+    }
+
+    fn request<T>(&self, path: &str, body: Vec<u8>) -> ApiPromise<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let path_moved = path.to_string();
+        self.fetch(path, body, move |response| {
+            decode_json::<T>(&path_moved, response)
+        })
+    }
+
+    fn fetch<T, F>(&self, path: &str, body: Vec<u8>, decode: F) -> ApiPromise<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&ehttp::Response) -> Result<T, ApiError> + Send + 'static,
+    {
+        let (sender, promise) = Promise::new();
+        self.send(path.to_string(), body, decode, move |result| {
+            sender.send(result)
+        });
+        promise
+    }
+
+    /// 发送请求，Bearer token 过期时重新获取后重试一次
+    fn send<T, F, D>(&self, path: String, body: Vec<u8>, decode: F, done: D)
+    where
+        T: Send + 'static,
+        F: FnOnce(&ehttp::Response) -> Result<T, ApiError> + Send + 'static,
+        D: FnOnce(Result<T, ApiError>) + Send + 'static,
+    {
+        let url = self.url(&path);
+        let mut request = ehttp::Request::post(&url, body.clone());
+        self.add_auth(&mut request);
+
+        let api = self.clone();
+        ehttp::fetch(request, move |response| {
+            let result = match response {
+                Ok(response) => {
+                    store_cookies(&api.session, &url, &response.headers);
+                    match check_response(&response) {
+                        Err(ApiError::SessionExpired) if api.refresh_login.is_some() => {
+                            let mut retry = api.clone();
+                            retry.refresh_login = None;
+                            api.refresh_token(Box::new(move |result| match result {
+                                Ok(()) => retry.send(path, body, decode, done),
+                                Err(err) => retry.finish(Err(err), done),
+                            }));
+                            return;
+                        }
+                        result => result.and_then(|_| decode(&response)),
+                    }
+                }
+                Err(err) => Err(ApiError::Transport(err)),
+            };
+            api.finish(result, done);
+        });
+    }
+
+    fn finish<T>(&self, result: Result<T, ApiError>, done: impl FnOnce(Result<T, ApiError>)) {
+        if let Err(ApiError::SessionExpired) = result {
+            *self.session_expired.lock().unwrap() = true;
+        }
+        done(result);
+        if let Some(ctx) = &self.ctx {
+            ctx.request_repaint(); // wake up UI thread
+        }
+    }
+
+    /// 用保存的用户名密码重新获取 token，失败时会话过期
+    // 回调装箱，避免和 send 互相递归实例化
+    fn refresh_token(&self, done: Box<dyn FnOnce(Result<(), ApiError>) + Send>) {
+        let Some(req) = &self.refresh_login else {
+            return done(Err(ApiError::SessionExpired));
+        };
+        info!("refresh token");
+        let session = self.session.clone();
+        let api = ApiClient::new(self.api_url.clone());
+        api.send("token".into(), to_json(req), decode_token, move |result| {
+            done(match result {
+                Ok(token) => {
+                    session.lock().unwrap().token = Some(token);
+                    Ok(())
+                }
+                Err(ApiError::Transport(err)) => Err(ApiError::Transport(err)),
+                Err(_) => Err(ApiError::SessionExpired),
+            })
+        });
+    }
+
+    /// 以流的方式读取 GET 回复(服务器推送)，每收到一段数据调用一次 `on_data`，
+    /// 返回 `Break` 时断开
+    pub fn stream<F>(&self, path: &str, on_data: F)
+    where
+        F: Fn(ehttp::Result<Part>) -> ControlFlow<()> + Send + 'static,
+    {
+        let url = self.url(path);
+        let mut request = ehttp::Request::get(&url);
+        request.headers = ehttp::Headers::new(&[("Accept", "text/event-stream")]);
+        self.add_auth(&mut request);
+
+        let api = self.clone();
+        ehttp::streaming::fetch(request, move |part| {
+            if let Ok(Part::Response(response)) = &part {
+                store_cookies(&api.session, &url, &response.headers);
+                if response.status == 401 {
+                    info!("logout code: 401");
+                    // 重新获取 token 后由调用者重新连接
+                    let session_expired = api.session_expired.clone();
+                    api.refresh_token(Box::new(move |result| {
+                        if let Err(ApiError::SessionExpired) = result {
+                            *session_expired.lock().unwrap() = true;
+                        }
+                    }));
+                }
+            }
+            let flow = on_data(part);
+            if let Some(ctx) = &api.ctx {
+                ctx.request_repaint();
+            }
+            flow
+        });
+    }
+
+    fn url(&self, path: &str) -> String {
+        let mut url = if let Some('/') = self.api_url.chars().last() {
+            self.api_url.clone()
+        } else {
+            format!("{}/", self.api_url)
+        };
+        url.push_str(path);
+        url
+    }
+
+    /// 使用保存的 token 和 cookies，过期的和地址不匹配的 cookie 不发送
+    fn add_auth(&self, request: &mut ehttp::Request) {
+        let session = self.session.lock().unwrap();
+        if let Some(token) = &session.token {
+            request.headers.headers.push(token.header());
+        }
+        let is_web = cfg!(target_arch = "wasm32");
+        if is_web {
+            return;
+        }
+        if let Some(header) = session.cookies.header(&request.url, unix_now()) {
+            request.headers.headers.push(("Cookie".into(), header));
+        }
+    }
+}
+
+fn to_json(req: &impl Serialize) -> Vec<u8> {
+    serde_json::to_vec(req).unwrap()
+}
+
+fn check_response(response: &ehttp::Response) -> Result<(), ApiError> {
+    if response.status == 401 {
+        info!("logout code: 401");
+        return Err(ApiError::SessionExpired);
+    }
+
+    if !response.ok {
+        return Err(ApiError::HttpStatus {
+            status: response.status,
+            body: response.text().unwrap_or_default().to_string(),
+        });
+    }
+
+    // 列表类回复没有 code 字段, 只有通用回复需要检查错误码
+    if let Ok(general) = serde_json::from_slice::<proto::GeneralResponse>(&response.bytes) {
+        match general.code {
+            0 => {}
+            10086 => {
+                info!("logout code: 10086");
+                return Err(ApiError::SessionExpired);
+            }
+            code => {
+                return Err(ApiError::Server {
+                    code,
+                    msg: general.msg,
+                })
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn decode_json<T: DeserializeOwned>(path: &str, response: &ehttp::Response) -> Result<T, ApiError> {
+    serde_json::from_slice::<T>(&response.bytes).map_err(|err| ApiError::Decode {
+        path: path.to_string(),
+        err: err.to_string(),
+    })
+}
+
+// 响应中的 Set-Cookie 头部
+fn set_cookies(headers: &ehttp::Headers) -> impl Iterator<Item = &str> {
+    headers
+        .headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("Set-Cookie"))
+        .map(|(_, value)| value.as_str())
+}
+
+/// 任何回复中的 Set-Cookie 都更新保存的 cookies
+fn store_cookies(session: &SharedSession, url: &str, headers: &ehttp::Headers) {
+    let jar = &mut session.lock().unwrap().cookies;
+    let now = unix_now();
+    jar.store(url, set_cookies(headers), now);
+    jar.remove_expired(now);
+}
+
+fn decode_token(response: &ehttp::Response) -> Result<Token, ApiError> {
+    let response = decode_json::<proto::TokenResponse>("token", response)?;
+    Ok(Token::Bearer(response.token))
+}
@@ -1,16 +1,24 @@
-use crate::proto::GeneralResponse;
+use crate::api::{is_pending, ApiClient, ApiPromise};
+use crate::auth::{AuthMode, Session, SharedSession};
+use crate::cookie::{unix_now, SESSION_COOKIE};
+use crate::profile::{find_profile, ProfileColor, ServerProfile};
+use crate::proto::{self, GeneralResponse};
+use crate::push::PushChannel;
 use crate::render;
 use crate::render::RenderUI;
-use crate::resource::Resource;
+use crate::secret::{
+    PassphraseStore, ProfileSecrets, SecretBackend, SecretError, SecretStore, Secrets,
+};
 use eframe::epaint::text::{FontData, FontDefinitions};
 use eframe::epaint::FontFamily;
-use log::info;
-use poll_promise::Promise;
-use serde_urlencoded;
+use log::{info, warn};
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 会话 cookie 过期前多久重新登录(秒)
+static SESSION_RENEW_MARGIN: u64 = 60;
 
 pub struct SubPage {
     render: Rc<RefCell<dyn RenderUI>>,
@@ -24,20 +32,46 @@ pub struct TemplateApp {
 
     /// 用户名
     pub(crate) username: String,
-    /// 密码
+    /// 密码，只在勾选 remember me 后通过 SecretStore 保存
+    #[serde(skip)]
     pub(crate) password: String,
     /// 是否是暗黑主题
     pub(crate) is_dark_them: bool,
     /// 是否已登录
+    #[serde(skip)]
     pub(crate) logged_in: bool,
-    /// cookies缓存
-    pub(crate) cookies: Vec<String>,
-
+    /// 认证方式
+    pub(crate) auth_mode: AuthMode,
+    /// 登录后的 cookies/token 缓存
     #[serde(skip)]
-    pub(crate) promise_map: HashMap<String, Promise<ehttp::Result<Resource>>>,
-
+    pub(crate) session: SharedSession,
+    /// 是否保存密码和会话
+    pub(crate) remember_me: bool,
+    /// 密码和会话的保存方式
+    pub(crate) secret_backend: SecretBackend,
+    /// 使用主密码加密后的密码和会话
+    sealed_secrets: Option<String>,
+    /// 保存的服务器
+    pub(crate) profiles: Vec<ServerProfile>,
+    /// 当前使用的服务器，None 表示未保存的临时服务器
+    pub(crate) active_profile: Option<usize>,
+
+    /// 已解锁的 SecretStore，未解锁时不保存
+    #[serde(skip)]
+    secret_store: Option<Box<dyn SecretStore>>,
+    // 上次保存的数据，没有变化时不重新加密
+    #[serde(skip)]
+    saved_secrets: Option<Secrets>,
+    #[serde(skip)]
+    session_expired: Arc<Mutex<bool>>,
+    #[serde(skip)]
+    logout_promise: Option<ApiPromise<GeneralResponse>>,
+    /// 会话快过期时的重新登录
+    #[serde(skip)]
+    renew_promise: Option<ApiPromise<Session>>,
+    /// 登录期间的服务器推送连接
     #[serde(skip)]
-    need_check: Arc<Mutex<bool>>,
+    push: Option<PushChannel>,
 
     #[serde(skip)]
     pub(crate) can_modify_api_url: bool,
@@ -58,9 +92,19 @@ impl Default for TemplateApp {
             password: "".into(),
             logged_in: false,
             is_dark_them: true,
-            promise_map: HashMap::new(),
-            cookies: Vec::new(),
-            need_check: Arc::new(Mutex::new(false)),
+            auth_mode: AuthMode::Cookie,
+            session: SharedSession::default(),
+            remember_me: false,
+            secret_backend: SecretBackend::Passphrase,
+            sealed_secrets: None,
+            secret_store: None,
+            saved_secrets: None,
+            profiles: Vec::new(),
+            active_profile: None,
+            session_expired: Arc::new(Mutex::new(false)),
+            logout_promise: None,
+            renew_promise: None,
+            push: None,
             can_modify_api_url: true,
             login_ui: Rc::new(RefCell::new(render::login::Logic::new())),
             cur_page_index: 0,
@@ -73,6 +117,18 @@ impl Default for TemplateApp {
                     name: "🔀tunnel".into(),
                     render: Rc::new(RefCell::new(render::tunnel::Logic::new())),
                 },
+                SubPage {
+                    name: "📈stats".into(),
+                    render: Rc::new(RefCell::new(render::stats::Logic::new())),
+                },
+                SubPage {
+                    name: "🌐servers".into(),
+                    render: Rc::new(RefCell::new(render::servers::Logic::new())),
+                },
+                SubPage {
+                    name: "🗄config".into(),
+                    render: Rc::new(RefCell::new(render::config::Logic::new())),
+                },
             ],
         }
     }
@@ -95,6 +151,13 @@ impl TemplateApp {
 
         load_fonts(&cc.egui_ctx);
 
+        // 钥匙串不需要主密码，启动时直接读取
+        if app.remember_me && app.secret_backend == SecretBackend::Keyring {
+            if let Err(err) = app.open_keyring() {
+                warn!("{}", err);
+            }
+        }
+
         #[cfg(target_arch = "wasm32")]
         if let Some(url) = get_current_url() {
             app.api_url = url;
@@ -104,141 +167,71 @@ impl TemplateApp {
         app
     }
 
-    pub fn can_request(&mut self, request_type: &String) -> bool {
-        if let Some(promise) = self.promise_map.get(request_type) {
-            promise.ready().is_some()
+    /// 当前会话的接口
+    pub(crate) fn api(&self, ctx: &egui::Context) -> ApiClient {
+        let api = ApiClient::new(self.api_url.clone())
+            .with_session(self.session.clone())
+            .with_context(ctx.clone())
+            .with_session_flag(self.session_expired.clone());
+        if self.auth_mode == AuthMode::Bearer && !self.password.is_empty() {
+            api.with_token_refresh(self.login_req())
         } else {
-            true
+            api
         }
     }
 
-    pub fn http_request_ex(
-        &mut self,
-        ctx: &egui::Context,
-        path: &str,
-        key: String,
-        params: Option<HashMap<String, String>>,
-        body: Vec<u8>,
-    ) {
-        let mut url = if let Some('/') = self.api_url.chars().last() {
-            self.api_url.clone()
-        } else {
-            format!("{}/", self.api_url)
-        };
-
-        url.push_str(path);
-
-        if let Some(params) = params {
-            let encoded: String = serde_urlencoded::to_string(params).unwrap();
-            if encoded.len() > 0 {
-                url.push_str("?");
-                url.push_str(&encoded);
-            }
+    /// 使用当前用户名密码(API key 方式时为 key)的登录请求
+    pub(crate) fn login_req(&self) -> proto::LoginReq {
+        proto::LoginReq {
+            username: self.username.clone(),
+            password: self.password.clone(),
         }
+    }
 
-        let mut request = ehttp::Request::post(url, body);
-
-        // 使用保存的 cookies
-        let is_web = cfg!(target_arch = "wasm32");
-        if !is_web {
-            request
-                .headers
-                .headers
-                .push(("Cookie".into(), self.cookies.join(";")));
+    /// 服务器的接口，当前服务器使用当前会话，其他服务器使用保存的会话
+    pub(crate) fn profile_api(&self, index: usize, ctx: &egui::Context) -> Option<ApiClient> {
+        if self.active_profile == Some(index) {
+            return self.logged_in.then(|| self.api(ctx));
         }
-
-        let path_moved = path.to_string();
-        let need_check = self.need_check.clone();
-        let ctx = ctx.clone();
-        let (sender, promise) = Promise::new();
-        ehttp::fetch(request, move |response| {
-            ctx.request_repaint(); // wake up UI thread
-            *need_check.lock().unwrap() = true;
-            let resource =
-                response.map(|response| Resource::from_response(&ctx, response, path_moved));
-            sender.send(resource);
-        });
-
-        self.promise_map.insert(key, promise);
+        self.profiles.get(index)?.api(ctx)
     }
 
-    pub fn http_request(
-        &mut self,
-        ctx: &egui::Context,
-        path: &str,
-        params: Option<HashMap<String, String>>,
-        body: Vec<u8>,
-    ) {
-        self.http_request_ex(ctx, path, path.to_string(), params, body);
+    /// 登录非当前的服务器，会话保存在配置中
+    pub(crate) fn profile_login_success(&mut self, index: usize, session: Session) {
+        if self.active_profile == Some(index) {
+            self.login_success(session.shared());
+        } else if let Some(profile) = self.profiles.get_mut(index) {
+            *profile.session_expired.lock().unwrap() = false;
+            profile.session = Some(session.shared());
+        }
     }
 
     fn http_response_check(&mut self) {
-        if *self.need_check.lock().unwrap() == false {
-            return;
-        }
-        for (key, value) in &mut self.promise_map {
-            if let Some(result) = value.ready_mut() {
-                if let Ok(resource) = result {
-                    if !resource.checked {
-                        resource.checked = false;
-                        let ref response = resource.response;
-                        if response.ok {
-                            if let Ok(response) =
-                                serde_json::from_slice::<GeneralResponse>(&response.bytes)
-                            {
-                                if response.code == 10086 {
-                                    info!("logout code: 10086");
-                                    self.logout();
-                                    break;
-                                }
-                            }
-                        } else if response.status == 401 {
-                            info!("logout code: 401");
-                            self.logout();
-                            break;
-                        }
-
-                        if key == "logout" {
-                            info!("logout code: 0");
-                            self.logout();
-                            break;
-                        }
-                    }
-                } else {
-                    if key == "logout" {
-                        info!("logout code: 0");
-                        self.logout();
-                        break;
-                    }
-                }
+        for profile in &mut self.profiles {
+            if std::mem::take(&mut *profile.session_expired.lock().unwrap()) {
+                profile.session = None;
             }
         }
 
-        *self.need_check.lock().unwrap() = false;
-    }
-
-    /// 登录成功
-    pub fn login_success(&mut self, cookies: Vec<String>) {
-        for ref page in &self.sub_pages {
-            page.render.borrow_mut().reset();
+        let session_expired = std::mem::take(&mut *self.session_expired.lock().unwrap());
+        if session_expired {
+            self.logout();
+            return;
         }
-        self.promise_map.clear();
-        self.logged_in = true;
-        self.cookies = cookies;
-    }
 
-    /// 登出，清理数据
-    pub fn logout(&mut self) {
-        self.promise_map.clear();
-        self.logged_in = false;
-        self.cookies.clear();
+        if let Some(promise) = &self.logout_promise {
+            if promise.ready().is_some() {
+                info!("logout code: 0");
+                self.logout();
+            }
+        }
     }
-}
 
-impl eframe::App for TemplateApp {
-    /// Called each time the UI needs repainting, which may be many times per second.
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    /// 绘制一帧, 不依赖 eframe::Frame 以便在测试中直接驱动
+    pub(crate) fn ui(&mut self, ctx: &egui::Context) {
         self.http_response_check();
+        self.renew_session(ctx);
+        self.update_push(ctx);
 
         // Put your widgets into a `SidePanel`, `TopBottomPanel`, `CentralPanel`, `Window` or `Area`.
         // For inspiration and more examples, go to https://emilk.github.io/egui
@@ -256,15 +249,17 @@ impl eframe::App for TemplateApp {
                             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         }
                         if ui.button("Test").clicked() {
-                            self.http_request(ctx, "test_auth", None, Vec::new());
+                            let _ = self.api(ctx).test_auth();
                         }
                     }
-                    if self.logged_in {
-                        if ui.button("Logout").clicked() && self.can_request(&"logout".into()) {
-                            self.http_request(ctx, "logout", None, Vec::new());
-                        }
+                    if self.logged_in
+                        && ui.button("Logout").clicked()
+                        && !is_pending(&self.logout_promise)
+                    {
+                        self.logout_promise = Some(self.api(ctx).logout());
                     }
                 });
+                self.render_profile_switcher(ui);
                 ui.add_space(16.0);
 
                 egui::widgets::global_dark_light_mode_buttons(ui);
@@ -300,8 +295,312 @@ impl eframe::App for TemplateApp {
         });
     }
 
+    /// 当前的推送连接，未登录时为 None
+    pub(crate) fn push(&self) -> Option<&PushChannel> {
+        self.push.as_ref()
+    }
+
+    /// 登录后建立推送连接，断开后重连
+    ///
+    /// 登出或切换服务器时丢弃连接，底层请求要等服务器下一次发送数据(事件或心跳)
+    /// 才会关闭；服务器不发心跳时空闲连接会一直保留到下一个事件
+    fn update_push(&mut self, ctx: &egui::Context) {
+        if !self.logged_in {
+            self.push = None;
+            return;
+        }
+        let api = self.api(ctx);
+        self.push
+            .get_or_insert_with(|| PushChannel::connect(&api))
+            .poll(&api, ctx);
+    }
+
+    /// 会话 cookie 快过期时用当前密码重新登录，没有密码时在过期时登出
+    fn renew_session(&mut self, ctx: &egui::Context) {
+        if let Some(promise) = &self.renew_promise {
+            match promise.ready() {
+                None => return,
+                Some(Ok(session)) => {
+                    info!("session renewed");
+                    let cookies = session.cookies.clone();
+                    self.session.lock().unwrap().cookies.merge(cookies);
+                    self.renew_promise = None;
+                }
+                Some(Err(err)) => {
+                    warn!("renew session: {}", err);
+                    self.logout();
+                    return;
+                }
+            }
+        }
+        // token 方式在服务器返回过期时重新获取
+        if !self.logged_in || self.auth_mode != AuthMode::Cookie {
+            return;
+        }
+
+        let expires = self
+            .session
+            .lock()
+            .unwrap()
+            .cookies
+            .get(SESSION_COOKIE)
+            .and_then(|x| x.expires);
+        let Some(expires) = expires else {
+            return;
+        };
+        let now = unix_now();
+        if !self.password.is_empty() {
+            let renew_at = expires.saturating_sub(SESSION_RENEW_MARGIN);
+            if renew_at <= now {
+                let api = ApiClient::new(self.api_url.clone()).with_context(ctx.clone());
+                self.renew_promise = Some(api.login(AuthMode::Cookie, &self.login_req()));
+            } else {
+                ctx.request_repaint_after(Duration::from_secs(renew_at - now));
+            }
+        } else if expires <= now {
+            info!("session expired");
+            self.logout();
+        } else {
+            ctx.request_repaint_after(Duration::from_secs(expires - now));
+        }
+    }
+
+    /// 顶部菜单中的服务器切换
+    fn render_profile_switcher(&mut self, ui: &mut egui::Ui) {
+        if self.profiles.is_empty() {
+            return;
+        }
+        let title = match self.active_profile.and_then(|x| self.profiles.get(x)) {
+            Some(profile) => profile.title(),
+            None => egui::RichText::new("server"),
+        };
+        let mut switch_to = None;
+        ui.menu_button(title, |ui| {
+            for (index, profile) in self.profiles.iter().enumerate() {
+                let response = ui
+                    .selectable_label(self.active_profile == Some(index), profile.title())
+                    .on_hover_text(&profile.api_url);
+                if response.clicked() {
+                    switch_to = Some(index);
+                    ui.close_menu();
+                }
+            }
+        });
+        if let Some(index) = switch_to {
+            self.switch_profile(Some(index));
+        }
+    }
+
+    /// 切换服务器，当前会话保存到当前配置中，切换回来时继续使用
+    pub(crate) fn switch_profile(&mut self, index: Option<usize>) {
+        if index == self.active_profile {
+            return;
+        }
+        let session = self.logged_in.then(|| self.session.clone());
+        if let Some(profile) = self.active_profile.and_then(|x| self.profiles.get_mut(x)) {
+            *profile.session_expired.lock().unwrap() = false;
+            profile.session = session;
+        }
+
+        self.active_profile = index.filter(|x| *x < self.profiles.len());
+        let session = match self.active_profile.map(|x| &mut self.profiles[x]) {
+            Some(profile) => {
+                if self.can_modify_api_url {
+                    self.api_url = profile.api_url.clone();
+                }
+                self.username = profile.username.clone();
+                self.password = profile.password.clone().unwrap_or_default();
+                self.auth_mode = profile.auth_mode;
+                profile.session.take()
+            }
+            None => None,
+        };
+
+        // 旧服务器上未完成的请求不影响新的会话
+        self.session_expired = Arc::new(Mutex::new(false));
+        self.logout_promise = None;
+        self.renew_promise = None;
+        self.logout();
+        if let Some(session) = session {
+            self.login_success(session);
+        }
+    }
+
+    /// 用当前的地址和用户名新建或更新配置，并切换到该配置
+    pub(crate) fn save_profile(&mut self, name: &str, color: ProfileColor, remember: bool) {
+        let profile = ServerProfile {
+            name: name.to_string(),
+            api_url: self.api_url.clone(),
+            username: self.username.clone(),
+            password: remember.then(|| self.password.clone()),
+            auth_mode: self.auth_mode,
+            color,
+            ..Default::default()
+        };
+        match find_profile(&self.profiles, name) {
+            Some(index) => self.profiles[index] = profile,
+            None => self.profiles.push(profile),
+        }
+        self.active_profile = find_profile(&self.profiles, name);
+    }
+
+    /// 删除配置，当前会话保持不变
+    pub(crate) fn remove_profile(&mut self, index: usize) {
+        if index >= self.profiles.len() {
+            return;
+        }
+        self.profiles.remove(index);
+        self.active_profile = match self.active_profile {
+            Some(x) if x == index => None,
+            Some(x) if x > index => Some(x - 1),
+            x => x,
+        };
+    }
+
+    /// 登录成功
+    pub fn login_success(&mut self, session: SharedSession) {
+        for page in &self.sub_pages {
+            page.render.borrow_mut().reset();
+        }
+        self.logged_in = true;
+        self.session = session;
+        // 使用新的 cookies/token 重新连接
+        self.push = None;
+
+        if let Some(profile) = self.active_profile.and_then(|x| self.profiles.get_mut(x)) {
+            profile.username = self.username.clone();
+            profile.auth_mode = self.auth_mode;
+            if profile.password.is_some() {
+                profile.password = Some(self.password.clone());
+            }
+        }
+    }
+
+    /// 保存的密码和会话需要输入主密码
+    pub(crate) fn secrets_locked(&self) -> bool {
+        self.remember_me && self.secret_store.is_none() && self.sealed_secrets.is_some()
+    }
+
+    pub(crate) fn secrets_unlocked(&self) -> bool {
+        self.secret_store.is_some()
+    }
+
+    /// 输入主密码，已有密文时解密并恢复密码和会话，否则设置新的主密码
+    pub(crate) fn unlock_secrets(&mut self, passphrase: &str) -> Result<(), SecretError> {
+        match &self.sealed_secrets {
+            Some(sealed) => {
+                let (store, secrets) = PassphraseStore::unlock(passphrase, sealed)?;
+                self.secret_store = Some(Box::new(store));
+                self.apply_secrets(secrets);
+            }
+            None => self.secret_store = Some(Box::new(PassphraseStore::new(passphrase)?)),
+        }
+        Ok(())
+    }
+
+    /// 使用系统钥匙串，读取保存的密码和会话
+    #[cfg(all(feature = "keyring", not(target_arch = "wasm32")))]
+    pub(crate) fn open_keyring(&mut self) -> Result<(), SecretError> {
+        let store = crate::secret::KeyringStore::new()?;
+        let secrets = store.load(None)?;
+        self.secret_store = Some(Box::new(store));
+        if let Some(secrets) = secrets {
+            self.apply_secrets(secrets);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(all(feature = "keyring", not(target_arch = "wasm32"))))]
+    pub(crate) fn open_keyring(&mut self) -> Result<(), SecretError> {
+        Err(SecretError::Backend("OS keyring is not available".into()))
+    }
+
+    /// 删除保存的密码和会话
+    pub(crate) fn forget_secrets(&mut self) {
+        if let Some(store) = self.secret_store.take() {
+            if let Err(err) = store.clear() {
+                warn!("{}", err);
+            }
+        }
+        self.sealed_secrets = None;
+        self.saved_secrets = None;
+    }
+
+    /// 勾选 remember me 时保存密码和会话
+    pub(crate) fn save_secrets(&mut self) {
+        if !self.remember_me {
+            return;
+        }
+        let Some(store) = &self.secret_store else {
+            return;
+        };
+        let secrets = self.collect_secrets();
+        if self.saved_secrets.as_ref() == Some(&secrets) {
+            return;
+        }
+        match store.save(&secrets) {
+            Ok(sealed) => {
+                if sealed.is_some() {
+                    self.sealed_secrets = sealed;
+                }
+                self.saved_secrets = Some(secrets);
+            }
+            Err(err) => warn!("{}", err),
+        }
+    }
+
+    fn collect_secrets(&self) -> Secrets {
+        Secrets {
+            password: self.password.clone(),
+            session: self.logged_in.then(|| self.session.lock().unwrap().clone()),
+            profiles: self
+                .profiles
+                .iter()
+                .map(|x| {
+                    let secrets = ProfileSecrets {
+                        password: x.password.clone(),
+                        session: x.session.as_ref().map(|x| x.lock().unwrap().clone()),
+                    };
+                    (x.name.clone(), secrets)
+                })
+                .collect(),
+        }
+    }
+
+    /// 恢复保存的密码和会话
+    fn apply_secrets(&mut self, secrets: Secrets) {
+        for profile in &mut self.profiles {
+            if let Some(x) = secrets.profiles.get(&profile.name) {
+                profile.password = x.password.clone();
+                profile.session = x.session.clone().map(Session::shared);
+            }
+        }
+        self.password = secrets.password.clone();
+        if let Some(session) = secrets.session.clone() {
+            self.login_success(session.shared());
+        }
+        self.saved_secrets = Some(secrets);
+    }
+
+    /// 登出，清理数据
+    pub fn logout(&mut self) {
+        self.logout_promise = None;
+        self.logged_in = false;
+        self.renew_promise = None;
+        self.session = SharedSession::default();
+        self.push = None;
+    }
+}
+
+impl eframe::App for TemplateApp {
+    /// Called each time the UI needs repainting, which may be many times per second.
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.ui(ctx);
+    }
+
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.save_secrets();
         eframe::set_value(storage, eframe::APP_KEY, self);
     }
 
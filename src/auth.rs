@@ -0,0 +1,75 @@
+//! 认证方式：cookie 会话、Bearer token 或 API key，按服务器选择。
+
+use crate::cookie::CookieJar;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// 登录时选择的认证方式
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AuthMode {
+    /// 服务器下发的 cookie(web 端由浏览器管理)
+    #[default]
+    Cookie,
+    /// 用户名密码换取 token，过期时自动重新获取
+    Bearer,
+    /// 固定的 API key，不需要用户名密码
+    ApiKey,
+}
+
+impl AuthMode {
+    pub const ALL: [AuthMode; 3] = [AuthMode::Cookie, AuthMode::Bearer, AuthMode::ApiKey];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AuthMode::Cookie => "cookie",
+            AuthMode::Bearer => "bearer token",
+            AuthMode::ApiKey => "API key",
+        }
+    }
+}
+
+/// 请求头中携带的 token
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum Token {
+    Bearer(String),
+    ApiKey(String),
+}
+
+impl Token {
+    /// 对应的请求头
+    pub fn header(&self) -> (String, String) {
+        match self {
+            Token::Bearer(token) => ("Authorization".into(), format!("Bearer {}", token)),
+            Token::ApiKey(key) => ("X-Api-Key".into(), key.clone()),
+        }
+    }
+}
+
+/// 登录后的凭据，回复中的 Set-Cookie 和重新获取的 token 直接写入
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct Session {
+    pub cookies: CookieJar,
+    /// None 时只使用 cookie
+    pub token: Option<Token>,
+}
+
+/// 多个请求共享的会话
+pub type SharedSession = Arc<Mutex<Session>>;
+
+impl Session {
+    pub fn with_token(token: Token) -> Self {
+        Self {
+            token: Some(token),
+            ..Default::default()
+        }
+    }
+
+    pub fn shared(self) -> SharedSession {
+        Arc::new(Mutex::new(self))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty() && self.token.is_none()
+    }
+}
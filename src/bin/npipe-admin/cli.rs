@@ -0,0 +1,637 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use npipe_web::api::{ApiClient, ApiError};
+use npipe_web::auth::AuthMode;
+use npipe_web::proto;
+use npipe_web::validation::{check_endpoint, check_source};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Write;
+use std::process::ExitCode;
+
+/// 查找单个条目时每页的数量
+const SEARCH_PAGE_SIZE: usize = 100;
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(
+    name = "npipe-admin",
+    version,
+    about = "Command line administration for npipe"
+)]
+struct Cli {
+    /// Api url of the npipe server
+    #[arg(
+        long,
+        env = "NPIPE_API_URL",
+        default_value = "http://127.0.0.1:8120/api/"
+    )]
+    url: String,
+
+    /// Admin username
+    #[arg(long, env = "NPIPE_USERNAME", default_value = "admin")]
+    username: String,
+
+    /// Admin password
+    #[arg(
+        long,
+        env = "NPIPE_PASSWORD",
+        hide_env_values = true,
+        default_value = ""
+    )]
+    password: String,
+
+    /// Output format
+    #[arg(long, global = true, value_enum, default_value_t = Format::Table)]
+    format: Format,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage players
+    #[command(subcommand)]
+    Player(PlayerCommand),
+    /// Manage tunnels
+    #[command(subcommand)]
+    Tunnel(TunnelCommand),
+}
+
+#[derive(Subcommand)]
+enum PlayerCommand {
+    /// List players
+    List(ListArgs),
+    /// Add a player
+    Add { username: String, password: String },
+    /// Update a player, unspecified fields are kept
+    Update {
+        id: u32,
+        #[arg(long)]
+        new_username: Option<String>,
+        #[arg(long)]
+        new_password: Option<String>,
+    },
+    /// Remove a player
+    Remove { id: u32 },
+}
+
+#[derive(Subcommand)]
+enum TunnelCommand {
+    /// List tunnels
+    List(ListArgs),
+    /// Add a tunnel, `--source` and `--endpoint` are required
+    Add(TunnelArgs),
+    /// Update a tunnel, unspecified fields are kept
+    Update {
+        id: u32,
+        #[command(flatten)]
+        args: TunnelArgs,
+    },
+    /// Remove a tunnel
+    Remove { id: u32 },
+    /// Enable a tunnel
+    Enable { id: u32 },
+    /// Disable a tunnel
+    Disable { id: u32 },
+}
+
+#[derive(Args)]
+struct ListArgs {
+    /// Page number, starting from 1
+    #[arg(long, default_value_t = 1)]
+    page: usize,
+    #[arg(long, default_value_t = 20)]
+    page_size: usize,
+    /// Fetch all pages
+    #[arg(long)]
+    all: bool,
+}
+
+#[derive(Args)]
+struct TunnelArgs {
+    /// Listen address
+    #[arg(long)]
+    source: Option<String>,
+    #[arg(long)]
+    endpoint: Option<String>,
+    #[arg(long)]
+    enabled: Option<bool>,
+    /// Sender(client) player id
+    #[arg(long)]
+    sender: Option<u32>,
+    /// Receiver(server) player id, 0 means the server
+    #[arg(long)]
+    receiver: Option<u32>,
+    #[arg(long)]
+    description: Option<String>,
+    /// TCP, UDP or SOCKS5
+    #[arg(long = "type", value_parser = parse_tunnel_type)]
+    tunnel_type: Option<u32>,
+    /// SOCKS5 username
+    #[arg(long)]
+    socks_username: Option<String>,
+    /// SOCKS5 password
+    #[arg(long)]
+    socks_password: Option<String>,
+    #[arg(long)]
+    compressed: Option<bool>,
+    /// None, Aes128 or Xor
+    #[arg(long, value_parser = parse_encryption_method)]
+    encryption: Option<String>,
+    /// Custom mapping entry `key=value`, may be repeated. Replaces the whole mapping.
+    #[arg(long = "map", value_parser = parse_mapping)]
+    custom_mapping: Vec<(String, String)>,
+}
+
+impl TunnelArgs {
+    fn apply(self, item: &mut proto::TunnelListItem) {
+        if let Some(source) = self.source {
+            item.source = source;
+        }
+        if let Some(endpoint) = self.endpoint {
+            item.endpoint = endpoint;
+        }
+        if let Some(enabled) = self.enabled {
+            item.enabled = enabled;
+        }
+        if let Some(sender) = self.sender {
+            item.sender = sender;
+        }
+        if let Some(receiver) = self.receiver {
+            item.receiver = receiver;
+        }
+        if let Some(description) = self.description {
+            item.description = description;
+        }
+        if let Some(tunnel_type) = self.tunnel_type {
+            item.tunnel_type = tunnel_type;
+        }
+        if let Some(username) = self.socks_username {
+            item.username = username;
+        }
+        if let Some(password) = self.socks_password {
+            item.password = password;
+        }
+        if let Some(compressed) = self.compressed {
+            item.is_compressed = compressed;
+        }
+        if let Some(encryption) = self.encryption {
+            item.encryption_method = encryption;
+        }
+        if !self.custom_mapping.is_empty() {
+            item.custom_mapping = self.custom_mapping.into_iter().collect();
+        }
+    }
+}
+
+fn parse_tunnel_type(value: &str) -> Result<u32, String> {
+    // 最后一项 UNKNOWN 不能用于新建
+    proto::TUNNEL_TYPE_OPTION[..proto::TUNNEL_TYPE_OPTION.len() - 1]
+        .iter()
+        .position(|x| x.eq_ignore_ascii_case(value))
+        .map(|x| x as u32)
+        .ok_or_else(|| format!("unknown tunnel type: {}", value))
+}
+
+fn parse_encryption_method(value: &str) -> Result<String, String> {
+    proto::TUNNEL_ENCRYPTION_METHOD
+        .iter()
+        .find(|x| x.eq_ignore_ascii_case(value))
+        .map(|x| x.to_string())
+        .ok_or_else(|| format!("unknown encryption method: {}", value))
+}
+
+fn parse_mapping(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected key=value: {}", value))
+}
+
+pub fn run() -> ExitCode {
+    let cli = Cli::parse();
+
+    match execute(cli, &mut std::io::stdout().lock()) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn execute(cli: Cli, out: &mut dyn Write) -> CliResult<()> {
+    let req = proto::LoginReq {
+        username: cli.username.clone(),
+        password: cli.password.clone(),
+    };
+    let session = ApiClient::new(cli.url.clone())
+        .login(AuthMode::Cookie, &req)
+        .block_and_take()?;
+    let api = ApiClient::new(cli.url.clone()).with_session(session.shared());
+
+    let result = match cli.command {
+        Command::Player(command) => player_command(&api, cli.format, command, out),
+        Command::Tunnel(command) => tunnel_command(&api, cli.format, command, out),
+    };
+
+    // 登出失败不影响命令结果
+    let _ = api.logout().block_and_take();
+
+    result
+}
+
+fn player_command(
+    api: &ApiClient,
+    format: Format,
+    command: PlayerCommand,
+    out: &mut dyn Write,
+) -> CliResult<()> {
+    match command {
+        PlayerCommand::List(args) => {
+            let players = fetch_pages(&args, |page_number, page_size| {
+                let req = proto::PlayerListRequest {
+                    page_number,
+                    page_size,
+                    ..Default::default()
+                };
+                let response = api.player_list(&req).block_and_take()?;
+                Ok((response.players, response.total_count))
+            })?;
+            match format {
+                Format::Json => print_json(out, &players)?,
+                Format::Table => print_table(
+                    out,
+                    &["id", "username", "password", "online"],
+                    players
+                        .iter()
+                        .map(|x| {
+                            vec![
+                                x.id.to_string(),
+                                x.username.clone(),
+                                x.password.clone(),
+                                x.online.to_string(),
+                            ]
+                        })
+                        .collect(),
+                )?,
+            }
+            Ok(())
+        }
+        PlayerCommand::Add { username, password } => {
+            let req = proto::PlayerAddReq { username, password };
+            print_general(out, format, api.add_player(&req).block_and_take()?)
+        }
+        PlayerCommand::Update {
+            id,
+            new_username,
+            new_password,
+        } => {
+            let mut req = proto::PlayerUpdateReq::from(find_player(api, id)?);
+            if let Some(username) = new_username {
+                req.username = username;
+            }
+            if let Some(password) = new_password {
+                req.password = password;
+            }
+            print_general(out, format, api.update_player(&req).block_and_take()?)
+        }
+        PlayerCommand::Remove { id } => {
+            let req = proto::PlayerRemoveReq { id };
+            print_general(out, format, api.remove_player(&req).block_and_take()?)
+        }
+    }
+}
+
+fn tunnel_command(
+    api: &ApiClient,
+    format: Format,
+    command: TunnelCommand,
+    out: &mut dyn Write,
+) -> CliResult<()> {
+    match command {
+        TunnelCommand::List(args) => {
+            let tunnels = fetch_pages(&args, |page_number, page_size| {
+                let req = proto::TunnelListRequest {
+                    page_number,
+                    page_size,
+                    ..Default::default()
+                };
+                let response = api.tunnel_list(&req).block_and_take()?;
+                Ok((response.tunnels, response.total_count))
+            })?;
+            match format {
+                Format::Json => print_json(out, &tunnels)?,
+                Format::Table => print_table(
+                    out,
+                    &[
+                        "id",
+                        "type",
+                        "source",
+                        "endpoint",
+                        "enabled",
+                        "sender",
+                        "receiver",
+                        "compressed",
+                        "encryption",
+                        "description",
+                    ],
+                    tunnels
+                        .iter()
+                        .map(|x| {
+                            vec![
+                                x.id.to_string(),
+                                tunnel_type_name(x.tunnel_type).to_string(),
+                                x.source.clone(),
+                                x.endpoint.clone(),
+                                x.enabled.to_string(),
+                                x.sender.to_string(),
+                                x.receiver.to_string(),
+                                x.is_compressed.to_string(),
+                                x.encryption_method.clone(),
+                                x.description.clone(),
+                            ]
+                        })
+                        .collect(),
+                )?,
+            }
+            Ok(())
+        }
+        TunnelCommand::Add(args) => {
+            if args.source.is_none() || args.endpoint.is_none() {
+                return Err("--source and --endpoint are required".into());
+            }
+            let mut item = proto::TunnelListItem {
+                id: 0,
+                source: String::new(),
+                endpoint: String::new(),
+                enabled: true,
+                sender: 0,
+                receiver: 0,
+                description: String::new(),
+                tunnel_type: 0,
+                password: String::new(),
+                username: String::new(),
+                is_compressed: false,
+                encryption_method: proto::TUNNEL_ENCRYPTION_METHOD[0].to_string(),
+                custom_mapping: HashMap::new(),
+            };
+            args.apply(&mut item);
+            check_tunnel(&item)?;
+            let req = proto::TunnelAddReq::from(item);
+            print_general(out, format, api.add_tunnel(&req).block_and_take()?)
+        }
+        TunnelCommand::Update { id, args } => {
+            let mut item = find_tunnel(api, id)?;
+            args.apply(&mut item);
+            check_tunnel(&item)?;
+            let req = proto::TunnelUpdateReq::from(item);
+            print_general(out, format, api.update_tunnel(&req).block_and_take()?)
+        }
+        TunnelCommand::Remove { id } => {
+            let req = proto::TunnelRemoveReq { id };
+            print_general(out, format, api.remove_tunnel(&req).block_and_take()?)
+        }
+        TunnelCommand::Enable { id } | TunnelCommand::Disable { id } => {
+            let enabled = matches!(command, TunnelCommand::Enable { .. });
+            let mut req = proto::TunnelUpdateReq::from(find_tunnel(api, id)?);
+            req.enabled = enabled as u8;
+            print_general(out, format, api.update_tunnel(&req).block_and_take()?)
+        }
+    }
+}
+
+/// 发送前检查地址，避免服务器上出现无法使用的隧道
+fn check_tunnel(item: &proto::TunnelListItem) -> CliResult<()> {
+    check_source(&item.source).map_err(|err| format!("--source: {}", err))?;
+    check_endpoint(item.tunnel_type, &item.endpoint)
+        .map_err(|err| format!("--endpoint: {}", err))?;
+    Ok(())
+}
+
+fn tunnel_type_name(tunnel_type: u32) -> &'static str {
+    let index = (tunnel_type as usize).min(proto::TUNNEL_TYPE_OPTION.len() - 1);
+    proto::TUNNEL_TYPE_OPTION[index]
+}
+
+/// 按参数请求一页或所有页
+fn fetch_pages<T>(
+    args: &ListArgs,
+    mut fetch: impl FnMut(usize, usize) -> Result<(Vec<T>, usize), ApiError>,
+) -> Result<Vec<T>, ApiError> {
+    // 接口页码从0开始
+    let first_page = args.page.max(1) - 1;
+    let page_size = args.page_size.max(1);
+    if !args.all {
+        return fetch(first_page, page_size).map(|(items, _)| items);
+    }
+
+    let mut items = Vec::new();
+    let mut page_number = 0;
+    loop {
+        let (page, total_count) = fetch(page_number, page_size)?;
+        let is_empty = page.is_empty();
+        items.extend(page);
+        if is_empty || items.len() >= total_count {
+            return Ok(items);
+        }
+        page_number += 1;
+    }
+}
+
+/// 没有按id查询的接口，只能逐页查找
+fn find_in_pages<T>(
+    id: u32,
+    get_id: impl Fn(&T) -> u32,
+    fetch: impl FnMut(usize, usize) -> Result<(Vec<T>, usize), ApiError>,
+) -> CliResult<T> {
+    let args = ListArgs {
+        page: 1,
+        page_size: SEARCH_PAGE_SIZE,
+        all: true,
+    };
+    fetch_pages(&args, fetch)?
+        .into_iter()
+        .find(|x| get_id(x) == id)
+        .ok_or_else(|| format!("id {} not found", id).into())
+}
+
+fn find_player(api: &ApiClient, id: u32) -> CliResult<proto::PlayerListItem> {
+    find_in_pages(
+        id,
+        |x: &proto::PlayerListItem| x.id,
+        |page_number, page_size| {
+            let req = proto::PlayerListRequest {
+                page_number,
+                page_size,
+                ..Default::default()
+            };
+            let response = api.player_list(&req).block_and_take()?;
+            Ok((response.players, response.total_count))
+        },
+    )
+}
+
+fn find_tunnel(api: &ApiClient, id: u32) -> CliResult<proto::TunnelListItem> {
+    find_in_pages(
+        id,
+        |x: &proto::TunnelListItem| x.id,
+        |page_number, page_size| {
+            let req = proto::TunnelListRequest {
+                page_number,
+                page_size,
+                ..Default::default()
+            };
+            let response = api.tunnel_list(&req).block_and_take()?;
+            Ok((response.tunnels, response.total_count))
+        },
+    )
+}
+
+fn print_general(
+    out: &mut dyn Write,
+    format: Format,
+    response: proto::GeneralResponse,
+) -> CliResult<()> {
+    match format {
+        Format::Json => print_json(out, &response),
+        Format::Table => {
+            writeln!(out, "ok")?;
+            Ok(())
+        }
+    }
+}
+
+fn print_json(out: &mut dyn Write, value: &impl Serialize) -> CliResult<()> {
+    writeln!(out, "{}", serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+fn print_table(out: &mut dyn Write, header: &[&str], rows: Vec<Vec<String>>) -> CliResult<()> {
+    let mut widths: Vec<usize> = header.iter().map(|x| x.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut print_row = |cells: Vec<&str>| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        writeln!(out, "{}", line.join("  ").trim_end())
+    };
+
+    print_row(header.to_vec())?;
+    for row in &rows {
+        print_row(row.iter().map(String::as_str).collect())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use npipe_web::mock::MockServer;
+
+    /// 对模拟服务器执行命令，返回输出
+    fn run_cli(server: &MockServer, args: &[&str]) -> String {
+        let url = server.api_url();
+        let mut argv = vec!["npipe-admin", "--url", &url, "--password", "123"];
+        argv.extend_from_slice(args);
+        let mut out = Vec::new();
+        execute(Cli::try_parse_from(argv).unwrap(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn parse_format_anywhere() {
+        for args in [
+            ["npipe-admin", "--format", "json", "player", "list"],
+            ["npipe-admin", "player", "--format", "json", "list"],
+            ["npipe-admin", "player", "list", "--format", "json"],
+        ] {
+            let cli = Cli::try_parse_from(args).unwrap();
+            assert!(matches!(cli.format, Format::Json));
+            assert!(matches!(
+                cli.command,
+                Command::Player(PlayerCommand::List(_))
+            ));
+        }
+
+        let cli = Cli::try_parse_from(["npipe-admin", "tunnel", "list", "--all"]).unwrap();
+        assert!(matches!(cli.format, Format::Table));
+        assert!(matches!(
+            cli.command,
+            Command::Tunnel(TunnelCommand::List(ListArgs { all: true, .. }))
+        ));
+        assert!(Cli::try_parse_from(["npipe-admin", "player", "list", "--format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn list_from_mock_server() {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        server.with_store(|store| {
+            store.add_player("alice", "pwd");
+            store.add_player("bob", "pwd");
+        });
+        let output = run_cli(
+            &server,
+            &[
+                "tunnel",
+                "add",
+                "--source",
+                "0.0.0.0:8080",
+                "--endpoint",
+                "127.0.0.1:80",
+                "--description",
+                "web",
+            ],
+        );
+        assert_eq!(output, "ok\n");
+
+        let output = run_cli(&server, &["player", "list"]);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "id  username  password  online");
+        assert_eq!(lines[1], "1   alice     pwd       false");
+
+        let output = run_cli(
+            &server,
+            &[
+                "--format",
+                "json",
+                "player",
+                "list",
+                "--all",
+                "--page-size",
+                "1",
+            ],
+        );
+        let players: Vec<proto::PlayerListItem> = serde_json::from_str(&output).unwrap();
+        let names: Vec<&str> = players.iter().map(|x| x.username.as_str()).collect();
+        assert_eq!(names, ["alice", "bob"]);
+
+        let output = run_cli(&server, &["tunnel", "list"]);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("id  type  source"));
+        assert!(lines[1].contains("0.0.0.0:8080") && lines[1].ends_with("web"));
+
+        let output = run_cli(&server, &["tunnel", "list", "--format", "json"]);
+        let tunnels: Vec<proto::TunnelListItem> = serde_json::from_str(&output).unwrap();
+        assert_eq!(tunnels.len(), 1);
+        assert_eq!(tunnels[0].endpoint, "127.0.0.1:80");
+    }
+}
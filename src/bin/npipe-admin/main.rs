@@ -0,0 +1,15 @@
+#![warn(clippy::all, rust_2018_idioms)]
+
+//! npipe 命令行管理工具，和网页端共用 `proto` 与 `api`，方便在脚本/定时任务中使用。
+
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> std::process::ExitCode {
+    cli::run()
+}
+
+// 命令行工具没有 web 版本
+#[cfg(target_arch = "wasm32")]
+fn main() {}
@@ -0,0 +1,108 @@
+#![warn(clippy::all, rust_2018_idioms)]
+
+//! 独立运行的模拟 npipe 后台，用于在没有真实服务器时调试网页端/命令行工具。
+
+use clap::{Parser, ValueEnum};
+use npipe_web::mock::{Fault, MockServer};
+use npipe_web::proto;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(
+    name = "npipe-mock",
+    about = "In-memory npipe api server for offline testing"
+)]
+struct Cli {
+    #[arg(long, default_value = "127.0.0.1:8120")]
+    listen: String,
+    #[arg(long, default_value = "admin")]
+    username: String,
+    #[arg(long, default_value = "admin")]
+    password: String,
+    /// Number of sample players to create
+    #[arg(long, default_value_t = 0)]
+    players: usize,
+    /// Number of sample tunnels to create
+    #[arg(long, default_value_t = 0)]
+    tunnels: usize,
+    /// Delay every response by this many milliseconds
+    #[arg(long)]
+    delay_ms: Option<u64>,
+    /// Fail every request
+    #[arg(long, value_enum)]
+    fault: Option<FaultArg>,
+    /// Toggle the next sample player online/offline every this many seconds
+    #[arg(long)]
+    flap_secs: Option<u64>,
+    /// Serve without the `events` push endpoint, like older servers
+    #[arg(long)]
+    no_push: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FaultArg {
+    /// HTTP 401
+    Unauthorized,
+    /// code 10086
+    SessionExpired,
+}
+
+fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
+
+    let server = MockServer::start(&cli.listen, &cli.username, &cli.password)?;
+    server.with_store(|store| {
+        for i in 0..cli.players {
+            store.add_player(&format!("player{}", i + 1), "123456");
+        }
+        for i in 0..cli.tunnels {
+            store.add_tunnel(proto::TunnelAddReq {
+                source: format!("0.0.0.0:{}", 10000 + i),
+                endpoint: format!("127.0.0.1:{}", 20000 + i),
+                enabled: 1,
+                sender: 0,
+                receiver: 0,
+                description: format!("tunnel{}", i + 1),
+                tunnel_type: 0,
+                password: String::new(),
+                username: String::new(),
+                is_compressed: 0,
+                encryption_method: proto::TUNNEL_ENCRYPTION_METHOD[0].to_string(),
+                custom_mapping: HashMap::new(),
+            });
+        }
+    });
+
+    if let Some(delay_ms) = cli.delay_ms {
+        server.inject(None, Fault::Delay(Duration::from_millis(delay_ms)));
+    }
+    match cli.fault {
+        Some(FaultArg::Unauthorized) => server.inject(None, Fault::Unauthorized),
+        Some(FaultArg::SessionExpired) => server.inject(None, Fault::SessionExpired),
+        None => {}
+    }
+
+    if cli.no_push {
+        server.set_push_support(false);
+    }
+
+    println!("npipe mock api listening on {}", server.api_url());
+    let Some(flap_secs) = cli.flap_secs else {
+        loop {
+            std::thread::park();
+        }
+    };
+    // 依次切换玩家的在线状态，通过推送接口通知客户端
+    for index in 0.. {
+        std::thread::sleep(Duration::from_secs(flap_secs));
+        let player = server.with_store(|store| {
+            let count = store.players.len();
+            (count > 0).then(|| store.players[index % count].clone())
+        });
+        if let Some(player) = player {
+            server.set_online(player.id, !player.online);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,397 @@
+//! 玩家和隧道配置的导出/导入文档
+//!
+//! 玩家按用户名匹配，隧道按 `source` 和 `receiver` 匹配，
+//! 隧道中的 sender/receiver 保存为用户名，便于迁移到其他服务器。
+
+use crate::proto::{PlayerListItem, TunnelListItem};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// 当前文档版本
+pub const CONFIG_VERSION: u32 = 1;
+
+/// 表示服务器(id 为 0)的玩家名
+pub const SERVER_PLAYER: &str = "server";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PlayerConfig {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct TunnelConfig {
+    pub source: String,
+    #[serde(default)]
+    pub endpoint: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // 玩家用户名，`server` 表示服务器，找不到的玩家写作 `#id`，导入时跳过
+    pub sender: String,
+    pub receiver: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tunnel_type: u32,
+    #[serde(default)]
+    pub password: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub is_compressed: bool,
+    #[serde(default = "default_encryption_method")]
+    pub encryption_method: String,
+    #[serde(default)]
+    pub custom_mapping: BTreeMap<String, String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_encryption_method() -> String {
+    "None".to_string()
+}
+
+/// 导出的配置文档
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ConfigDocument {
+    pub version: u32,
+    #[serde(default)]
+    pub players: Vec<PlayerConfig>,
+    #[serde(default)]
+    pub tunnels: Vec<TunnelConfig>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ConfigFormat::Json => "json",
+            ConfigFormat::Toml => "toml",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ConfigError {
+    Json(String),
+    Toml(String),
+    UnsupportedVersion(u32),
+    UnknownPlayer(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Json(err) => write!(f, "json: {}", err),
+            ConfigError::Toml(err) => write!(f, "toml: {}", err),
+            ConfigError::UnsupportedVersion(version) => {
+                write!(f, "unsupported config version {}", version)
+            }
+            ConfigError::UnknownPlayer(name) => write!(f, "unknown player '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ConfigDocument {
+    pub fn from_live(players: &[PlayerListItem], tunnels: &[TunnelListItem]) -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            players: players
+                .iter()
+                .map(|x| PlayerConfig {
+                    username: x.username.clone(),
+                    password: x.password.clone(),
+                })
+                .collect(),
+            tunnels: tunnels
+                .iter()
+                .map(|x| TunnelConfig::from_item(x, players))
+                .collect(),
+        }
+    }
+
+    pub fn to_string(&self, format: ConfigFormat) -> Result<String, ConfigError> {
+        match format {
+            ConfigFormat::Json => {
+                serde_json::to_string_pretty(self).map_err(|err| ConfigError::Json(err.to_string()))
+            }
+            ConfigFormat::Toml => {
+                toml::to_string_pretty(self).map_err(|err| ConfigError::Toml(err.to_string()))
+            }
+        }
+    }
+
+    /// 以 `{` 开头的按 JSON 解析，否则按 TOML 解析
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let document: Self = if text.trim_start().starts_with('{') {
+            serde_json::from_str(text).map_err(|err| ConfigError::Json(err.to_string()))?
+        } else {
+            toml::from_str(text).map_err(|err| ConfigError::Toml(err.to_string()))?
+        };
+        if document.version == 0 || document.version > CONFIG_VERSION {
+            return Err(ConfigError::UnsupportedVersion(document.version));
+        }
+        Ok(document)
+    }
+}
+
+pub(crate) fn player_to_name(players: &[PlayerListItem], id: u32) -> String {
+    if id == 0 {
+        return SERVER_PLAYER.to_string();
+    }
+    match players.iter().find(|x| x.id == id) {
+        Some(player) => player.username.clone(),
+        None => format!("#{}", id),
+    }
+}
+
+/// `#id` 是导出时来源服务器上的 id，在其他服务器上没有意义，同样视为未知玩家
+pub(crate) fn name_to_player(players: &[PlayerListItem], name: &str) -> Result<u32, ConfigError> {
+    if name == SERVER_PLAYER {
+        return Ok(0);
+    }
+    players
+        .iter()
+        .find(|x| x.username == name)
+        .map(|x| x.id)
+        .ok_or_else(|| ConfigError::UnknownPlayer(name.to_string()))
+}
+
+impl TunnelConfig {
+    pub fn from_item(item: &TunnelListItem, players: &[PlayerListItem]) -> Self {
+        Self {
+            source: item.source.clone(),
+            endpoint: item.endpoint.clone(),
+            enabled: item.enabled,
+            sender: player_to_name(players, item.sender),
+            receiver: player_to_name(players, item.receiver),
+            description: item.description.clone(),
+            tunnel_type: item.tunnel_type,
+            password: item.password.clone(),
+            username: item.username.clone(),
+            is_compressed: item.is_compressed,
+            encryption_method: item.encryption_method.clone(),
+            custom_mapping: item
+                .custom_mapping
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        }
+    }
+
+    /// 按用户名找到 sender/receiver 的 id
+    pub fn to_item(
+        &self,
+        id: u32,
+        players: &[PlayerListItem],
+    ) -> Result<TunnelListItem, ConfigError> {
+        Ok(TunnelListItem {
+            id,
+            source: self.source.clone(),
+            endpoint: self.endpoint.clone(),
+            enabled: self.enabled,
+            sender: name_to_player(players, &self.sender)?,
+            receiver: name_to_player(players, &self.receiver)?,
+            description: self.description.clone(),
+            tunnel_type: self.tunnel_type,
+            password: self.password.clone(),
+            username: self.username.clone(),
+            is_compressed: self.is_compressed,
+            encryption_method: self.encryption_method.clone(),
+            custom_mapping: self
+                .custom_mapping
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect::<HashMap<_, _>>(),
+        })
+    }
+
+    fn key(&self) -> (&str, &str) {
+        (&self.source, &self.receiver)
+    }
+}
+
+/// 文档与服务器数据的差异
+#[derive(Default, Debug)]
+pub struct ConfigDiff {
+    pub add_players: Vec<PlayerConfig>,
+    // 密码改变的玩家，已经替换为新密码
+    pub update_players: Vec<PlayerListItem>,
+    pub remove_players: Vec<PlayerListItem>,
+    pub add_tunnels: Vec<TunnelConfig>,
+    // 服务器上的隧道 id 和文档中的新配置
+    pub update_tunnels: Vec<(u32, TunnelConfig)>,
+    pub remove_tunnels: Vec<TunnelListItem>,
+}
+
+impl ConfigDiff {
+    pub fn new(
+        document: &ConfigDocument,
+        players: &[PlayerListItem],
+        tunnels: &[TunnelListItem],
+    ) -> Self {
+        let mut diff = Self::default();
+
+        for config in &document.players {
+            match players.iter().find(|x| x.username == config.username) {
+                Some(player) if player.password != config.password => {
+                    let mut player = player.clone();
+                    player.password = config.password.clone();
+                    diff.update_players.push(player);
+                }
+                Some(_) => {}
+                None => diff.add_players.push(config.clone()),
+            }
+        }
+        diff.remove_players = players
+            .iter()
+            .filter(|x| !document.players.iter().any(|y| y.username == x.username))
+            .cloned()
+            .collect();
+
+        let live: Vec<(u32, TunnelConfig)> = tunnels
+            .iter()
+            .map(|x| (x.id, TunnelConfig::from_item(x, players)))
+            .collect();
+        for config in &document.tunnels {
+            match live.iter().find(|(_, x)| x.key() == config.key()) {
+                Some((id, current)) if current != config => {
+                    diff.update_tunnels.push((*id, config.clone()));
+                }
+                Some(_) => {}
+                None => diff.add_tunnels.push(config.clone()),
+            }
+        }
+        diff.remove_tunnels = tunnels
+            .iter()
+            .zip(&live)
+            .filter(|(_, (_, x))| !document.tunnels.iter().any(|y| y.key() == x.key()))
+            .map(|(x, _)| x.clone())
+            .collect();
+
+        diff
+    }
+
+    pub fn is_empty(&self, with_removes: bool) -> bool {
+        self.add_players.is_empty()
+            && self.update_players.is_empty()
+            && self.add_tunnels.is_empty()
+            && self.update_tunnels.is_empty()
+            && (!with_removes || (self.remove_players.is_empty() && self.remove_tunnels.is_empty()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(id: u32, username: &str, password: &str) -> PlayerListItem {
+        PlayerListItem {
+            id,
+            username: username.into(),
+            password: password.into(),
+            online: false,
+        }
+    }
+
+    fn tunnel(id: u32, source: &str, sender: u32, receiver: u32) -> TunnelListItem {
+        TunnelListItem {
+            id,
+            source: source.into(),
+            endpoint: "127.0.0.1:80".into(),
+            enabled: true,
+            sender,
+            receiver,
+            description: String::new(),
+            tunnel_type: 0,
+            password: String::new(),
+            username: String::new(),
+            is_compressed: false,
+            encryption_method: "None".into(),
+            custom_mapping: HashMap::from([("a".into(), "1".into())]),
+        }
+    }
+
+    #[test]
+    fn round_trip_json_and_toml() {
+        let players = [player(1, "alice", "1")];
+        let tunnels = [tunnel(5, "0.0.0.0:80", 1, 0), tunnel(6, "0.0.0.0:81", 9, 1)];
+        let document = ConfigDocument::from_live(&players, &tunnels);
+        assert_eq!(document.tunnels[0].sender, "alice");
+        assert_eq!(document.tunnels[0].receiver, SERVER_PLAYER);
+        assert_eq!(document.tunnels[1].sender, "#9");
+
+        for format in [ConfigFormat::Json, ConfigFormat::Toml] {
+            let text = document.to_string(format).unwrap();
+            assert_eq!(ConfigDocument::parse(&text).unwrap(), document);
+        }
+        // 来源服务器上的 id 不能直接使用
+        assert_eq!(
+            document.tunnels[1].to_item(6, &players),
+            Err(ConfigError::UnknownPlayer("#9".into()))
+        );
+        let mut config = document.tunnels[1].clone();
+        config.sender = "alice".into();
+        let mut item = tunnels[1].clone();
+        item.sender = 1;
+        assert_eq!(config.to_item(6, &players), Ok(item));
+    }
+
+    #[test]
+    fn rejects_unknown_version_and_player() {
+        assert_eq!(
+            ConfigDocument::parse("version = 2"),
+            Err(ConfigError::UnsupportedVersion(2))
+        );
+        let document = ConfigDocument::parse("version = 1").unwrap();
+        assert!(document.players.is_empty());
+
+        let mut config = TunnelConfig::from_item(&tunnel(1, "0.0.0.0:80", 0, 0), &[]);
+        config.sender = "bob".into();
+        assert_eq!(
+            config.to_item(0, &[]),
+            Err(ConfigError::UnknownPlayer("bob".into()))
+        );
+    }
+
+    #[test]
+    fn diff_against_live() {
+        let players = [player(1, "alice", "1"), player(2, "bob", "2")];
+        let tunnels = [
+            tunnel(1, "0.0.0.0:80", 1, 0),
+            tunnel(2, "0.0.0.0:81", 0, 0),
+            tunnel(3, "0.0.0.0:82", 0, 0),
+        ];
+        let mut document = ConfigDocument::from_live(&players, &tunnels);
+        document.players.remove(1);
+        document.players[0].password = "new".into();
+        document.players.push(PlayerConfig {
+            username: "carol".into(),
+            password: "3".into(),
+        });
+        document.tunnels[0].description = "changed".into();
+        document.tunnels.remove(1);
+        document.tunnels[1].receiver = "alice".into();
+
+        let diff = ConfigDiff::new(&document, &players, &tunnels);
+        assert_eq!(diff.add_players[0].username, "carol");
+        assert_eq!(diff.update_players, [player(1, "alice", "new")]);
+        assert_eq!(diff.remove_players, [players[1].clone()]);
+        // receiver 改变后视为新的隧道
+        assert_eq!(diff.add_tunnels, [document.tunnels[1].clone()]);
+        assert_eq!(diff.update_tunnels, [(1, document.tunnels[0].clone())]);
+        let removed: Vec<u32> = diff.remove_tunnels.iter().map(|x| x.id).collect();
+        assert_eq!(removed, [2, 3]);
+        assert!(!diff.is_empty(false));
+    }
+}
@@ -0,0 +1,405 @@
+//! 本地版本的 cookie 管理(web 端由浏览器管理)。
+//!
+//! 按 Set-Cookie 的 Max-Age/Expires、Path、Domain 保存 cookie，任何回复中的
+//! Set-Cookie 都会更新，过期的 cookie 不再发送。
+
+use serde::{Deserialize, Serialize};
+
+/// 服务器登录会话的 cookie 名称
+pub static SESSION_COOKIE: &str = "auth-id";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// 没有 Domain 属性时只发送给设置它的主机
+    pub domain: String,
+    pub host_only: bool,
+    pub path: String,
+    pub secure: bool,
+    /// 过期时间(unix 秒)，None 表示会话 cookie
+    pub expires: Option<u64>,
+}
+
+impl Cookie {
+    /// 解析一个 Set-Cookie，`url` 为请求的地址
+    pub fn parse(header: &str, url: &str, now: u64) -> Option<Cookie> {
+        let (host, request_path) = split_url(url);
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(&request_path),
+            secure: false,
+            expires: None,
+        };
+        let mut max_age = None;
+        for attribute in parts {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+            match key.to_ascii_lowercase().as_str() {
+                "max-age" => {
+                    if let Ok(secs) = value.parse::<i64>() {
+                        max_age = Some(secs);
+                    }
+                }
+                "expires" => {
+                    if let Some(time) = parse_http_date(value) {
+                        cookie.expires = Some(time);
+                    }
+                }
+                "domain" => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                    if !domain.is_empty() {
+                        // 不能设置其他域名的 cookie
+                        if !domain_match(&host, &domain) {
+                            return None;
+                        }
+                        cookie.domain = domain;
+                        cookie.host_only = false;
+                    }
+                }
+                "path" => {
+                    if value.starts_with('/') {
+                        cookie.path = value.to_string();
+                    }
+                }
+                "secure" => cookie.secure = true,
+                _ => {}
+            }
+        }
+        // Max-Age 优先于 Expires
+        if let Some(secs) = max_age {
+            cookie.expires = Some(if secs <= 0 {
+                0
+            } else {
+                now.saturating_add(secs as u64)
+            });
+        }
+        Some(cookie)
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires.map_or(false, |x| x <= now)
+    }
+
+    /// 请求这个地址时是否发送
+    fn matches(&self, url: &str) -> bool {
+        let (host, path) = split_url(url);
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(&host, &self.domain)
+        };
+        domain_ok && path_match(&path, &self.path) && (!self.secure || url.starts_with("https:"))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    /// 解析回复中的 Set-Cookie 并保存
+    pub fn from_response<'a>(
+        url: &str,
+        set_cookies: impl IntoIterator<Item = &'a str>,
+        now: u64,
+    ) -> Self {
+        let mut jar = Self::default();
+        jar.store(url, set_cookies, now);
+        jar
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    /// 保存回复中的 Set-Cookie，同名的 cookie 被替换，已过期的被删除
+    pub fn store<'a>(
+        &mut self,
+        url: &str,
+        set_cookies: impl IntoIterator<Item = &'a str>,
+        now: u64,
+    ) {
+        for header in set_cookies {
+            let Some(cookie) = Cookie::parse(header, url, now) else {
+                continue;
+            };
+            self.cookies.retain(|x| {
+                x.name != cookie.name || x.domain != cookie.domain || x.path != cookie.path
+            });
+            if !cookie.is_expired(now) {
+                self.cookies.push(cookie);
+            }
+        }
+    }
+
+    /// 合并另一个 jar 中的 cookie，用于重新登录后更新会话
+    pub fn merge(&mut self, other: CookieJar) {
+        for cookie in other.cookies {
+            self.cookies.retain(|x| {
+                x.name != cookie.name || x.domain != cookie.domain || x.path != cookie.path
+            });
+            self.cookies.push(cookie);
+        }
+    }
+
+    pub fn remove_expired(&mut self, now: u64) {
+        self.cookies.retain(|x| !x.is_expired(now));
+    }
+
+    /// 请求这个地址时的 Cookie 头部，路径更长的 cookie 在前
+    pub fn header(&self, url: &str, now: u64) -> Option<String> {
+        let mut cookies: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|x| !x.is_expired(now) && x.matches(url))
+            .collect();
+        if cookies.is_empty() {
+            return None;
+        }
+        cookies.sort_by(|a, b| b.path.len().cmp(&a.path.len()));
+        let pairs: Vec<String> = cookies
+            .iter()
+            .map(|x| format!("{}={}", x.name, x.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Cookie> {
+        self.cookies.iter().find(|x| x.name == name)
+    }
+}
+
+/// 当前时间(unix 秒)
+pub fn unix_now() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        (js_sys::Date::now() / 1000.0) as u64
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |x| x.as_secs())
+    }
+}
+
+/// 地址中的主机名(小写，不含端口)和路径
+fn split_url(url: &str) -> (String, String) {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let (authority, path) = match rest.find(['/', '?', '#']) {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let path = if path.starts_with('/') { path } else { "/" };
+
+    let authority = authority.rsplit('@').next().unwrap_or_default();
+    let host = match authority.strip_prefix('[') {
+        // IPv6
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => authority.split(':').next().unwrap_or_default(),
+    };
+    (host.to_ascii_lowercase(), path.to_string())
+}
+
+/// 没有 Path 属性时使用请求路径的目录
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => path[..index].to_string(),
+    }
+}
+
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain || (host.ends_with(domain) && host[..host.len() - domain.len()].ends_with('.'))
+}
+
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || (path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || path[cookie_path.len()..].starts_with('/')))
+}
+
+/// Expires 中允许的最大年份，超出时忽略这个属性
+const MAX_YEAR: u64 = 9999;
+
+/// 宽松地解析 Expires 中的日期，例如 `Wed, 21 Oct 2015 07:28:00 GMT`，
+/// 日期来自服务器，超出范围时返回 None
+fn parse_http_date(text: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let (mut time, mut day, mut month, mut year) = (None, None, None, None);
+    for token in text
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != ':')
+        .filter(|x| !x.is_empty())
+    {
+        if time.is_none() && token.contains(':') {
+            let parts: Vec<u64> = token.split(':').filter_map(|x| x.parse().ok()).collect();
+            if let [hour, minute, second] = parts[..] {
+                if hour >= 24 || minute >= 60 || second >= 60 {
+                    return None;
+                }
+                time = Some(hour * 3600 + minute * 60 + second);
+            }
+        } else if let Ok(number) = token.parse::<u64>() {
+            if day.is_none() && token.len() <= 2 {
+                day = Some(number);
+            } else if year.is_none() {
+                year = Some(match number {
+                    0..=69 => number + 2000,
+                    70..=99 => number + 1900,
+                    _ => number,
+                });
+            }
+        } else if month.is_none() && token.len() >= 3 {
+            let name = token[..3].to_ascii_lowercase();
+            month = MONTHS.iter().position(|x| *x == name).map(|x| x as u64 + 1);
+        }
+    }
+    let (day, month, year) = (day?, month?, year?);
+    if !(1..=31).contains(&day) || !(1..=12).contains(&month) || !(1970..=MAX_YEAR).contains(&year)
+    {
+        return None;
+    }
+    days_from_civil(year, month, day)
+        .checked_mul(86400)?
+        .checked_add(time?)
+}
+
+/// 公历日期距 1970-01-01 的天数
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static URL: &str = "http://127.0.0.1:8120/api/login";
+
+    #[test]
+    fn parse_attributes() {
+        let cookie = Cookie::parse(
+            "auth-id=a%3D; HttpOnly; SameSite=Lax; Path=/; Max-Age=3600",
+            URL,
+            1000,
+        )
+        .unwrap();
+        assert_eq!(cookie.name, "auth-id");
+        assert_eq!(cookie.value, "a%3D");
+        assert_eq!(cookie.domain, "127.0.0.1");
+        assert_eq!(cookie.path, "/");
+        assert_eq!(cookie.expires, Some(4600));
+
+        let cookie = Cookie::parse("a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT", URL, 0).unwrap();
+        assert_eq!(cookie.expires, Some(1445412480));
+        assert_eq!(cookie.path, "/api");
+        // Max-Age 优先
+        let cookie = Cookie::parse("a=1; Max-Age=10; Expires=Wed, 21 Oct 2015", URL, 5).unwrap();
+        assert_eq!(cookie.expires, Some(15));
+        // 不能设置其他域名
+        assert_eq!(Cookie::parse("a=1; Domain=example.com", URL, 0), None);
+    }
+
+    #[test]
+    fn jar_expiry_and_matching() {
+        let mut jar = CookieJar::default();
+        jar.store(
+            "https://www.example.com/api/login",
+            [
+                "auth-id=1; Path=/; Max-Age=60",
+                "api=2; Path=/api",
+                "other=3; Path=/other",
+                "shared=4; Domain=example.com; Path=/; Secure",
+            ],
+            100,
+        );
+        assert_eq!(
+            jar.header("https://www.example.com/api/player_list", 100)
+                .as_deref(),
+            Some("api=2; auth-id=1; shared=4")
+        );
+        assert_eq!(
+            jar.header("http://a.example.com/apiv2", 100).as_deref(),
+            None
+        );
+        assert_eq!(jar.header("http://example.com:81/", 100), None);
+        assert_eq!(
+            jar.header("https://example.com/", 100).as_deref(),
+            Some("shared=4")
+        );
+
+        // 过期后不再发送
+        assert_eq!(
+            jar.header("https://www.example.com/", 160).as_deref(),
+            Some("shared=4")
+        );
+
+        // 同名 cookie 被替换，Max-Age=0 删除
+        jar.store(
+            "https://www.example.com/",
+            ["auth-id=5; Path=/; Max-Age=60"],
+            100,
+        );
+        assert_eq!(jar.get("auth-id").unwrap().value, "5");
+        jar.store(
+            "https://www.example.com/",
+            ["auth-id=; Path=/; Max-Age=0"],
+            100,
+        );
+        assert_eq!(jar.get("auth-id"), None);
+    }
+
+    #[test]
+    fn http_dates() {
+        assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+        assert_eq!(
+            parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"),
+            Some(784111777)
+        );
+        assert_eq!(
+            parse_http_date("Tue, 29 Feb 2028 12:00:00 GMT"),
+            Some(1835438400)
+        );
+        assert_eq!(parse_http_date("not a date"), None);
+        // 超出范围的部分不会溢出
+        assert_eq!(
+            parse_http_date("Wed, 21 Oct 2015 18446744073709551615:0:0 GMT"),
+            None
+        );
+        assert_eq!(parse_http_date("Wed, 21 Oct 2015 24:00:00 GMT"), None);
+        assert_eq!(parse_http_date("Wed, 21 Oct 2015 07:60:00 GMT"), None);
+        assert_eq!(
+            parse_http_date("Wed, 21 Oct 18446744073709551615 07:28:00 GMT"),
+            None
+        );
+        assert_eq!(
+            parse_http_date("Fri, 31 Dec 9999 23:59:59 GMT"),
+            Some(253402300799)
+        );
+        let cookie = Cookie::parse("a=1; Expires=Wed, 21 Oct 99999999999 07:28:00", URL, 5);
+        assert_eq!(cookie.unwrap().expires, None);
+    }
+}
@@ -1,8 +1,19 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
+pub mod api;
 mod app;
-mod proto;
+pub mod auth;
+pub mod config;
+pub mod cookie;
+#[cfg(all(not(target_arch = "wasm32"), any(test, feature = "mock")))]
+pub mod mock;
+pub mod profile;
+pub mod proto;
+pub mod push;
 mod render;
-mod resource;
+pub mod secret;
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod ui_tests;
+pub mod validation;
 
 pub use app::TemplateApp;
@@ -0,0 +1,1081 @@
+//! 模拟的 npipe 后台，数据保存在内存中，用于离线测试客户端。
+//!
+//! 除了正常的接口实现外还可以注入故障(401、code 10086、延迟回复)，
+//! 用来测试客户端的登出与加载状态。`events` 接口以 SSE 格式推送隧道变化和
+//! [`MockServer::set_online`] 模拟的玩家上下线，流量统计由隧道 id 和时间生成。
+
+use crate::proto;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tiny_http::{Header, Request, Response, Server};
+
+/// 服务器下发的 cookie 名称
+const AUTH_COOKIE: &str = "auth-id";
+
+/// 推送连接上没有事件时发送注释的间隔，用于发现断开的客户端
+const KEEP_ALIVE: Duration = Duration::from_secs(1);
+
+/// 流量曲线中点的数量
+const SERIES_POINTS: u64 = 60;
+
+/// 注入的故障
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// 回复 HTTP 401
+    Unauthorized,
+    /// 回复 code 10086
+    SessionExpired,
+    /// 延迟回复
+    Delay(Duration),
+    /// 生成回复后延迟发送，回复中是收到请求时的数据
+    HoldReply(Duration),
+    /// 回复 code -1
+    Error,
+}
+
+/// 内存中的数据
+#[derive(Default)]
+pub struct Store {
+    pub players: Vec<proto::PlayerListItem>,
+    pub tunnels: Vec<proto::TunnelListItem>,
+    next_id: u32,
+}
+
+impl Store {
+    fn next_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// 添加一个玩家，返回玩家id
+    pub fn add_player(&mut self, username: &str, password: &str) -> u32 {
+        let id = self.next_id();
+        self.players.push(proto::PlayerListItem {
+            id,
+            username: username.to_string(),
+            password: password.to_string(),
+            online: false,
+        });
+        id
+    }
+
+    /// 添加一个通道，返回通道id
+    pub fn add_tunnel(&mut self, req: proto::TunnelAddReq) -> u32 {
+        let id = self.next_id();
+        self.tunnels.push(proto::TunnelListItem {
+            id,
+            source: req.source,
+            endpoint: req.endpoint,
+            enabled: req.enabled != 0,
+            sender: req.sender,
+            receiver: req.receiver,
+            description: req.description,
+            tunnel_type: req.tunnel_type,
+            password: req.password,
+            username: req.username,
+            is_compressed: req.is_compressed != 0,
+            encryption_method: req.encryption_method,
+            custom_mapping: req.custom_mapping,
+        });
+        id
+    }
+}
+
+struct State {
+    username: String,
+    password: String,
+    sessions: Vec<String>,
+    next_session: u32,
+    // 会话 cookie 的 Max-Age(秒)，每次请求后重新计算
+    session_max_age: u64,
+    api_keys: Vec<String>,
+    faults: Vec<(Option<String>, Fault)>,
+    // 是否支持列表过滤/排序参数
+    list_query: bool,
+    // 是否提供 `events` 推送接口
+    push: bool,
+    subscribers: Vec<Sender<proto::PushEvent>>,
+    // 还没有断开的推送连接数
+    open_streams: Arc<AtomicUsize>,
+    // 每个接口收到的请求数
+    requests: HashMap<String, usize>,
+    store: Store,
+}
+
+impl State {
+    fn faults(&self, path: &str) -> Vec<Fault> {
+        self.faults
+            .iter()
+            .filter(|(x, _)| x.as_deref().map_or(true, |x| x == path))
+            .map(|(_, fault)| *fault)
+            .collect()
+    }
+
+    fn broadcast(&mut self, event: proto::PushEvent) {
+        self.subscribers.retain(|x| x.send(event.clone()).is_ok());
+    }
+}
+
+/// 后台线程中运行的模拟服务器，drop 时停止
+pub struct MockServer {
+    addr: SocketAddr,
+    server: Arc<Server>,
+    state: Arc<Mutex<State>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// 在指定地址启动，端口为0时自动分配
+    pub fn start(addr: &str, username: &str, password: &str) -> std::io::Result<Self> {
+        let server = Server::http(addr).map_err(std::io::Error::other)?;
+        let server = Arc::new(server);
+        let addr = server
+            .server_addr()
+            .to_ip()
+            .ok_or_else(|| std::io::Error::other("not an ip address"))?;
+
+        let state = Arc::new(Mutex::new(State {
+            username: username.to_string(),
+            password: password.to_string(),
+            sessions: Vec::new(),
+            next_session: 0,
+            session_max_age: 3600,
+            api_keys: Vec::new(),
+            faults: Vec::new(),
+            list_query: true,
+            push: true,
+            subscribers: Vec::new(),
+            open_streams: Arc::default(),
+            requests: HashMap::new(),
+            store: Store::default(),
+        }));
+
+        let thread = {
+            let server = server.clone();
+            let state = state.clone();
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    let state = state.clone();
+                    // 每个请求单独一个线程，延迟回复不阻塞其他请求
+                    std::thread::spawn(move || handle(&state, request));
+                }
+            })
+        };
+
+        Ok(Self {
+            addr,
+            server,
+            state,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// 客户端使用的 api url
+    pub fn api_url(&self) -> String {
+        format!("http://{}/api/", self.addr)
+    }
+
+    /// 注入故障，`path` 为 None 时对所有接口生效
+    pub fn inject(&self, path: Option<&str>, fault: Fault) {
+        self.state
+            .lock()
+            .unwrap()
+            .faults
+            .push((path.map(str::to_string), fault));
+    }
+
+    pub fn clear_faults(&self) {
+        self.state.lock().unwrap().faults.clear();
+    }
+
+    /// 模拟不支持列表过滤/排序参数的旧版本服务器
+    pub fn set_list_query_support(&self, enabled: bool) {
+        self.state.lock().unwrap().list_query = enabled;
+    }
+
+    /// 模拟没有推送接口的旧版本服务器
+    pub fn set_push_support(&self, enabled: bool) {
+        self.state.lock().unwrap().push = enabled;
+    }
+
+    /// 设置会话 cookie 的有效时间
+    pub fn set_session_max_age(&self, secs: u64) {
+        self.state.lock().unwrap().session_max_age = secs;
+    }
+
+    /// 添加可以使用的 API key
+    pub fn add_api_key(&self, key: &str) {
+        self.state.lock().unwrap().api_keys.push(key.to_string());
+    }
+
+    /// 所有会话和 token 失效，之后的请求回复 401
+    pub fn expire_sessions(&self) {
+        self.state.lock().unwrap().sessions.clear();
+    }
+
+    /// 向所有推送连接发送事件
+    pub fn push(&self, event: proto::PushEvent) {
+        self.state.lock().unwrap().broadcast(event);
+    }
+
+    /// 模拟玩家上线/下线并推送
+    pub fn set_online(&self, id: u32, online: bool) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(player) = state.store.players.iter_mut().find(|x| x.id == id) {
+            player.online = online;
+            state.broadcast(proto::PushEvent::PlayerOnline { id, online });
+        }
+    }
+
+    /// 接口收到的请求数
+    pub fn request_count(&self, path: &str) -> usize {
+        let state = self.state.lock().unwrap();
+        state.requests.get(path).copied().unwrap_or_default()
+    }
+
+    /// 还没有断开的推送连接数
+    pub fn open_streams(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.open_streams.load(Ordering::SeqCst)
+    }
+
+    /// 读写内存数据，直接修改不会推送事件
+    pub fn with_store<R>(&self, f: impl FnOnce(&mut Store) -> R) -> R {
+        f(&mut self.state.lock().unwrap().store)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        // 结束推送连接
+        self.state.lock().unwrap().subscribers.clear();
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn handle(state: &Mutex<State>, mut request: Request) {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    let cookie = request
+        .headers()
+        .iter()
+        .filter(|x| x.field.equiv("Cookie"))
+        .flat_map(|x| x.value.as_str().split(';'))
+        .filter_map(|x| x.trim().split_once('='))
+        .find(|(name, _)| *name == AUTH_COOKIE)
+        .map(|(_, value)| value.to_string());
+    let header = |name: &'static str| {
+        request
+            .headers()
+            .iter()
+            .find(|x| x.field.equiv(name))
+            .map(|x| x.value.to_string())
+    };
+    let bearer =
+        header("Authorization").and_then(|x| x.strip_prefix("Bearer ").map(str::to_string));
+    let api_key = header("X-Api-Key");
+
+    let mut body = Vec::new();
+    let _ = request.as_reader().read_to_end(&mut body);
+
+    let faults = {
+        let mut state = state.lock().unwrap();
+        *state.requests.entry(path.clone()).or_default() += 1;
+        state.faults(&path)
+    };
+    for fault in &faults {
+        if let Fault::Delay(delay) = fault {
+            std::thread::sleep(*delay);
+        }
+    }
+
+    let response = if faults.contains(&Fault::Unauthorized) {
+        Response::from_string("Unauthorized").with_status_code(401)
+    } else if faults.contains(&Fault::SessionExpired) {
+        json_response(&general(10086, "session expired"))
+    } else if faults.contains(&Fault::Error) {
+        json_response(&general(-1, "injected error"))
+    } else {
+        let mut state = state.lock().unwrap();
+        let session = [cookie.clone(), bearer]
+            .into_iter()
+            .flatten()
+            .find(|x| state.sessions.contains(x))
+            .or_else(|| api_key.filter(|x| state.api_keys.contains(x)));
+        if path == "login" {
+            login(&mut state, &body)
+        } else if path == "token" {
+            token(&mut state, &body)
+        } else if let Some(session) = session {
+            if path == "events" && state.push {
+                let (sender, receiver) = channel();
+                state.subscribers.push(sender);
+                let open_streams = state.open_streams.clone();
+                drop(state);
+                open_streams.fetch_add(1, Ordering::SeqCst);
+                stream_events(request, receiver);
+                open_streams.fetch_sub(1, Ordering::SeqCst);
+                return;
+            }
+            let max_age = state.session_max_age;
+            let response = dispatch(&mut state, &path, &body, &session);
+            // 登出时删除 cookie，其他请求延长会话
+            match cookie {
+                Some(cookie) if cookie == session => {
+                    let max_age = if path == "logout" { 0 } else { max_age };
+                    response.with_header(session_cookie(&session, max_age))
+                }
+                _ => response,
+            }
+        } else {
+            Response::from_string("Unauthorized").with_status_code(401)
+        }
+    };
+
+    for fault in &faults {
+        if let Fault::HoldReply(delay) = fault {
+            std::thread::sleep(*delay);
+        }
+    }
+    let _ = request.respond(response);
+}
+
+/// 以 SSE 格式持续下发事件，客户端断开或服务器停止后结束
+fn stream_events(request: Request, receiver: Receiver<proto::PushEvent>) {
+    let mut writer = request.into_writer();
+    let mut text = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                    Cache-Control: no-cache\r\nConnection: close\r\n\r\n"
+        .to_string();
+    loop {
+        if writer
+            .write_all(text.as_bytes())
+            .and_then(|_| writer.flush())
+            .is_err()
+        {
+            return;
+        }
+        text = match receiver.recv_timeout(KEEP_ALIVE) {
+            Ok(event) => format!("data: {}\n\n", serde_json::to_string(&event).unwrap()),
+            Err(RecvTimeoutError::Timeout) => ": ping\n\n".to_string(),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+    }
+}
+
+type MockResponse = Response<std::io::Cursor<Vec<u8>>>;
+
+fn general(code: i32, msg: &str) -> proto::GeneralResponse {
+    proto::GeneralResponse {
+        code,
+        msg: msg.to_string(),
+    }
+}
+
+fn json_response(value: &impl Serialize) -> MockResponse {
+    Response::from_string(serde_json::to_string(value).unwrap())
+        .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap())
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, MockResponse> {
+    serde_json::from_slice(body)
+        .map_err(|err| Response::from_string(err.to_string()).with_status_code(400))
+}
+
+fn login(state: &mut State, body: &[u8]) -> MockResponse {
+    let req = match parse::<proto::LoginReq>(body) {
+        Ok(req) => req,
+        Err(response) => return response,
+    };
+    if req.username != state.username || req.password != state.password {
+        return json_response(&general(-1, "wrong username or password"));
+    }
+
+    state.next_session += 1;
+    let session = format!("session{}", state.next_session);
+    state.sessions.push(session.clone());
+
+    json_response(&general(0, "")).with_header(session_cookie(&session, state.session_max_age))
+}
+
+/// 用户名密码换取 Bearer token
+fn token(state: &mut State, body: &[u8]) -> MockResponse {
+    let req = match parse::<proto::LoginReq>(body) {
+        Ok(req) => req,
+        Err(response) => return response,
+    };
+    if req.username != state.username || req.password != state.password {
+        return json_response(&general(-1, "wrong username or password"));
+    }
+
+    state.next_session += 1;
+    let token = format!("token{}", state.next_session);
+    state.sessions.push(token.clone());
+    json_response(&proto::TokenResponse {
+        msg: String::new(),
+        code: 0,
+        token,
+    })
+}
+
+fn session_cookie(session: &str, max_age: u64) -> Header {
+    let cookie = format!(
+        "{}={}; HttpOnly; SameSite=Lax; Path=/; Max-Age={}",
+        AUTH_COOKIE, session, max_age
+    );
+    Header::from_bytes("Set-Cookie", cookie).unwrap()
+}
+
+fn dispatch(state: &mut State, path: &str, body: &[u8], session: &str) -> MockResponse {
+    let result = match path {
+        "logout" => {
+            state.sessions.retain(|x| x != session);
+            Ok(json_response(&general(0, "")))
+        }
+        "test_auth" => Ok(json_response(&general(0, ""))),
+        "player_list" => parse::<proto::PlayerListRequest>(body).map(|req| {
+            let mut players = state.store.players.clone();
+            if state.list_query {
+                req.apply_query(&mut players);
+            }
+            json_response(&proto::PlayerListResponse {
+                players: page(&players, req.page_number, req.page_size),
+                cur_page_number: req.page_number,
+                total_count: players.len(),
+                filtered: state.list_query,
+            })
+        }),
+        "add_player" => parse::<proto::PlayerAddReq>(body).map(|req| {
+            let store = &mut state.store;
+            if req.username.is_empty() {
+                json_response(&general(-1, "username is empty"))
+            } else if store.players.iter().any(|x| x.username == req.username) {
+                json_response(&general(-1, "username already exists"))
+            } else {
+                store.add_player(&req.username, &req.password);
+                json_response(&general(0, ""))
+            }
+        }),
+        "update_player" => parse::<proto::PlayerUpdateReq>(body).map(|req| {
+            match state.store.players.iter_mut().find(|x| x.id == req.id) {
+                Some(player) => {
+                    player.username = req.username;
+                    player.password = req.password;
+                    json_response(&general(0, ""))
+                }
+                None => json_response(&general(-1, "player not found")),
+            }
+        }),
+        "remove_player" => parse::<proto::PlayerRemoveReq>(body).map(|req| {
+            let players = &mut state.store.players;
+            let count = players.len();
+            players.retain(|x| x.id != req.id);
+            if players.len() == count {
+                json_response(&general(-1, "player not found"))
+            } else {
+                json_response(&general(0, ""))
+            }
+        }),
+        "tunnel_stats" => parse::<proto::TrafficStatsRequest>(body).map(|req| {
+            let now = unix_now();
+            let items = state
+                .store
+                .tunnels
+                .iter()
+                .map(|tunnel| {
+                    let name = if tunnel.description.is_empty() {
+                        tunnel.source.clone()
+                    } else {
+                        tunnel.description.clone()
+                    };
+                    let series = state
+                        .store
+                        .traffic(|x| x.id == tunnel.id, now, req.window_secs);
+                    traffic_stats(tunnel.id, name, &series)
+                })
+                .collect();
+            json_response(&proto::TrafficStatsResponse { now, items })
+        }),
+        "player_stats" => parse::<proto::TrafficStatsRequest>(body).map(|req| {
+            let now = unix_now();
+            let items = state
+                .store
+                .players
+                .iter()
+                .map(|player| {
+                    let series = state.store.traffic(
+                        |x| x.sender == player.id || x.receiver == player.id,
+                        now,
+                        req.window_secs,
+                    );
+                    traffic_stats(player.id, player.username.clone(), &series)
+                })
+                .collect();
+            json_response(&proto::TrafficStatsResponse { now, items })
+        }),
+        "traffic_series" => parse::<proto::TrafficSeriesRequest>(body).map(|req| {
+            let now = unix_now();
+            let points = state.store.traffic(
+                |x| {
+                    req.tunnel.map_or(true, |id| x.id == id)
+                        && req
+                            .player
+                            .map_or(true, |id| x.sender == id || x.receiver == id)
+                },
+                now,
+                req.window_secs,
+            );
+            json_response(&proto::TrafficSeriesResponse {
+                now,
+                step_secs: series_step(req.window_secs),
+                points,
+            })
+        }),
+        "tunnel_list" => parse::<proto::TunnelListRequest>(body).map(|req| {
+            let mut tunnels = state.store.tunnels.clone();
+            if state.list_query {
+                req.apply_query(&mut tunnels);
+            }
+            json_response(&proto::TunnelListResponse {
+                tunnels: page(&tunnels, req.page_number, req.page_size),
+                cur_page_number: req.page_number,
+                total_count: tunnels.len(),
+                filtered: state.list_query,
+            })
+        }),
+        "add_tunnel" => parse::<proto::TunnelAddReq>(body).map(|req| {
+            state.store.add_tunnel(req);
+            if let Some(tunnel) = state.store.tunnels.last().cloned() {
+                state.broadcast(proto::PushEvent::TunnelAdded { tunnel });
+            }
+            json_response(&general(0, ""))
+        }),
+        "update_tunnel" => parse::<proto::TunnelUpdateReq>(body).map(|req| {
+            match state.store.tunnels.iter_mut().find(|x| x.id == req.id) {
+                Some(tunnel) => {
+                    tunnel.source = req.source;
+                    tunnel.endpoint = req.endpoint;
+                    tunnel.enabled = req.enabled != 0;
+                    tunnel.sender = req.sender;
+                    tunnel.receiver = req.receiver;
+                    tunnel.description = req.description;
+                    tunnel.tunnel_type = req.tunnel_type;
+                    tunnel.password = req.password;
+                    tunnel.username = req.username;
+                    tunnel.is_compressed = req.is_compressed != 0;
+                    tunnel.encryption_method = req.encryption_method;
+                    tunnel.custom_mapping = req.custom_mapping;
+                    let tunnel = tunnel.clone();
+                    state.broadcast(proto::PushEvent::TunnelUpdated { tunnel });
+                    json_response(&general(0, ""))
+                }
+                None => json_response(&general(-1, "tunnel not found")),
+            }
+        }),
+        "remove_tunnel" => parse::<proto::TunnelRemoveReq>(body).map(|req| {
+            let tunnels = &mut state.store.tunnels;
+            let count = tunnels.len();
+            tunnels.retain(|x| x.id != req.id);
+            if tunnels.len() == count {
+                json_response(&general(-1, "tunnel not found"))
+            } else {
+                state.broadcast(proto::PushEvent::TunnelRemoved { id: req.id });
+                json_response(&general(0, ""))
+            }
+        }),
+        _ => Ok(Response::from_string("Not Found").with_status_code(404)),
+    };
+
+    result.unwrap_or_else(|response| response)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs())
+}
+
+fn series_step(window_secs: u64) -> u64 {
+    (window_secs / SERIES_POINTS).max(1)
+}
+
+/// 由 id 和时间段决定的伪随机数
+fn noise(id: u32, slot: u64) -> u64 {
+    let mut x = ((id as u64) << 32) ^ slot;
+    x = x.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    x ^= x >> 31;
+    x = x.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x ^ (x >> 29)
+}
+
+impl Store {
+    /// 模拟的流量曲线，合计满足条件的隧道，停用的隧道没有流量
+    fn traffic(
+        &self,
+        filter: impl Fn(&proto::TunnelListItem) -> bool,
+        now: u64,
+        window_secs: u64,
+    ) -> Vec<proto::TrafficPoint> {
+        let step = series_step(window_secs);
+        let start = (now / step).saturating_sub(SERIES_POINTS - 1) * step;
+        let tunnels: Vec<&proto::TunnelListItem> = self
+            .tunnels
+            .iter()
+            .filter(|x| x.enabled && filter(x))
+            .collect();
+        (0..SERIES_POINTS)
+            .map(|i| {
+                let time = start + i * step;
+                let mut point = proto::TrafficPoint {
+                    time,
+                    bytes_in: 0,
+                    bytes_out: 0,
+                    connections: 0,
+                };
+                for tunnel in &tunnels {
+                    let n = noise(tunnel.id, time / step);
+                    // 每个隧道的流量规模不同
+                    let scale = (tunnel.id as u64 % 4 + 1) * 1024;
+                    point.bytes_in += n % 256 * scale * step / 60;
+                    point.bytes_out += (n >> 8) % 256 * scale * step / 120;
+                    point.connections += ((n >> 16) % 4) as u32;
+                }
+                point
+            })
+            .collect()
+    }
+}
+
+/// 曲线合计为统计数据，连接数取最后一个点
+fn traffic_stats(id: u32, name: String, series: &[proto::TrafficPoint]) -> proto::TrafficStats {
+    proto::TrafficStats {
+        id,
+        name,
+        bytes_in: series.iter().map(|x| x.bytes_in).sum(),
+        bytes_out: series.iter().map(|x| x.bytes_out).sum(),
+        connections: series.last().map_or(0, |x| x.connections),
+        last_active: series
+            .iter()
+            .rev()
+            .find(|x| x.bytes_in + x.bytes_out > 0)
+            .map(|x| x.time),
+    }
+}
+
+/// 页码从0开始
+fn page<T: Clone>(items: &[T], page_number: usize, page_size: usize) -> Vec<T> {
+    items
+        .iter()
+        .skip(page_number * page_size)
+        .take(page_size)
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{ApiClient, ApiError};
+    use crate::auth::{AuthMode, Token};
+    use crate::cookie::{unix_now, SESSION_COOKIE};
+    use crate::push::{PushChannel, PushState};
+    use std::time::Instant;
+
+    fn login(server: &MockServer) -> ApiClient {
+        let req = proto::LoginReq {
+            username: "admin".into(),
+            password: "123".into(),
+        };
+        let session = ApiClient::new(server.api_url())
+            .login(AuthMode::Cookie, &req)
+            .block_and_take()
+            .unwrap();
+        ApiClient::new(server.api_url()).with_session(session.shared())
+    }
+
+    fn list_players(api: &ApiClient) -> proto::PlayerListResponse {
+        let req = proto::PlayerListRequest {
+            page_number: 0,
+            page_size: 20,
+            ..Default::default()
+        };
+        api.player_list(&req).block_and_take().unwrap()
+    }
+
+    #[test]
+    fn login_and_crud_players() {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        let api = login(&server);
+
+        let req = proto::PlayerAddReq {
+            username: "alice".into(),
+            password: "pwd".into(),
+        };
+        api.add_player(&req).block_and_take().unwrap();
+        let result = api.add_player(&req).block_and_take();
+        assert!(matches!(result, Err(ApiError::Server { code: -1, .. })));
+
+        let list = list_players(&api);
+        assert_eq!(list.total_count, 1);
+        assert_eq!(list.players[0].username, "alice");
+
+        let req = proto::PlayerRemoveReq {
+            id: list.players[0].id,
+        };
+        api.remove_player(&req).block_and_take().unwrap();
+        assert_eq!(list_players(&api).total_count, 0);
+    }
+
+    #[test]
+    fn all_players_loads_every_page() {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        server.with_store(|store| {
+            for i in 0..250 {
+                store.add_player(&format!("player{}", i), "1");
+            }
+        });
+        let api = login(&server);
+
+        let players = api.all_players().block_and_take().unwrap();
+        assert_eq!(players.len(), 250);
+        assert_eq!(players[249].username, "player249");
+        assert_eq!(server.request_count("player_list"), 3);
+    }
+
+    #[test]
+    fn wrong_password_is_server_error() {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        let req = proto::LoginReq {
+            username: "admin".into(),
+            password: "456".into(),
+        };
+        let result = ApiClient::new(server.api_url())
+            .login(AuthMode::Cookie, &req)
+            .block_and_take();
+        assert!(matches!(result, Err(ApiError::Server { code: -1, .. })));
+    }
+
+    #[test]
+    fn session_expiry() {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        let flag = Arc::new(Mutex::new(false));
+
+        // 未登录
+        let api = ApiClient::new(server.api_url()).with_session_flag(flag.clone());
+        let result = api.test_auth().block_and_take();
+        assert!(matches!(result, Err(ApiError::SessionExpired)));
+        assert!(*flag.lock().unwrap());
+
+        let api = login(&server);
+        api.test_auth().block_and_take().unwrap();
+
+        server.inject(Some("test_auth"), Fault::SessionExpired);
+        let result = api.test_auth().block_and_take();
+        assert!(matches!(result, Err(ApiError::SessionExpired)));
+        api.player_list(&proto::PlayerListRequest {
+            page_number: 0,
+            page_size: 20,
+            ..Default::default()
+        })
+        .block_and_take()
+        .unwrap();
+
+        server.clear_faults();
+        server.inject(None, Fault::Unauthorized);
+        let result = api.test_auth().block_and_take();
+        assert!(matches!(result, Err(ApiError::SessionExpired)));
+
+        server.clear_faults();
+        api.logout().block_and_take().unwrap();
+        let result = api.test_auth().block_and_take();
+        assert!(matches!(result, Err(ApiError::SessionExpired)));
+    }
+
+    #[test]
+    fn cookies_follow_set_cookie() {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        server.set_session_max_age(60);
+        let req = proto::LoginReq {
+            username: "admin".into(),
+            password: "123".into(),
+        };
+        let session = ApiClient::new(server.api_url())
+            .login(AuthMode::Cookie, &req)
+            .block_and_take()
+            .unwrap();
+        let expires = session.cookies.get(SESSION_COOKIE).unwrap().expires;
+        assert!(expires.unwrap() <= unix_now() + 60);
+
+        // 其他接口的 Set-Cookie 也会更新
+        let session = session.shared();
+        let api = ApiClient::new(server.api_url()).with_session(session.clone());
+        server.set_session_max_age(3600);
+        api.test_auth().block_and_take().unwrap();
+        let expires = session
+            .lock()
+            .unwrap()
+            .cookies
+            .get(SESSION_COOKIE)
+            .unwrap()
+            .expires;
+        assert!(expires.unwrap() > unix_now() + 60);
+
+        // 登出时服务器删除 cookie
+        api.logout().block_and_take().unwrap();
+        assert!(session.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn bearer_token_and_api_key() {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        let req = proto::LoginReq {
+            username: "admin".into(),
+            password: "123".into(),
+        };
+        let session = ApiClient::new(server.api_url())
+            .login(AuthMode::Bearer, &req)
+            .block_and_take()
+            .unwrap();
+        assert_eq!(session.token, Some(Token::Bearer("token1".into())));
+        assert!(session.cookies.is_empty());
+
+        let session = session.shared();
+        let flag = Arc::new(Mutex::new(false));
+        let api = ApiClient::new(server.api_url())
+            .with_session(session.clone())
+            .with_session_flag(flag.clone());
+        list_players(&api);
+
+        // token 过期时重新获取并重试
+        server.expire_sessions();
+        list_players(&api.clone().with_token_refresh(req));
+        let token = session.lock().unwrap().token.clone();
+        assert_eq!(token, Some(Token::Bearer("token2".into())));
+        assert!(!*flag.lock().unwrap());
+
+        // 不能重新获取时会话过期
+        server.expire_sessions();
+        let result = api.test_auth().block_and_take();
+        assert!(matches!(result, Err(ApiError::SessionExpired)));
+        assert!(*flag.lock().unwrap());
+
+        server.add_api_key("key1");
+        let mut req = proto::LoginReq {
+            username: String::new(),
+            password: "key1".into(),
+        };
+        let session = ApiClient::new(server.api_url())
+            .login(AuthMode::ApiKey, &req)
+            .block_and_take()
+            .unwrap();
+        list_players(&ApiClient::new(server.api_url()).with_session(session.shared()));
+        req.password = "key2".into();
+        let result = ApiClient::new(server.api_url())
+            .login(AuthMode::ApiKey, &req)
+            .block_and_take();
+        assert!(matches!(result, Err(ApiError::SessionExpired)));
+    }
+
+    #[test]
+    fn player_list_query() {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        let api = login(&server);
+        server.with_store(|store| {
+            store.add_player("alice", "1");
+            store.add_player("bob", "1");
+            store.add_player("carol", "1");
+            store.players[0].online = true;
+        });
+
+        let req = proto::PlayerListRequest {
+            page_number: 0,
+            page_size: 20,
+            search: Some("O".into()),
+            sort_by: Some(proto::PlayerSortKey::Username),
+            sort_order: Some(proto::SortOrder::Desc),
+            ..Default::default()
+        };
+        let list = api.player_list(&req).block_and_take().unwrap();
+        assert!(list.filtered);
+        let names: Vec<_> = list.players.iter().map(|x| x.username.as_str()).collect();
+        assert_eq!(names, ["carol", "bob"]);
+
+        let req = proto::PlayerListRequest {
+            page_number: 0,
+            page_size: 20,
+            online: Some(true),
+            ..Default::default()
+        };
+        let list = api.player_list(&req).block_and_take().unwrap();
+        assert_eq!(list.total_count, 1);
+
+        server.set_list_query_support(false);
+        let list = api.player_list(&req).block_and_take().unwrap();
+        assert!(!list.filtered);
+        assert_eq!(list.total_count, 3);
+    }
+
+    #[test]
+    fn delayed_response() {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        let api = login(&server);
+        server.inject(
+            Some("player_list"),
+            Fault::Delay(Duration::from_millis(200)),
+        );
+
+        let start = Instant::now();
+        let promise = api.player_list(&proto::PlayerListRequest {
+            page_number: 0,
+            page_size: 20,
+            ..Default::default()
+        });
+        assert!(promise.ready().is_none());
+        promise.block_and_take().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    fn sample_tunnel(receiver: u32) -> proto::TunnelListItem {
+        proto::TunnelListItem {
+            id: 0,
+            source: "0.0.0.0:8000".into(),
+            endpoint: "127.0.0.1:80".into(),
+            enabled: true,
+            sender: 0,
+            receiver,
+            description: String::new(),
+            tunnel_type: 0,
+            password: String::new(),
+            username: String::new(),
+            is_compressed: false,
+            encryption_method: "None".into(),
+            custom_mapping: Default::default(),
+        }
+    }
+
+    fn wait_push_state(channel: &PushChannel, state: PushState) {
+        let start = Instant::now();
+        while channel.state() != state {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn push_tunnel_changes() {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        let api = login(&server);
+        let id = server.with_store(|store| store.add_player("alice", "1"));
+        let channel = PushChannel::connect(&api);
+        let events = channel.subscribe();
+        wait_push_state(&channel, PushState::Connected);
+
+        server.set_online(id, true);
+        let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(event, proto::PushEvent::PlayerOnline { id, online: true });
+
+        let mut tunnel = sample_tunnel(id);
+        api.add_tunnel(&tunnel.clone().into())
+            .block_and_take()
+            .unwrap();
+        match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+            proto::PushEvent::TunnelAdded { tunnel: added } => tunnel.id = added.id,
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        api.remove_tunnel(&proto::TunnelRemoveReq { id: tunnel.id })
+            .block_and_take()
+            .unwrap();
+        let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(event, proto::PushEvent::TunnelRemoved { id: tunnel.id });
+    }
+
+    #[test]
+    fn dropped_push_channel_disconnects() {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        let api = login(&server);
+        let channel = PushChannel::connect(&api);
+        wait_push_state(&channel, PushState::Connected);
+        assert_eq!(server.open_streams(), 1);
+
+        // 下一次心跳时断开
+        drop(channel);
+        let start = Instant::now();
+        while server.open_streams() != 0 {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn push_unsupported() {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        server.set_push_support(false);
+        let api = login(&server);
+        let channel = PushChannel::connect(&api);
+        wait_push_state(&channel, PushState::Unsupported);
+    }
+
+    #[test]
+    fn traffic_stats() {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        let api = login(&server);
+        let alice = server.with_store(|store| {
+            let alice = store.add_player("alice", "1");
+            store.add_tunnel(sample_tunnel(alice).into());
+            store.add_tunnel(
+                proto::TunnelListItem {
+                    enabled: false,
+                    ..sample_tunnel(alice)
+                }
+                .into(),
+            );
+            alice
+        });
+
+        let req = proto::TrafficStatsRequest { window_secs: 3600 };
+        let tunnels = api.tunnel_stats(&req).block_and_take().unwrap();
+        assert_eq!(tunnels.items.len(), 2);
+        assert!(tunnels.items[0].bytes_in > 0);
+        assert!(tunnels.items[0].last_active.is_some());
+        // 停用的隧道没有流量
+        assert_eq!(tunnels.items[1].bytes_in + tunnels.items[1].bytes_out, 0);
+        assert_eq!(tunnels.items[1].last_active, None);
+
+        let players = api.player_stats(&req).block_and_take().unwrap();
+        assert_eq!(players.items[0].name, "alice");
+        assert!(players.items[0].bytes_in > 0);
+
+        let req = proto::TrafficSeriesRequest {
+            window_secs: 3600,
+            player: Some(alice),
+            ..Default::default()
+        };
+        let series = api.traffic_series(&req).block_and_take().unwrap();
+        assert_eq!(series.step_secs, 60);
+        assert_eq!(series.points.len(), SERIES_POINTS as usize);
+        assert!(series.points.iter().all(|x| x.time <= series.now));
+    }
+}
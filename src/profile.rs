@@ -0,0 +1,103 @@
+//! 保存的服务器配置，在登录窗口和顶部菜单中切换。
+
+use crate::api::ApiClient;
+use crate::auth::{AuthMode, SharedSession};
+use crate::proto;
+use egui::Color32;
+use std::sync::{Arc, Mutex};
+
+/// 配置的颜色标签，用于区分测试/正式服务器
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ProfileColor {
+    #[default]
+    None,
+    Red,
+    Orange,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl ProfileColor {
+    pub const ALL: [ProfileColor; 6] = [
+        ProfileColor::None,
+        ProfileColor::Red,
+        ProfileColor::Orange,
+        ProfileColor::Green,
+        ProfileColor::Blue,
+        ProfileColor::Purple,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ProfileColor::None => "none",
+            ProfileColor::Red => "red",
+            ProfileColor::Orange => "orange",
+            ProfileColor::Green => "green",
+            ProfileColor::Blue => "blue",
+            ProfileColor::Purple => "purple",
+        }
+    }
+
+    pub fn color(self) -> Option<Color32> {
+        match self {
+            ProfileColor::None => None,
+            ProfileColor::Red => Some(Color32::from_rgb(0xe0, 0x40, 0x40)),
+            ProfileColor::Orange => Some(Color32::from_rgb(0xf0, 0x90, 0x20)),
+            ProfileColor::Green => Some(Color32::from_rgb(0x40, 0xb0, 0x50)),
+            ProfileColor::Blue => Some(Color32::from_rgb(0x40, 0x80, 0xe0)),
+            ProfileColor::Purple => Some(Color32::from_rgb(0xa0, 0x60, 0xd0)),
+        }
+    }
+}
+
+/// 一个保存的服务器
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ServerProfile {
+    pub name: String,
+    pub api_url: String,
+    pub username: String,
+    /// 记住的密码，None 表示不记住，和会话一样通过 SecretStore 保存
+    #[serde(skip)]
+    pub password: Option<String>,
+    pub auth_mode: AuthMode,
+    pub color: ProfileColor,
+    /// 切换走时保存的会话，切换回来时不需要重新登录
+    #[serde(skip)]
+    pub session: Option<SharedSession>,
+    /// 非当前服务器的会话过期标记
+    #[serde(skip)]
+    pub session_expired: Arc<Mutex<bool>>,
+}
+
+impl ServerProfile {
+    /// 带颜色标签的名称
+    pub fn title(&self) -> egui::RichText {
+        match self.color.color() {
+            Some(color) => egui::RichText::new(format!("● {}", self.name)).color(color),
+            None => egui::RichText::new(&self.name),
+        }
+    }
+
+    /// 使用保存的会话访问这个服务器，未登录时为 None
+    pub fn api(&self, ctx: &egui::Context) -> Option<ApiClient> {
+        let session = self.session.clone()?;
+        let api = ApiClient::new(self.api_url.clone())
+            .with_session(session)
+            .with_context(ctx.clone())
+            .with_session_flag(self.session_expired.clone());
+        Some(match (&self.password, self.auth_mode) {
+            (Some(password), AuthMode::Bearer) => api.with_token_refresh(proto::LoginReq {
+                username: self.username.clone(),
+                password: password.clone(),
+            }),
+            _ => api,
+        })
+    }
+}
+
+/// 按名称查找配置
+pub fn find_profile(profiles: &[ServerProfile], name: &str) -> Option<usize> {
+    profiles.iter().position(|x| x.name == name)
+}
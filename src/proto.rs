@@ -2,12 +2,21 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// 登录请求
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct LoginReq {
     pub username: String,
     pub password: String,
 }
 
+/// token 登录回复
+#[derive(Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub msg: String,
+    pub code: i32,
+    #[serde(default)]
+    pub token: String,
+}
+
 /// 通用回复
 #[derive(Serialize, Deserialize)]
 pub struct GeneralResponse {
@@ -15,16 +24,71 @@ pub struct GeneralResponse {
     pub code: i32,
 }
 
-/// 玩家列表回复
-#[derive(Serialize, Deserialize)]
+/// 列表排序方向
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// 玩家列表排序字段
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayerSortKey {
+    Id,
+    Username,
+    Online,
+}
+
+/// 玩家列表请求
+#[derive(Serialize, Deserialize, Default, Clone)]
 pub struct PlayerListRequest {
     // 页码  从1开始
     pub page_number: usize,
     pub page_size: usize,
+    // 用户名包含的字符串
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    // 只显示在线/离线玩家
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub online: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<PlayerSortKey>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_order: Option<SortOrder>,
+}
+
+impl PlayerListRequest {
+    /// 是否带有过滤/排序条件
+    pub fn has_query(&self) -> bool {
+        self.search.is_some() || self.online.is_some() || self.sort_by.is_some()
+    }
+
+    /// 按请求中的条件过滤并排序，服务器不支持时由客户端处理
+    pub fn apply_query(&self, players: &mut Vec<PlayerListItem>) {
+        if let Some(search) = &self.search {
+            let search = search.to_lowercase();
+            players.retain(|x| x.username.to_lowercase().contains(&search));
+        }
+        if let Some(online) = self.online {
+            players.retain(|x| x.online == online);
+        }
+        if let Some(sort_by) = self.sort_by {
+            players.sort_by(|a, b| match sort_by {
+                PlayerSortKey::Id => a.id.cmp(&b.id),
+                PlayerSortKey::Username => a.username.cmp(&b.username),
+                PlayerSortKey::Online => a.online.cmp(&b.online),
+            });
+            if self.sort_order == Some(SortOrder::Desc) {
+                players.reverse();
+            }
+        }
+    }
 }
 
 /// 玩家列表子项
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct PlayerListItem {
     pub id: u32,
     pub username: String,
@@ -38,6 +102,9 @@ pub struct PlayerListResponse {
     pub players: Vec<PlayerListItem>,
     pub cur_page_number: usize,
     pub total_count: usize,
+    // 服务器是否已处理过滤/排序条件，旧版本服务器没有这个字段
+    #[serde(default)]
+    pub filtered: bool,
 }
 
 /// 删除玩家
@@ -47,7 +114,7 @@ pub struct PlayerRemoveReq {
 }
 
 /// 添加玩家
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct PlayerAddReq {
     pub username: String,
     pub password: String,
@@ -61,16 +128,110 @@ pub struct PlayerUpdateReq {
     pub password: String,
 }
 
-/// 玩家列表回复
-#[derive(Serialize, Deserialize)]
+impl From<PlayerListItem> for PlayerUpdateReq {
+    fn from(item: PlayerListItem) -> Self {
+        Self {
+            id: item.id,
+            username: item.username,
+            password: item.password,
+        }
+    }
+}
+
+/// 通道列表排序字段
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TunnelSortKey {
+    Id,
+    TunnelType,
+    Source,
+    Sender,
+    Enabled,
+    Description,
+}
+
+/// 通道列表请求
+#[derive(Serialize, Deserialize, Default, Clone)]
 pub struct TunnelListRequest {
     // 页码  从1开始
     pub page_number: usize,
     pub page_size: usize,
+    // source/endpoint/description 包含的字符串
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tunnel_type: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receiver: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<TunnelSortKey>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_order: Option<SortOrder>,
 }
 
+impl TunnelListRequest {
+    /// 是否带有过滤/排序条件
+    pub fn has_query(&self) -> bool {
+        self.search.is_some()
+            || self.tunnel_type.is_some()
+            || self.enabled.is_some()
+            || self.sender.is_some()
+            || self.receiver.is_some()
+            || self.encryption_method.is_some()
+            || self.sort_by.is_some()
+    }
+
+    /// 按请求中的条件过滤并排序，服务器不支持时由客户端处理
+    pub fn apply_query(&self, tunnels: &mut Vec<TunnelListItem>) {
+        if let Some(search) = &self.search {
+            let search = search.to_lowercase();
+            tunnels.retain(|x| {
+                [&x.source, &x.endpoint, &x.description]
+                    .iter()
+                    .any(|text| text.to_lowercase().contains(&search))
+            });
+        }
+        tunnels.retain(|x| {
+            self.tunnel_type.map_or(true, |t| x.tunnel_type == t)
+                && self.enabled.map_or(true, |e| x.enabled == e)
+                && self.sender.map_or(true, |id| x.sender == id)
+                && self.receiver.map_or(true, |id| x.receiver == id)
+                && self
+                    .encryption_method
+                    .as_ref()
+                    .map_or(true, |m| &x.encryption_method == m)
+        });
+        if let Some(sort_by) = self.sort_by {
+            tunnels.sort_by(|a, b| match sort_by {
+                TunnelSortKey::Id => a.id.cmp(&b.id),
+                TunnelSortKey::TunnelType => a.tunnel_type.cmp(&b.tunnel_type),
+                TunnelSortKey::Source => a.source.cmp(&b.source),
+                TunnelSortKey::Sender => a.sender.cmp(&b.sender),
+                TunnelSortKey::Enabled => a.enabled.cmp(&b.enabled),
+                TunnelSortKey::Description => a.description.cmp(&b.description),
+            });
+            if self.sort_order == Some(SortOrder::Desc) {
+                tunnels.reverse();
+            }
+        }
+    }
+}
+
+/// 通道类型, 下标即 tunnel_type
+pub const TUNNEL_TYPE_OPTION: [&str; 4] = ["TCP", "UDP", "SOCKS5", "UNKNOWN"];
+/// SOCKS5 通道的 tunnel_type
+pub const TUNNEL_TYPE_SOCKS5: u32 = 2;
+/// 通道加密方式
+pub const TUNNEL_ENCRYPTION_METHOD: [&str; 3] = ["None", "Aes128", "Xor"];
+
 /// 通道列表子项
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct TunnelListItem {
     pub id: u32,
     pub source: String,
@@ -93,6 +254,9 @@ pub struct TunnelListResponse {
     pub tunnels: Vec<TunnelListItem>,
     pub cur_page_number: usize,
     pub total_count: usize,
+    // 服务器是否已处理过滤/排序条件，旧版本服务器没有这个字段
+    #[serde(default)]
+    pub filtered: bool,
 }
 
 /// 删除通道请求
@@ -135,3 +299,120 @@ pub struct TunnelUpdateReq {
     pub encryption_method: String,
     pub custom_mapping: HashMap<String, String>,
 }
+
+impl From<TunnelListItem> for TunnelAddReq {
+    fn from(item: TunnelListItem) -> Self {
+        Self {
+            source: item.source,
+            endpoint: item.endpoint,
+            enabled: item.enabled as u8,
+            sender: item.sender,
+            receiver: item.receiver,
+            description: item.description,
+            tunnel_type: item.tunnel_type,
+            password: item.password,
+            username: item.username,
+            is_compressed: item.is_compressed as u8,
+            encryption_method: item.encryption_method,
+            custom_mapping: item.custom_mapping,
+        }
+    }
+}
+
+impl From<TunnelListItem> for TunnelUpdateReq {
+    fn from(item: TunnelListItem) -> Self {
+        Self {
+            id: item.id,
+            source: item.source,
+            endpoint: item.endpoint,
+            enabled: item.enabled as u8,
+            sender: item.sender,
+            receiver: item.receiver,
+            description: item.description,
+            tunnel_type: item.tunnel_type,
+            password: item.password,
+            username: item.username,
+            is_compressed: item.is_compressed as u8,
+            encryption_method: item.encryption_method,
+            custom_mapping: item.custom_mapping,
+        }
+    }
+}
+
+/// 服务器推送的事件，`events` 接口以 SSE 格式下发，每条 data 为一个 JSON
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PushEvent {
+    /// 玩家上线/下线
+    PlayerOnline {
+        id: u32,
+        online: bool,
+    },
+    TunnelAdded {
+        tunnel: TunnelListItem,
+    },
+    TunnelUpdated {
+        tunnel: TunnelListItem,
+    },
+    TunnelRemoved {
+        id: u32,
+    },
+}
+
+/// 隧道/玩家流量统计请求
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct TrafficStatsRequest {
+    // 统计最近多少秒
+    pub window_secs: u64,
+}
+
+/// 一个隧道或玩家在统计时间范围内的流量
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct TrafficStats {
+    pub id: u32,
+    // 隧道的描述或 source，玩家的用户名
+    pub name: String,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    // 当前的连接数
+    pub connections: u32,
+    // 最后一次有数据的时间(unix 秒)，没有记录时为 None
+    #[serde(default)]
+    pub last_active: Option<u64>,
+}
+
+/// 流量统计回复
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrafficStatsResponse {
+    // 服务器当前时间(unix 秒)
+    pub now: u64,
+    pub items: Vec<TrafficStats>,
+}
+
+/// 流量曲线请求，tunnel/player 都为空时统计全部隧道
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct TrafficSeriesRequest {
+    pub window_secs: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tunnel: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player: Option<u32>,
+}
+
+/// 曲线中的一个点，流量为 `step_secs` 内的合计
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct TrafficPoint {
+    // 开始时间(unix 秒)
+    pub time: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub connections: u32,
+}
+
+/// 流量曲线回复
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrafficSeriesResponse {
+    pub now: u64,
+    pub step_secs: u64,
+    pub points: Vec<TrafficPoint>,
+}
@@ -0,0 +1,211 @@
+//! 服务器推送：通过 `events` 接口(SSE)接收玩家上下线和隧道变化，
+//! 服务器没有这个接口时列表页面退回到定时刷新。
+
+use crate::api::ApiClient;
+use crate::proto::PushEvent;
+use ehttp::streaming::Part;
+use log::warn;
+use std::ops::ControlFlow;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+/// 断开后重连的间隔(秒)
+static RECONNECT_DELAY: f64 = 5.0;
+
+/// 推送连接的状态
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PushState {
+    Connecting,
+    Connected,
+    /// 服务器没有推送接口，不再重连
+    Unsupported,
+    /// 连接断开，稍后重连
+    Disconnected,
+}
+
+struct Shared {
+    state: PushState,
+    subscribers: Vec<Sender<PushEvent>>,
+    // 每次重连加一，旧连接的回调据此退出
+    generation: u32,
+    closed: bool,
+}
+
+/// 登录期间保持的推送连接，收到的事件分发给所有订阅者
+pub struct PushChannel {
+    shared: Arc<Mutex<Shared>>,
+    // 断开后下次重连的时间
+    retry_at: Option<f64>,
+}
+
+impl PushChannel {
+    pub fn connect(api: &ApiClient) -> Self {
+        let channel = Self {
+            shared: Arc::new(Mutex::new(Shared {
+                state: PushState::Connecting,
+                subscribers: Vec::new(),
+                generation: 0,
+                closed: false,
+            })),
+            retry_at: None,
+        };
+        channel.start(api, 0);
+        channel
+    }
+
+    pub fn state(&self) -> PushState {
+        self.shared.lock().unwrap().state
+    }
+
+    /// 订阅之后收到的事件
+    pub fn subscribe(&self) -> Receiver<PushEvent> {
+        let (sender, receiver) = channel();
+        self.shared.lock().unwrap().subscribers.push(sender);
+        receiver
+    }
+
+    /// 每帧调用，断开后定时重连
+    pub fn poll(&mut self, api: &ApiClient, ctx: &egui::Context) {
+        let generation = {
+            let shared = self.shared.lock().unwrap();
+            if shared.state != PushState::Disconnected {
+                return;
+            }
+            shared.generation
+        };
+
+        let now = ctx.input(|i| i.time);
+        let retry_at = *self.retry_at.get_or_insert(now + RECONNECT_DELAY);
+        if now < retry_at {
+            ctx.request_repaint_after(std::time::Duration::from_secs_f64(retry_at - now));
+            return;
+        }
+
+        self.retry_at = None;
+        {
+            let mut shared = self.shared.lock().unwrap();
+            shared.state = PushState::Connecting;
+            shared.generation = generation + 1;
+        }
+        self.start(api, generation + 1);
+    }
+
+    fn start(&self, api: &ApiClient, generation: u32) {
+        let shared = self.shared.clone();
+        let parser = Mutex::new(EventParser::default());
+        api.stream("events", move |part| {
+            let mut shared = shared.lock().unwrap();
+            if shared.closed || shared.generation != generation {
+                return ControlFlow::Break(());
+            }
+            match part {
+                Ok(Part::Response(response)) => {
+                    let is_event_stream = response
+                        .headers
+                        .get("Content-Type")
+                        .map_or(false, |x| x.starts_with("text/event-stream"));
+                    if response.ok && is_event_stream {
+                        shared.state = PushState::Connected;
+                        return ControlFlow::Continue(());
+                    }
+                    // 旧版本服务器回复 404，或者网页服务器回复了首页
+                    shared.state = if response.status == 401 {
+                        PushState::Disconnected
+                    } else {
+                        PushState::Unsupported
+                    };
+                    ControlFlow::Break(())
+                }
+                Ok(Part::Chunk(chunk)) if chunk.is_empty() => {
+                    shared.state = PushState::Disconnected;
+                    ControlFlow::Break(())
+                }
+                Ok(Part::Chunk(chunk)) => {
+                    for data in parser.lock().unwrap().feed(&chunk) {
+                        match serde_json::from_str::<PushEvent>(&data) {
+                            Ok(event) => {
+                                shared.subscribers.retain(|x| x.send(event.clone()).is_ok())
+                            }
+                            Err(err) => warn!("unknown push event {}: {}", data, err),
+                        }
+                    }
+                    ControlFlow::Continue(())
+                }
+                Err(err) => {
+                    warn!("push channel: {}", err);
+                    shared.state = PushState::Disconnected;
+                    ControlFlow::Break(())
+                }
+            }
+        });
+    }
+}
+
+impl Drop for PushChannel {
+    fn drop(&mut self) {
+        // ehttp 不能中止请求，连接在下一次收到数据时断开，
+        // 服务器定时发送的心跳注释保证空闲连接也能及时关闭
+        self.shared.lock().unwrap().closed = true;
+    }
+}
+
+/// SSE 解析，只处理 `data` 字段
+#[derive(Default)]
+struct EventParser {
+    // 还没有收到换行的数据
+    line: Vec<u8>,
+    data: Vec<String>,
+}
+
+impl EventParser {
+    /// 输入一段数据，返回其中完整事件的 data
+    fn feed(&mut self, chunk: &[u8]) -> Vec<String> {
+        let mut events = Vec::new();
+        for byte in chunk {
+            if *byte != b'\n' {
+                self.line.push(*byte);
+                continue;
+            }
+            let line = std::mem::take(&mut self.line);
+            let line = String::from_utf8_lossy(&line);
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            if line.is_empty() {
+                // 空行表示一个事件结束
+                if !self.data.is_empty() {
+                    events.push(std::mem::take(&mut self.data).join("\n"));
+                }
+            } else if let Some(value) = line.strip_prefix("data:") {
+                self.data
+                    .push(value.strip_prefix(' ').unwrap_or(value).to_string());
+            }
+            // 注释(`:` 开头)和其他字段忽略
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_split_events() {
+        let mut parser = EventParser::default();
+        assert!(parser.feed(b": ping\n\ndata: {\"a\"").is_empty());
+        assert_eq!(parser.feed(b":1}\r\n\r\ndata:x\n"), vec!["{\"a\":1}"]);
+        assert_eq!(parser.feed(b"data: y\nid: 3\n\n"), vec!["x\ny"]);
+    }
+
+    #[test]
+    fn parse_push_event() {
+        let event: PushEvent =
+            serde_json::from_str(r#"{"type":"player_online","id":3,"online":true}"#).unwrap();
+        assert_eq!(
+            event,
+            PushEvent::PlayerOnline {
+                id: 3,
+                online: true
+            }
+        );
+    }
+}
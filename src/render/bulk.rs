@@ -0,0 +1,182 @@
+//! 批量操作，逐项提交请求并显示每一项的结果
+
+use crate::api::{ApiError, ApiPromise};
+use crate::proto::GeneralResponse;
+use crate::render::render_api_error;
+use eframe::epaint::Color32;
+
+enum Status {
+    Pending,
+    Running(ApiPromise<GeneralResponse>),
+    Done,
+    Failed(ApiError),
+}
+
+struct Entry<R> {
+    label: String,
+    request: R,
+    status: Status,
+}
+
+/// 一组批量请求，同一时间只有一个请求在进行
+pub struct BulkJob<R> {
+    title: String,
+    entries: Vec<Entry<R>>,
+}
+
+impl<R: Clone> BulkJob<R> {
+    /// `items` 为每一项的显示名称和请求
+    pub fn new(title: impl Into<String>, items: Vec<(String, R)>) -> Self {
+        Self {
+            title: title.into(),
+            entries: items
+                .into_iter()
+                .map(|(label, request)| Entry {
+                    label,
+                    request,
+                    status: Status::Pending,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|x| matches!(x.status, Status::Pending | Status::Running(_)))
+    }
+
+    pub fn failed_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|x| matches!(x.status, Status::Failed(_)))
+            .count()
+    }
+
+    fn finished_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|x| matches!(x.status, Status::Done | Status::Failed(_)))
+            .count()
+    }
+
+    /// 失败的项重新排队
+    pub fn retry_failed(&mut self) {
+        for entry in self.entries.iter_mut() {
+            if let Status::Failed(_) = entry.status {
+                entry.status = Status::Pending;
+            }
+        }
+    }
+
+    /// 检查正在进行的请求并发起下一个，返回这次成功完成的请求
+    pub fn poll(&mut self, mut send: impl FnMut(&R) -> ApiPromise<GeneralResponse>) -> Vec<R> {
+        let mut succeeded = Vec::new();
+        for entry in self.entries.iter_mut() {
+            let result = match &entry.status {
+                Status::Running(promise) => match promise.ready() {
+                    Some(result) => result.as_ref().map(|_| ()).map_err(Clone::clone),
+                    None => return succeeded,
+                },
+                _ => continue,
+            };
+            entry.status = match result {
+                Ok(()) => {
+                    succeeded.push(entry.request.clone());
+                    Status::Done
+                }
+                Err(err) => Status::Failed(err),
+            };
+        }
+
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|x| matches!(x.status, Status::Pending))
+        {
+            entry.status = Status::Running(send(&entry.request));
+        }
+        succeeded
+    }
+
+    /// 进度窗口，返回 false 表示窗口已关闭
+    pub fn show(&mut self, ctx: &egui::Context) -> bool {
+        let running = self.is_running();
+        let failed_count = self.failed_count();
+        let total = self.entries.len();
+        let finished = self.finished_count();
+        let mut open = true;
+        let mut need_retry = false;
+
+        egui::Window::new(&self.title)
+            .id(egui::Id::new("bulk_job"))
+            .collapsible(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.add(
+                    egui::ProgressBar::new(finished as f32 / total.max(1) as f32)
+                        .text(format!("{} / {}", finished, total)),
+                );
+                if failed_count > 0 {
+                    ui.colored_label(
+                        ui.visuals().error_fg_color,
+                        format!("{} failed", failed_count),
+                    );
+                }
+
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("bulk_job_entries")
+                            .num_columns(2)
+                            .show(ui, |ui| {
+                                for entry in self.entries.iter() {
+                                    ui.label(&entry.label);
+                                    match &entry.status {
+                                        Status::Pending => {
+                                            ui.weak("waiting");
+                                        }
+                                        Status::Running(_) => {
+                                            ui.spinner();
+                                        }
+                                        Status::Done => {
+                                            ui.colored_label(Color32::GREEN, "✔");
+                                        }
+                                        Status::Failed(err) => {
+                                            ui.horizontal(|ui| {
+                                                ui.colored_label(ui.visuals().error_fg_color, "✖");
+                                                render_api_error(ui, err);
+                                            });
+                                        }
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            !running && failed_count > 0,
+                            egui::Button::new(format!("retry failed ({})", failed_count)),
+                        )
+                        .clicked()
+                    {
+                        need_retry = true;
+                    }
+                    if ui
+                        .add_enabled(!running, egui::Button::new("close"))
+                        .clicked()
+                    {
+                        open = false;
+                    }
+                });
+            });
+
+        if need_retry {
+            self.retry_failed();
+        }
+        open
+    }
+}
@@ -0,0 +1,510 @@
+//! 导出/导入全部玩家和隧道配置
+
+use crate::api::{ApiClient, ApiError, ApiPromise};
+use crate::config::{ConfigDiff, ConfigDocument, ConfigFormat};
+use crate::proto::{self, PlayerListItem, PlayerListResponse, TunnelListItem, TunnelListResponse};
+use crate::render::bulk::BulkJob;
+#[cfg(target_arch = "wasm32")]
+use crate::render::file_io::{download, upload, PendingUpload};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::render::file_io::{load_file, save_file};
+use crate::render::{render_api_error, RenderUI};
+use crate::TemplateApp;
+use eframe::epaint::Color32;
+use egui::Ui;
+
+/// 分页加载全部数据时每页的数量
+static LOAD_PAGE_SIZE: usize = 100;
+
+/// 分页加载服务器上的全部玩家和隧道
+pub(crate) struct LiveLoader {
+    pub(crate) players: Vec<PlayerListItem>,
+    pub(crate) tunnels: Vec<TunnelListItem>,
+    players_promise: Option<ApiPromise<PlayerListResponse>>,
+    tunnels_promise: Option<ApiPromise<TunnelListResponse>>,
+}
+
+impl LiveLoader {
+    pub(crate) fn new(api: &ApiClient) -> Self {
+        Self {
+            players: Vec::new(),
+            tunnels: Vec::new(),
+            players_promise: Some(api.player_list(&player_page(0))),
+            tunnels_promise: Some(api.tunnel_list(&tunnel_page(0))),
+        }
+    }
+
+    /// 加载完成返回 `Some(Ok)`，出错返回 `Some(Err)`
+    pub(crate) fn poll(&mut self, api: &ApiClient) -> Option<Result<(), ApiError>> {
+        if let Some(result) = self.players_promise.as_ref().and_then(|x| x.ready()) {
+            let response = match result {
+                Ok(response) => response,
+                Err(err) => return Some(Err(err.clone())),
+            };
+            self.players.extend(response.players.iter().cloned());
+            self.players_promise = (!response.players.is_empty()
+                && self.players.len() < response.total_count)
+                .then(|| api.player_list(&player_page(response.cur_page_number + 1)));
+        }
+        if let Some(result) = self.tunnels_promise.as_ref().and_then(|x| x.ready()) {
+            let response = match result {
+                Ok(response) => response,
+                Err(err) => return Some(Err(err.clone())),
+            };
+            self.tunnels.extend(response.tunnels.iter().cloned());
+            self.tunnels_promise = (!response.tunnels.is_empty()
+                && self.tunnels.len() < response.total_count)
+                .then(|| api.tunnel_list(&tunnel_page(response.cur_page_number + 1)));
+        }
+
+        (self.players_promise.is_none() && self.tunnels_promise.is_none()).then_some(Ok(()))
+    }
+}
+
+fn player_page(page_number: usize) -> proto::PlayerListRequest {
+    proto::PlayerListRequest {
+        page_number,
+        page_size: LOAD_PAGE_SIZE,
+        ..Default::default()
+    }
+}
+
+fn tunnel_page(page_number: usize) -> proto::TunnelListRequest {
+    proto::TunnelListRequest {
+        page_number,
+        page_size: LOAD_PAGE_SIZE,
+        ..Default::default()
+    }
+}
+
+/// 导入时每一项的请求
+#[derive(Clone)]
+enum ConfigRequest {
+    AddPlayer(proto::PlayerAddReq),
+    UpdatePlayer(PlayerListItem),
+    RemovePlayer(u32),
+    AddTunnel(Box<TunnelListItem>),
+    UpdateTunnel(Box<TunnelListItem>),
+    RemoveTunnel(u32),
+}
+
+/// 导入分两步，新增的玩家创建后才能确定隧道中 sender/receiver 的 id
+enum Apply {
+    // 新增/修改玩家，删除隧道
+    Players(BulkJob<ConfigRequest>),
+    // 重新加载玩家列表
+    Reloading(LiveLoader),
+    // 新增/修改隧道，删除玩家
+    Tunnels(BulkJob<ConfigRequest>),
+}
+
+/// 加载服务器数据的目的
+enum Loading {
+    Export(LiveLoader),
+    Diff(LiveLoader),
+}
+
+pub struct Logic {
+    format: ConfigFormat,
+    #[cfg(not(target_arch = "wasm32"))]
+    path: String,
+    #[cfg(target_arch = "wasm32")]
+    upload: PendingUpload,
+
+    loading: Option<Loading>,
+    load_error: Option<ApiError>,
+    // 最近一次操作的结果
+    message: Option<Result<String, String>>,
+
+    // 导入的文档和与服务器数据的差异
+    document: Option<ConfigDocument>,
+    diff: Option<ConfigDiff>,
+    // 是否删除文档中没有的玩家和隧道
+    with_removes: bool,
+    apply: Option<Apply>,
+    // 导入时无法处理的隧道
+    skipped: Vec<String>,
+}
+
+impl Logic {
+    pub fn new() -> Self {
+        Self {
+            format: ConfigFormat::Json,
+            #[cfg(not(target_arch = "wasm32"))]
+            path: "npipe-config.json".to_string(),
+            #[cfg(target_arch = "wasm32")]
+            upload: Default::default(),
+            loading: None,
+            load_error: None,
+            message: None,
+            document: None,
+            diff: None,
+            with_removes: false,
+            apply: None,
+            skipped: Vec::new(),
+        }
+    }
+}
+
+impl RenderUI for Logic {
+    fn render(&mut self, ctx: &egui::Context, app: &mut TemplateApp) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let api = app.api(ctx);
+            self.process_loading(&api);
+            self.process_apply(ctx, &api);
+            #[cfg(target_arch = "wasm32")]
+            {
+                let uploaded = self.upload.lock().unwrap().take();
+                if let Some(result) = uploaded {
+                    self.import_text(result, &api);
+                }
+            }
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                self.render_content(ui, &api);
+            });
+        });
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Logic {
+    fn render_content(&mut self, ui: &mut Ui, api: &ApiClient) {
+        let idle = self.loading.is_none() && self.apply.is_none();
+
+        ui.horizontal(|ui| {
+            ui.label("format:");
+            for format in [ConfigFormat::Json, ConfigFormat::Toml] {
+                if ui
+                    .radio_value(&mut self.format, format, format.extension())
+                    .changed()
+                {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    self.set_path_extension();
+                }
+            }
+        });
+
+        #[cfg(not(target_arch = "wasm32"))]
+        ui.horizontal(|ui| {
+            ui.label("file:");
+            ui.add(egui::TextEdit::singleline(&mut self.path).desired_width(300.0));
+        });
+
+        ui.add_enabled_ui(idle, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("⬇ export").clicked() {
+                    self.message = None;
+                    self.load_error = None;
+                    self.loading = Some(Loading::Export(LiveLoader::new(api)));
+                }
+                if ui.button("⬆ import").clicked() {
+                    self.message = None;
+                    self.load_error = None;
+                    #[cfg(not(target_arch = "wasm32"))]
+                    self.import_text(load_file(self.path.trim()), api);
+                    #[cfg(target_arch = "wasm32")]
+                    if let Err(err) = upload(ui.ctx(), ".json,.toml", self.upload.clone()) {
+                        self.message = Some(Err(err));
+                    }
+                }
+                if self.loading.is_some() {
+                    ui.spinner();
+                }
+            });
+        });
+
+        if let Some(err) = &self.load_error {
+            render_api_error(ui, err);
+        }
+        match &self.message {
+            Some(Ok(text)) => {
+                ui.colored_label(Color32::GREEN, text);
+            }
+            Some(Err(err)) => {
+                ui.colored_label(ui.visuals().error_fg_color, err);
+            }
+            None => {}
+        }
+        for text in &self.skipped {
+            ui.colored_label(ui.visuals().warn_fg_color, text);
+        }
+
+        if let Some(diff) = &self.diff {
+            ui.separator();
+            render_diff(ui, diff);
+            ui.checkbox(
+                &mut self.with_removes,
+                "remove players and tunnels missing from the file",
+            );
+            let empty = diff.is_empty(self.with_removes);
+            if empty {
+                ui.label("nothing to apply");
+            }
+            if ui
+                .add_enabled(idle && !empty, egui::Button::new("apply"))
+                .clicked()
+            {
+                self.start_apply();
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn set_path_extension(&mut self) {
+        for format in [ConfigFormat::Json, ConfigFormat::Toml] {
+            if let Some(stem) = self.path.strip_suffix(format.extension()) {
+                self.path = format!("{}{}", stem, self.format.extension());
+                return;
+            }
+        }
+    }
+
+    /// 解析导入的文件，然后加载服务器数据计算差异
+    fn import_text(&mut self, text: Result<String, String>, api: &ApiClient) {
+        self.diff = None;
+        self.skipped.clear();
+        match text.and_then(|text| ConfigDocument::parse(&text).map_err(|err| err.to_string())) {
+            Ok(document) => {
+                self.document = Some(document);
+                self.loading = Some(Loading::Diff(LiveLoader::new(api)));
+            }
+            Err(err) => {
+                self.document = None;
+                self.message = Some(Err(err));
+            }
+        }
+    }
+
+    fn process_loading(&mut self, api: &ApiClient) {
+        let result = match &mut self.loading {
+            Some(Loading::Export(loader)) | Some(Loading::Diff(loader)) => loader.poll(api),
+            None => return,
+        };
+        match result {
+            None => {}
+            Some(Err(err)) => {
+                self.loading = None;
+                self.load_error = Some(err);
+            }
+            Some(Ok(())) => match self.loading.take() {
+                Some(Loading::Export(loader)) => self.export(loader),
+                Some(Loading::Diff(loader)) => {
+                    if let Some(document) = &self.document {
+                        self.diff =
+                            Some(ConfigDiff::new(document, &loader.players, &loader.tunnels));
+                    }
+                }
+                None => {}
+            },
+        }
+    }
+
+    fn export(&mut self, loader: LiveLoader) {
+        let document = ConfigDocument::from_live(&loader.players, &loader.tunnels);
+        let summary = format!(
+            "exported {} player(s), {} tunnel(s)",
+            document.players.len(),
+            document.tunnels.len()
+        );
+        let result = document
+            .to_string(self.format)
+            .map_err(|err| err.to_string())
+            .and_then(|text| {
+                #[cfg(not(target_arch = "wasm32"))]
+                return save_file(self.path.trim(), &text)
+                    .map(|_| format!("{} to {}", summary, self.path.trim()));
+                #[cfg(target_arch = "wasm32")]
+                return download(&format!("npipe-config.{}", self.format.extension()), &text)
+                    .map(|_| summary);
+            });
+        self.message = Some(result);
+    }
+
+    fn start_apply(&mut self) {
+        let Some(diff) = &self.diff else {
+            return;
+        };
+        let mut items = Vec::new();
+        for player in &diff.add_players {
+            items.push((
+                format!("add player {}", player.username),
+                ConfigRequest::AddPlayer(proto::PlayerAddReq {
+                    username: player.username.clone(),
+                    password: player.password.clone(),
+                }),
+            ));
+        }
+        for player in &diff.update_players {
+            items.push((
+                format!("update player {}", player.username),
+                ConfigRequest::UpdatePlayer(player.clone()),
+            ));
+        }
+        if self.with_removes {
+            for tunnel in &diff.remove_tunnels {
+                items.push((
+                    format!("remove tunnel #{} {}", tunnel.id, tunnel.source),
+                    ConfigRequest::RemoveTunnel(tunnel.id),
+                ));
+            }
+        }
+        self.skipped.clear();
+        self.apply = Some(Apply::Players(BulkJob::new("Import players", items)));
+    }
+
+    /// 玩家处理完成后按新的玩家列表生成隧道请求
+    fn tunnel_requests(&mut self, players: &[PlayerListItem]) -> Vec<(String, ConfigRequest)> {
+        let Some(diff) = &self.diff else {
+            return Vec::new();
+        };
+        let mut items = Vec::new();
+        let mut skipped = Vec::new();
+        let tunnels = diff
+            .add_tunnels
+            .iter()
+            .map(|x| (None, x))
+            .chain(diff.update_tunnels.iter().map(|(id, x)| (Some(*id), x)));
+        for (id, config) in tunnels {
+            match config.to_item(id.unwrap_or_default(), players) {
+                Ok(item) => {
+                    let item = Box::new(item);
+                    items.push(match id {
+                        None => (
+                            format!("add tunnel {}", config.source),
+                            ConfigRequest::AddTunnel(item),
+                        ),
+                        Some(id) => (
+                            format!("update tunnel #{} {}", id, config.source),
+                            ConfigRequest::UpdateTunnel(item),
+                        ),
+                    });
+                }
+                Err(err) => skipped.push(format!("skipped tunnel {}: {}", config.source, err)),
+            }
+        }
+        if self.with_removes {
+            for player in &diff.remove_players {
+                items.push((
+                    format!("remove player {}", player.username),
+                    ConfigRequest::RemovePlayer(player.id),
+                ));
+            }
+        }
+        self.skipped = skipped;
+        items
+    }
+
+    fn process_apply(&mut self, ctx: &egui::Context, api: &ApiClient) {
+        let send = |request: &ConfigRequest| match request {
+            ConfigRequest::AddPlayer(req) => api.add_player(req),
+            ConfigRequest::UpdatePlayer(item) => {
+                api.update_player(&proto::PlayerUpdateReq::from(item.clone()))
+            }
+            ConfigRequest::RemovePlayer(id) => {
+                api.remove_player(&proto::PlayerRemoveReq { id: *id })
+            }
+            ConfigRequest::AddTunnel(item) => {
+                api.add_tunnel(&proto::TunnelAddReq::from((**item).clone()))
+            }
+            ConfigRequest::UpdateTunnel(item) => {
+                api.update_tunnel(&proto::TunnelUpdateReq::from((**item).clone()))
+            }
+            ConfigRequest::RemoveTunnel(id) => {
+                api.remove_tunnel(&proto::TunnelRemoveReq { id: *id })
+            }
+        };
+
+        match &mut self.apply {
+            None => {}
+            Some(Apply::Players(job)) => {
+                job.poll(send);
+                let open = job.show(ctx);
+                // 有失败的项时等待用户重试或关闭窗口
+                if !open || (!job.is_running() && job.failed_count() == 0) {
+                    self.apply = Some(Apply::Reloading(LiveLoader::new(api)));
+                }
+            }
+            Some(Apply::Reloading(loader)) => match loader.poll(api) {
+                None => {}
+                Some(Err(err)) => {
+                    self.apply = None;
+                    self.load_error = Some(err);
+                }
+                Some(Ok(())) => {
+                    let players = std::mem::take(&mut loader.players);
+                    let items = self.tunnel_requests(&players);
+                    self.apply = Some(Apply::Tunnels(BulkJob::new("Import tunnels", items)));
+                }
+            },
+            Some(Apply::Tunnels(job)) => {
+                job.poll(send);
+                if !job.show(ctx) {
+                    self.apply = None;
+                    // 重新计算剩余的差异
+                    if self.document.is_some() {
+                        self.loading = Some(Loading::Diff(LiveLoader::new(api)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn render_diff(ui: &mut Ui, diff: &ConfigDiff) {
+    let add_color = Color32::GREEN;
+    let update_color = ui.visuals().warn_fg_color;
+    let remove_color = ui.visuals().error_fg_color;
+
+    ui.label(format!(
+        "players: +{} ~{} -{}",
+        diff.add_players.len(),
+        diff.update_players.len(),
+        diff.remove_players.len()
+    ));
+    egui::CollapsingHeader::new("player changes")
+        .id_source("config_player_diff")
+        .show(ui, |ui| {
+            for player in &diff.add_players {
+                ui.colored_label(add_color, format!("+ {}", player.username));
+            }
+            for player in &diff.update_players {
+                ui.colored_label(update_color, format!("~ {} (password)", player.username));
+            }
+            for player in &diff.remove_players {
+                ui.colored_label(remove_color, format!("- {}", player.username));
+            }
+        });
+
+    ui.label(format!(
+        "tunnels: +{} ~{} -{}",
+        diff.add_tunnels.len(),
+        diff.update_tunnels.len(),
+        diff.remove_tunnels.len()
+    ));
+    egui::CollapsingHeader::new("tunnel changes")
+        .id_source("config_tunnel_diff")
+        .show(ui, |ui| {
+            for tunnel in &diff.add_tunnels {
+                ui.colored_label(
+                    add_color,
+                    format!("+ {} → {}", tunnel.source, tunnel.endpoint),
+                );
+            }
+            for (id, tunnel) in &diff.update_tunnels {
+                ui.colored_label(
+                    update_color,
+                    format!("~ #{} {} → {}", id, tunnel.source, tunnel.endpoint),
+                );
+            }
+            for tunnel in &diff.remove_tunnels {
+                ui.colored_label(
+                    remove_color,
+                    format!("- #{} {} → {}", tunnel.id, tunnel.source, tunnel.endpoint),
+                );
+            }
+        });
+}
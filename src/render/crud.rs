@@ -0,0 +1,1351 @@
+//! 列表页面的通用部分：分页、过滤条件、加载遮罩、新建/详情窗口、行操作和批量操作，
+//! 具体的数据类型只需要实现 [`CrudEntity`]
+
+use crate::api::{is_pending, ApiClient, ApiError, ApiPromise};
+use crate::proto::{GeneralResponse, PushEvent, SortOrder};
+use crate::push::{PushChannel, PushState};
+use crate::render::bulk::BulkJob;
+use crate::render::operation::{ItemOperations, Operation};
+use crate::render::{render_api_error, render_discard_warning, sort_header, RenderUI};
+use crate::TemplateApp;
+use eframe::emath::vec2;
+use eframe::epaint::Color32;
+use egui::{Rect, Ui};
+use egui_extras::{Column, TableBuilder};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+pub static PAGE_SIZE: usize = 20;
+
+/// 服务器不支持过滤/排序时分页加载全部数据，每页的数量
+static LOCAL_QUERY_PAGE_SIZE: usize = 100;
+
+/// 停止修改过滤条件多久后才请求(秒)
+static FILTER_DELAY: f64 = 0.3;
+
+/// 默认的自动刷新间隔(秒)
+static DEFAULT_REFRESH_INTERVAL: u32 = 10;
+
+static GRAY: Color32 = Color32::from_rgba_premultiplied(80, 80, 80, 80);
+
+/// 列表中的一页数据
+pub struct ListPage<I> {
+    pub items: Vec<I>,
+    pub cur_page_number: usize,
+    pub total_count: usize,
+    // 服务器是否已处理过滤/排序条件
+    pub filtered: bool,
+}
+
+/// 表格中的一列
+pub struct CrudColumn<K> {
+    pub title: &'static str,
+    // 可以按这一列排序时的排序字段
+    pub sort: Option<K>,
+    pub width: Column,
+}
+
+impl<K> CrudColumn<K> {
+    pub fn new(title: &'static str) -> Self {
+        Self {
+            title,
+            sort: None,
+            width: Column::auto(),
+        }
+    }
+
+    pub fn sortable(mut self, key: K) -> Self {
+        self.sort = Some(key);
+        self
+    }
+
+    pub fn width(mut self, width: Column) -> Self {
+        self.width = width;
+        self
+    }
+}
+
+/// 表格中正在渲染的一行
+pub struct CrudRow<'a, T: CrudEntity> {
+    // 在当前页中的序号
+    pub index: usize,
+    // 服务器上的数据
+    pub origin: &'a T::Item,
+    // 包含未保存修改的数据
+    pub item: &'a T::Item,
+    pub draft: &'a mut T::Draft,
+}
+
+/// 批量操作中每一项的请求
+pub enum CrudRequest<T: CrudEntity> {
+    Add(T::Draft),
+    Update(Box<T::Item>),
+    Remove(u32),
+}
+
+impl<T: CrudEntity> Clone for CrudRequest<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Add(draft) => Self::Add(draft.clone()),
+            Self::Update(item) => Self::Update(item.clone()),
+            Self::Remove(id) => Self::Remove(*id),
+        }
+    }
+}
+
+/// 批量任务的标题，以及每一项的名称和请求
+pub type BulkItems<T> = (String, Vec<(String, CrudRequest<T>)>);
+
+/// 推送事件对列表的影响
+pub enum PushChange<I> {
+    /// 服务器上的新数据，不在当前页时忽略
+    Updated(I),
+    /// 新增的数据需要刷新后才能显示
+    Added,
+    Removed(u32),
+}
+
+/// 行的编辑方式
+#[derive(PartialEq)]
+pub enum EditMode {
+    // 直接在表格中编辑，每行有更新按钮
+    Inline,
+    // 在详情窗口中编辑全部字段
+    Window,
+}
+
+/// 列表页面中的一种数据
+pub trait CrudEntity: Default + Sized {
+    /// 列表中的一行
+    type Item: Clone + PartialEq + Send + 'static;
+    /// 新建和编辑时的表单数据
+    type Draft: Clone + PartialEq + Default;
+    /// 搜索/过滤条件
+    type Query: Default + Clone;
+    type SortKey: Copy + PartialEq;
+    type ListRequest: Default;
+    type ListResponse: Send + 'static;
+
+    /// 小写的名称，用于按钮和提示，例如 "player"
+    const NAME: &'static str;
+    const EDIT_MODE: EditMode;
+    /// 是否提供定时在后台刷新当前页的选项
+    const AUTO_REFRESH: bool = false;
+
+    fn id(item: &Self::Item) -> u32;
+    /// 批量任务中显示的名称
+    fn label(item: &Self::Item) -> String;
+    fn to_draft(item: &Self::Item) -> Self::Draft;
+    /// 用表单中的数据替换 `origin` 中可以编辑的字段
+    fn from_draft(draft: &Self::Draft, origin: &Self::Item) -> Self::Item;
+    fn is_valid(_draft: &Self::Draft) -> bool {
+        true
+    }
+
+    fn list_request(
+        query: &Self::Query,
+        sort: Option<(Self::SortKey, SortOrder)>,
+        page_number: usize,
+        page_size: usize,
+    ) -> Self::ListRequest;
+    /// 请求是否带有过滤/排序条件
+    fn has_query(req: &Self::ListRequest) -> bool;
+    /// 服务器不支持过滤/排序时由客户端处理
+    fn apply_query(req: &Self::ListRequest, items: &mut Vec<Self::Item>);
+    fn to_page(response: &Self::ListResponse) -> ListPage<Self::Item>;
+
+    fn list(api: &ApiClient, req: &Self::ListRequest) -> ApiPromise<Self::ListResponse>;
+    fn add(api: &ApiClient, draft: &Self::Draft) -> ApiPromise<GeneralResponse>;
+    fn update(api: &ApiClient, item: &Self::Item) -> ApiPromise<GeneralResponse>;
+    fn remove(api: &ApiClient, id: u32) -> ApiPromise<GeneralResponse>;
+
+    fn columns(&self) -> Vec<CrudColumn<Self::SortKey>>;
+    fn cell(&self, ui: &mut Ui, column: usize, row: &mut CrudRow<'_, Self>);
+    /// 新建窗口和详情窗口中的表单
+    fn form(&self, ui: &mut Ui, draft: &mut Self::Draft);
+    /// 搜索/过滤条件，返回是否改变
+    fn render_filters(&mut self, ui: &mut Ui, query: &mut Self::Query) -> bool;
+
+    /// 请求列表数据时调用，用于加载额外的数据
+    fn on_list_request(&mut self, _api: &ApiClient) {}
+    /// 工具栏中额外的按钮
+    fn render_toolbar(&mut self, _ui: &mut Ui, _api: &ApiClient) {}
+    /// 选中行后额外的批量操作按钮，返回要开始的批量任务
+    fn render_bulk_actions(
+        &mut self,
+        _ui: &mut Ui,
+        _selected: &[&Self::Item],
+    ) -> Option<BulkItems<Self>> {
+        None
+    }
+    /// 推送事件对当前页的影响，`items` 为当前页的数据
+    fn push_change(_event: &PushEvent, _items: &[Self::Item]) -> Option<PushChange<Self::Item>> {
+        None
+    }
+    /// 额外的窗口，`idle` 为 false 时已有批量任务在进行
+    fn render_windows(&mut self, _ctx: &egui::Context, _idle: bool) -> Option<BulkItems<Self>> {
+        None
+    }
+}
+
+/// 客户端处理过滤/排序后取出指定的页
+fn local_page<T: CrudEntity>(
+    req: &T::ListRequest,
+    page_number: usize,
+    mut page: ListPage<T::Item>,
+) -> ListPage<T::Item> {
+    T::apply_query(req, &mut page.items);
+    page.total_count = page.items.len();
+    page.cur_page_number = page_number;
+    page.items = page
+        .items
+        .into_iter()
+        .skip(page_number * PAGE_SIZE)
+        .take(PAGE_SIZE)
+        .collect();
+    page
+}
+
+/// 服务器不支持过滤/排序时加载全部数据的一页
+fn local_request<T: CrudEntity>(page_number: usize) -> T::ListRequest {
+    T::list_request(
+        &T::Query::default(),
+        None,
+        page_number,
+        LOCAL_QUERY_PAGE_SIZE,
+    )
+}
+
+/// 首字母大写的名称，用于窗口标题
+fn title<T: CrudEntity>() -> String {
+    let mut chars = T::NAME.chars();
+    chars
+        .next()
+        .map(|x| x.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+pub struct CrudPage<T: CrudEntity> {
+    entity: T,
+
+    list_promise: Option<ApiPromise<T::ListResponse>>,
+    // 当前列表对应的请求和页码
+    list_request: T::ListRequest,
+    list_page_number: usize,
+    query: T::Query,
+    // 当前列表使用的过滤条件，取消丢弃修改时恢复
+    applied_query: T::Query,
+    sort: Option<(T::SortKey, SortOrder)>,
+    // 服务器是否支持过滤/排序参数，None 表示还不确定
+    server_query: Option<bool>,
+    // 服务器不支持过滤/排序时加载的全部数据，过滤条件改变时在本地处理
+    local_items: Option<Vec<T::Item>>,
+    // 正在分页加载的全部数据，加载完最后一页后移到 local_items
+    local_loading: Vec<T::Item>,
+    // 最后一次修改过滤条件的时间，等待输入停止后再请求
+    filter_changed: Option<f64>,
+    add_promise: Option<ApiPromise<GeneralResponse>>,
+
+    // 每一行正在进行的更新/删除，更新成功后写回列表
+    item_operations: ItemOperations<T::Item>,
+    // 等待依次提交的修改
+    save_queue: VecDeque<u32>,
+    // 保存队列中正在提交的行
+    save_current: Option<u32>,
+
+    // 是否正在等待列表数据刷新
+    wait_data_list: bool,
+    data: Option<ListPage<T::Item>>,
+    // 尚未保存的修改，列表中保持服务器上的原始数据
+    drafts: HashMap<u32, T::Draft>,
+    // 有未保存的修改时请求刷新，等待确认后加载的页
+    confirm_discard: Option<usize>,
+
+    show_create_window: bool,
+    create_data: T::Draft,
+
+    // 打开详情窗口的行
+    detail: Option<u32>,
+
+    // 当前页选中的行
+    selected: HashSet<u32>,
+    bulk: Option<BulkJob<CrudRequest<T>>>,
+    // 批量任务中有新增的数据，完成后需要刷新列表
+    bulk_added: bool,
+    // 批量删除前等待确认
+    confirm_bulk_remove: bool,
+
+    // 是否定时在后台刷新当前页
+    auto_refresh: bool,
+    // 自动刷新间隔(秒)
+    refresh_interval: u32,
+    refresh_promise: Option<ApiPromise<T::ListResponse>>,
+    // 上一次后台刷新失败的原因
+    refresh_error: Option<ApiError>,
+    // 最近一次收到列表数据的时间
+    last_updated: Option<f64>,
+    // 最近一次发起后台刷新的时间
+    last_refresh: f64,
+
+    push_events: Option<Receiver<PushEvent>>,
+    // 上一帧推送连接的状态
+    push_state: Option<PushState>,
+    // 需要在后台刷新一次，例如推送中有新增的数据
+    push_resync: bool,
+    // 列表请求期间在本地确认的修改(推送、保存、删除)，请求结果可能早于这些修改，收到后重新应用
+    pending_changes: Vec<PushChange<T::Item>>,
+}
+
+impl<T: CrudEntity> CrudPage<T> {
+    pub fn new() -> Self {
+        Self {
+            entity: T::default(),
+            list_promise: None,
+            list_request: T::ListRequest::default(),
+            list_page_number: 0,
+            query: T::Query::default(),
+            applied_query: T::Query::default(),
+            sort: None,
+            server_query: None,
+            local_items: None,
+            local_loading: Vec::new(),
+            filter_changed: None,
+            add_promise: None,
+            item_operations: ItemOperations::default(),
+            save_queue: VecDeque::new(),
+            save_current: None,
+            wait_data_list: false,
+            data: None,
+            drafts: HashMap::new(),
+            confirm_discard: None,
+            show_create_window: false,
+            create_data: T::Draft::default(),
+            detail: None,
+            selected: HashSet::new(),
+            bulk: None,
+            bulk_added: false,
+            confirm_bulk_remove: false,
+            auto_refresh: false,
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            refresh_promise: None,
+            refresh_error: None,
+            last_updated: None,
+            last_refresh: 0.0,
+            push_events: None,
+            push_state: None,
+            push_resync: false,
+            pending_changes: Vec::new(),
+        }
+    }
+}
+
+impl<T: CrudEntity> RenderUI for CrudPage<T> {
+    fn render(&mut self, ctx: &egui::Context, app: &mut TemplateApp) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let api = app.api(ctx);
+            self.process_push(app.push());
+            let mut need_update_page = if self.busy() {
+                self.render_loading(ui);
+                self.render_create_window(ctx, &api, false)
+            } else {
+                self.render_create_window(ctx, &api, true)
+            };
+            if T::EDIT_MODE == EditMode::Window {
+                self.render_detail_window(ctx, &api);
+            }
+            self.process_save_queue(&api);
+            if let Some(items) = self.entity.render_windows(ctx, self.bulk.is_none()) {
+                self.start_bulk(items);
+            }
+            need_update_page |= self.process_bulk(ctx, &api);
+
+            self.render_content(ui, &api, need_update_page);
+        });
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl<T: CrudEntity> CrudPage<T> {
+    fn render_content(&mut self, ui: &mut Ui, api: &ApiClient, need_update_page: bool) {
+        let mut need_request = need_update_page;
+        let mut discard_modified = false;
+        let mut cur_page_number: usize = 0;
+        // 服务器忽略了过滤条件，需要重新请求全部数据
+        let mut requery_page = None;
+        // 分页加载全部数据时的下一页
+        let mut next_local_page = None;
+        let now = ui.input(|i| i.time);
+        if let Some(promise) = &self.list_promise {
+            if let Some(result) = promise.ready() {
+                match result {
+                    Ok(response) if self.wait_data_list => {
+                        self.wait_data_list = false;
+                        let page = T::to_page(response);
+                        let page_number = self.list_page_number;
+                        let has_query = T::has_query(&self.list_request);
+                        let page = if has_query && !page.filtered {
+                            if self.server_query == Some(false) {
+                                match self.collect_local(page) {
+                                    Some(next) => {
+                                        next_local_page = Some(next);
+                                        None
+                                    }
+                                    None => self.local_page(page_number),
+                                }
+                            } else {
+                                self.server_query = Some(false);
+                                requery_page = Some(page_number);
+                                None
+                            }
+                        } else {
+                            if has_query {
+                                self.server_query = Some(true);
+                            }
+                            Some(page)
+                        };
+
+                        if let Some(page) = page {
+                            self.set_page(page, now);
+                            self.replay_changes();
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        self.wait_data_list = false;
+                        if err.is_retryable() && ui.button("retry").clicked() {
+                            need_request = true;
+                            self.local_items = None;
+                        }
+                        render_api_error(ui, err);
+                    }
+                }
+            }
+        } else {
+            need_request = true;
+        }
+        if let Some(next) = next_local_page {
+            self.list_promise = Some(T::list(api, &local_request::<T>(next)));
+            self.wait_data_list = true;
+        }
+        self.poll_refresh(api, now);
+
+        let modified_ids = self.modified_ids();
+        let modified_count = modified_ids.len();
+        let mut bulk = None;
+
+        // 列表渲染
+        let page_info = self
+            .data
+            .as_ref()
+            .map(|data| (data.cur_page_number, data.total_count));
+        if let Some((page_number, total_count)) = page_info {
+            ui.horizontal(|ui| {
+                // 刷新按钮
+                if ui.button("🔃").clicked() {
+                    need_request = true;
+                    self.local_items = None;
+                }
+
+                // 计算页数
+                cur_page_number = page_number;
+                let page_count = total_count.div_ceil(PAGE_SIZE).max(1);
+
+                if cur_page_number > 0 && page_count <= cur_page_number {
+                    cur_page_number = page_count - 1;
+                    need_request = true;
+                }
+
+                // 页数选择
+                if page_count > 1
+                    && egui::ComboBox::from_label("Page")
+                        .selected_text(format!("{}", cur_page_number + 1))
+                        .show_index(ui, &mut cur_page_number, page_count, |i| {
+                            format!("{}", i + 1)
+                        })
+                        .changed()
+                {
+                    need_request = true;
+                }
+
+                if T::AUTO_REFRESH {
+                    self.render_refresh_status(ui, now);
+                }
+            });
+
+            // 过滤条件停止修改一段时间或按下回车后回到第一页
+            if self.entity.render_filters(ui, &mut self.query) {
+                self.filter_changed = Some(now);
+            }
+            if let Some(time) = self
+                .filter_changed
+                .filter(|_| self.confirm_discard.is_none())
+            {
+                if now - time >= FILTER_DELAY || ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    cur_page_number = 0;
+                    need_request = true;
+                } else {
+                    ui.ctx().request_repaint_after(Duration::from_secs_f64(
+                        FILTER_DELAY - (now - time),
+                    ));
+                }
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button(format!("new {}", T::NAME)).clicked() {
+                    self.show_create_window = true;
+                }
+                self.entity.render_toolbar(ui, api);
+                ui.label(format!("total : {}", total_count));
+
+                if !self.save_queue.is_empty() {
+                    ui.spinner();
+                    ui.label(format!("saving, {} left", self.save_queue.len()));
+                } else if ui
+                    .add_enabled(
+                        modified_count > 0,
+                        egui::Button::new(format!("💾 save all modified ({})", modified_count)),
+                    )
+                    .clicked()
+                {
+                    self.save_queue = modified_ids.iter().copied().collect();
+                }
+            });
+
+            if !self.selected.is_empty() {
+                bulk = self.render_bulk_toolbar(ui);
+            }
+
+            if let Some(page_number) = self.confirm_discard {
+                match render_discard_warning(ui, modified_count) {
+                    Some(true) => {
+                        self.confirm_discard = None;
+                        cur_page_number = page_number;
+                        need_request = true;
+                        discard_modified = true;
+                    }
+                    Some(false) => {
+                        // 还没有应用的过滤条件一起撤销
+                        self.confirm_discard = None;
+                        self.filter_changed = None;
+                        self.query = self.applied_query.clone();
+                    }
+                    None => {}
+                }
+            }
+
+            if self.render_table(ui, api) {
+                cur_page_number = 0;
+                need_request = true;
+            }
+        }
+
+        if let Some(items) = bulk {
+            self.start_bulk(items);
+        }
+
+        if let Some(page_number) = requery_page {
+            cur_page_number = page_number;
+            need_request = true;
+            discard_modified = true;
+        }
+
+        // 有未保存的修改时先确认
+        if need_request && modified_count > 0 && !discard_modified {
+            self.confirm_discard = Some(cur_page_number);
+            need_request = false;
+        }
+
+        // 新增的数据需要从服务器重新加载
+        if need_update_page {
+            self.local_items = None;
+        }
+
+        // 请求列表数据
+        if need_request {
+            self.list_request = T::list_request(&self.query, self.sort, cur_page_number, PAGE_SIZE);
+            self.list_page_number = cur_page_number;
+            self.applied_query = self.query.clone();
+            self.filter_changed = None;
+            // 之前的后台刷新对应旧的请求
+            self.refresh_promise = None;
+            self.push_resync = false;
+            self.pending_changes.clear();
+            if !T::has_query(&self.list_request) {
+                self.local_items = None;
+            }
+            match self.local_page(cur_page_number) {
+                Some(page) => {
+                    self.wait_data_list = false;
+                    self.set_page(page, now);
+                }
+                None => {
+                    self.list_promise = Some(self.request_list(api));
+                    self.wait_data_list = true;
+                    self.entity.on_list_request(api);
+                }
+            }
+        } else if self.need_refresh(now) {
+            self.refresh_promise = Some(self.request_list(api));
+            self.last_refresh = now;
+            self.push_resync = false;
+            self.pending_changes.clear();
+        }
+    }
+
+    /// 暂存分页加载的一页，还有下一页时返回页码，否则全部数据移到 local_items
+    fn collect_local(&mut self, page: ListPage<T::Item>) -> Option<usize> {
+        let done = page.items.is_empty()
+            || self.local_loading.len() + page.items.len() >= page.total_count;
+        self.local_loading.extend(page.items);
+        if done {
+            self.local_items = Some(std::mem::take(&mut self.local_loading));
+            None
+        } else {
+            Some(page.cur_page_number + 1)
+        }
+    }
+
+    /// 服务器不支持过滤/排序时用已加载的全部数据处理当前的请求
+    fn local_page(&self, page_number: usize) -> Option<ListPage<T::Item>> {
+        if self.server_query != Some(false) {
+            return None;
+        }
+        let page = ListPage {
+            items: self.local_items.clone()?,
+            cur_page_number: 0,
+            total_count: 0,
+            filtered: false,
+        };
+        Some(local_page::<T>(&self.list_request, page_number, page))
+    }
+
+    /// 显示新加载的列表，丢弃草稿和正在进行的操作
+    fn set_page(&mut self, page: ListPage<T::Item>, now: f64) {
+        // 只保留新列表中仍然存在的选中项
+        self.selected
+            .retain(|id| page.items.iter().any(|x| T::id(x) == *id));
+        self.data = Some(page);
+        self.drafts.clear();
+        self.item_operations.clear();
+        self.save_queue.clear();
+        self.save_current = None;
+        self.last_updated = Some(now);
+        self.refresh_error = None;
+    }
+
+    /// 按当前的请求加载列表，服务器不支持过滤/排序时分页加载全部数据由客户端处理
+    fn request_list(&mut self, api: &ApiClient) -> ApiPromise<T::ListResponse> {
+        if T::has_query(&self.list_request) && self.server_query == Some(false) {
+            self.local_loading.clear();
+            T::list(api, &local_request::<T>(0))
+        } else {
+            T::list(api, &self.list_request)
+        }
+    }
+
+    /// 自动刷新开关、间隔和上次更新时间
+    fn render_refresh_status(&mut self, ui: &mut Ui, now: f64) {
+        ui.separator();
+        if self.push_state == Some(PushState::Connected) {
+            ui.colored_label(Color32::GREEN, "● live")
+                .on_hover_text("updates are pushed by the server");
+            return;
+        }
+        ui.toggle_value(&mut self.auto_refresh, "auto refresh");
+        ui.add_enabled(
+            self.auto_refresh,
+            egui::DragValue::new(&mut self.refresh_interval)
+                .range(1..=3600)
+                .suffix(" s"),
+        );
+        if let Some(time) = self.last_updated {
+            ui.weak(format!(
+                "last updated {} s ago",
+                (now - time).max(0.0) as u64
+            ));
+            // 保持提示中的时间走动
+            ui.ctx().request_repaint_after(Duration::from_secs(1));
+        }
+        if let Some(err) = &self.refresh_error {
+            render_api_error(ui, err);
+        }
+    }
+
+    /// 是否到了自动刷新的时间，有其他请求或等待用户确认时推迟
+    fn need_refresh(&self, now: f64) -> bool {
+        // 推送可用时不需要定时刷新
+        let polling =
+            T::AUTO_REFRESH && self.auto_refresh && self.push_state != Some(PushState::Connected);
+        if !(polling || self.push_resync)
+            || self.data.is_none()
+            || self.refresh_promise.is_some()
+            || is_pending(&self.list_promise)
+            || self.bulk.is_some()
+            || self.confirm_discard.is_some()
+        {
+            return false;
+        }
+        let last = self.last_updated.unwrap_or(0.0).max(self.last_refresh);
+        self.push_resync || now - last >= self.refresh_interval as f64
+    }
+
+    /// 把推送事件应用到当前页
+    fn process_push(&mut self, channel: Option<&PushChannel>) {
+        let Some(channel) = channel else {
+            return;
+        };
+        let state = channel.state();
+        if self.push_state != Some(state) {
+            match state {
+                // 断开期间的变化需要刷新获取
+                PushState::Connected => self.push_resync = true,
+                // 服务器不支持推送时退回到定时刷新
+                PushState::Unsupported => self.auto_refresh = true,
+                _ => {}
+            }
+            self.push_state = Some(state);
+        }
+
+        let events: Vec<PushEvent> = self
+            .push_events
+            .get_or_insert_with(|| channel.subscribe())
+            .try_iter()
+            .collect();
+        for event in events {
+            let Some(data) = &self.data else {
+                continue;
+            };
+            match T::push_change(&event, &data.items) {
+                Some(PushChange::Updated(item)) => self.updated(item),
+                Some(PushChange::Added) => self.push_resync = true,
+                Some(PushChange::Removed(id)) => {
+                    self.removed(id);
+                    // 后面的数据移到当前页
+                    self.push_resync = true;
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// 后台刷新完成后只替换服务器数据，草稿和正在进行的操作保持不变
+    fn poll_refresh(&mut self, api: &ApiClient, now: f64) {
+        let Some(promise) = self.refresh_promise.take() else {
+            return;
+        };
+        let result = match promise.try_take() {
+            Ok(result) => result,
+            Err(promise) => {
+                self.refresh_promise = Some(promise);
+                return;
+            }
+        };
+        match result {
+            Ok(response) => {
+                let mut page = T::to_page(&response);
+                if T::has_query(&self.list_request) && !page.filtered {
+                    // 服务器对过滤条件的支持有变化，等下次手动刷新处理
+                    if self.server_query != Some(false) {
+                        return;
+                    }
+                    if let Some(next) = self.collect_local(page) {
+                        self.refresh_promise = Some(T::list(api, &local_request::<T>(next)));
+                        return;
+                    }
+                    let Some(local) = self.local_page(self.list_page_number) else {
+                        return;
+                    };
+                    page = local;
+                }
+                self.selected
+                    .retain(|id| page.items.iter().any(|x| T::id(x) == *id));
+                self.data = Some(page);
+                self.replay_changes();
+                self.last_updated = Some(now);
+                self.refresh_error = None;
+            }
+            Err(err) => self.refresh_error = Some(err),
+        }
+    }
+
+    fn render_loading(&self, ui: &mut Ui) {
+        ui.painter().rect_filled(ui.max_rect(), 0.0, GRAY);
+
+        egui::Spinner::new().paint_at(
+            ui,
+            Rect::from_center_size(ui.max_rect().center(), vec2(30.0, 30.0)),
+        );
+
+        // 屏蔽下层输入
+        ui.interact(
+            ui.min_rect(),
+            egui::Id::new("Some Id"),
+            egui::Sense::click(),
+        );
+    }
+
+    /// 返回排序条件是否改变
+    fn render_table(&mut self, ui: &mut Ui, api: &ApiClient) -> bool {
+        let mut sort_changed = false;
+        let mut need_update_item_info = None;
+        let mut need_remove_item_info = None;
+        let mut need_revert_item = None;
+        let mut need_open_detail = None;
+        let mut need_toggle_select = None;
+        let mut need_select_page = None;
+
+        let Self {
+            entity,
+            data,
+            drafts,
+            selected,
+            item_operations: operations,
+            sort,
+            detail,
+            ..
+        } = self;
+        let Some(data) = data else {
+            return false;
+        };
+        let entity = &*entity;
+        let columns = entity.columns();
+        let page_ids: Vec<u32> = data.items.iter().map(T::id).collect();
+
+        let mut table = TableBuilder::new(ui)
+            .striped(true)
+            .resizable(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::auto());
+        for column in &columns {
+            table = table.column(column.width);
+        }
+        table
+            .column(Column::auto())
+            .column(Column::auto())
+            .min_scrolled_height(0.0)
+            .header(20.0, |mut header| {
+                header.col(|ui| {
+                    // 全选当前页
+                    let mut all =
+                        !page_ids.is_empty() && page_ids.iter().all(|id| selected.contains(id));
+                    if ui
+                        .checkbox(&mut all, "")
+                        .on_hover_text("select all on page")
+                        .changed()
+                    {
+                        need_select_page = Some(all);
+                    }
+                });
+                for column in &columns {
+                    header.col(|ui| match column.sort {
+                        Some(key) => sort_changed |= sort_header(ui, column.title, key, sort),
+                        None => {
+                            ui.strong(column.title);
+                        }
+                    });
+                }
+                header.col(|ui| {
+                    ui.strong(match T::EDIT_MODE {
+                        EditMode::Inline => "update",
+                        EditMode::Window => "edit",
+                    });
+                });
+                header.col(|ui| {
+                    ui.strong("remove");
+                });
+            })
+            .body(|mut body| {
+                for (index, origin) in data.items.iter().enumerate() {
+                    let id = T::id(origin);
+                    // 有草稿时显示修改后的数据
+                    let pristine = T::to_draft(origin);
+                    let mut draft = drafts.get(&id).cloned().unwrap_or_else(|| pristine.clone());
+                    let item = T::from_draft(&draft, origin);
+                    let modified = item != *origin;
+                    body.row(20.0, |mut row| {
+                        row.col(|ui| {
+                            let mut checked = selected.contains(&id);
+                            if ui.checkbox(&mut checked, "").changed() {
+                                need_toggle_select = Some(id);
+                            }
+                        });
+                        let mut crud_row = CrudRow {
+                            index,
+                            origin,
+                            item: &item,
+                            draft: &mut draft,
+                        };
+                        for column in 0..columns.len() {
+                            row.col(|ui| entity.cell(ui, column, &mut crud_row));
+                        }
+                        row.col(|ui| match (&T::EDIT_MODE, operations.get(id)) {
+                            (_, Some((Operation::Update(_), None))) => {
+                                ui.spinner();
+                            }
+                            (EditMode::Inline, Some((Operation::Update(_), Some(Err(err))))) => {
+                                let text = if err.is_retryable() {
+                                    "🔄retry"
+                                } else {
+                                    "🔄update"
+                                };
+                                if ui.button(text).clicked() {
+                                    need_update_item_info = Some(id);
+                                }
+                                render_api_error(ui, err);
+                            }
+                            // 正在删除当前元素
+                            (EditMode::Inline, Some((Operation::Remove, None))) => {}
+                            (EditMode::Inline, _) => {
+                                if ui.button("🔄update").clicked() {
+                                    need_update_item_info = Some(id);
+                                }
+                                if modified && ui.button("↺").on_hover_text("revert").clicked() {
+                                    need_revert_item = Some(id);
+                                }
+                            }
+                            (EditMode::Window, _) => {
+                                let text = if modified { "✏*" } else { "✏" };
+                                if ui
+                                    .selectable_label(*detail == Some(id), text)
+                                    .on_hover_text("details")
+                                    .clicked()
+                                {
+                                    need_open_detail = Some(id);
+                                }
+                                if modified && ui.button("↺").on_hover_text("revert").clicked() {
+                                    need_revert_item = Some(id);
+                                }
+                            }
+                        });
+                        row.col(|ui| match operations.get(id) {
+                            Some((Operation::Remove, None)) => {
+                                ui.spinner();
+                            }
+                            Some((Operation::Remove, Some(Err(err)))) => {
+                                if ui.button("✖").clicked() {
+                                    need_remove_item_info = Some(id);
+                                }
+                                render_api_error(ui, err);
+                            }
+                            // 正在更新当前元素
+                            Some((Operation::Update(_), None)) => {}
+                            _ => {
+                                if ui.button("✖").clicked() {
+                                    need_remove_item_info = Some(id);
+                                }
+                            }
+                        });
+                    });
+
+                    // 只保留有修改的草稿
+                    if draft != pristine {
+                        drafts.insert(id, draft);
+                    } else {
+                        drafts.remove(&id);
+                    }
+                }
+            });
+
+        if need_open_detail.is_some() {
+            self.detail = need_open_detail;
+        }
+
+        if let Some(all) = need_select_page {
+            if all {
+                self.selected.extend(page_ids);
+            } else {
+                self.selected.clear();
+            }
+        }
+
+        if let Some(id) = need_toggle_select {
+            if !self.selected.remove(&id) {
+                self.selected.insert(id);
+            }
+        }
+
+        // 还原修改
+        if let Some(id) = need_revert_item {
+            self.drafts.remove(&id);
+        }
+
+        // 更新操作
+        if let Some(id) = need_update_item_info {
+            self.update_item(api, id);
+        }
+
+        // 删除操作
+        if let Some(id) = need_remove_item_info {
+            self.item_operations
+                .start(id, Operation::Remove, T::remove(api, id));
+        }
+
+        sort_changed
+    }
+
+    /// 选中行后显示的批量操作按钮
+    fn render_bulk_toolbar(&mut self, ui: &mut Ui) -> Option<BulkItems<T>> {
+        let selected: Vec<&T::Item> = self
+            .data
+            .iter()
+            .flat_map(|data| data.items.iter())
+            .filter(|x| self.selected.contains(&T::id(x)))
+            .collect();
+        let selected_count = self.selected.len();
+        let confirm_remove = &mut self.confirm_bulk_remove;
+        let entity = &mut self.entity;
+
+        let mut result = None;
+        ui.add_enabled_ui(self.bulk.is_none(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} selected", selected_count));
+                result = entity.render_bulk_actions(ui, &selected);
+                if ui.button("🗑 delete").clicked() {
+                    *confirm_remove = true;
+                }
+            });
+
+            if *confirm_remove {
+                ui.horizontal(|ui| {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!("delete {} {}(s)?", selected_count, T::NAME),
+                    );
+                    if ui.button("yes").clicked() {
+                        *confirm_remove = false;
+                        let items = selected
+                            .iter()
+                            .map(|x| (T::label(x), CrudRequest::Remove(T::id(x))))
+                            .collect();
+                        result = Some((format!("Remove {}s", T::NAME), items));
+                    }
+                    if ui.button("cancel").clicked() {
+                        *confirm_remove = false;
+                    }
+                });
+            }
+        });
+        result
+    }
+
+    fn render_create_window(
+        &mut self,
+        ctx: &egui::Context,
+        api: &ApiClient,
+        enabled: bool,
+    ) -> bool {
+        let mut request_finish = false;
+        egui::Window::new(format!("New {}", title::<T>()))
+            .vscroll(true)
+            .hscroll(true)
+            .resizable(true)
+            .collapsible(true)
+            .open(&mut self.show_create_window)
+            .enabled(enabled)
+            .show(ctx, |ui| {
+                self.entity.form(ui, &mut self.create_data);
+                ui.separator();
+                ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+                    if ui
+                        .add_enabled(
+                            T::is_valid(&self.create_data),
+                            egui::Button::new("   ok   "),
+                        )
+                        .clicked()
+                        && !is_pending(&self.add_promise)
+                    {
+                        self.add_promise = Some(T::add(api, &self.create_data));
+                    }
+
+                    if let Some(promise) = &self.add_promise {
+                        if let Some(result) = promise.ready() {
+                            match result {
+                                Ok(_) => {
+                                    request_finish = true;
+                                }
+                                Err(err) => {
+                                    render_api_error(ui, err);
+                                }
+                            }
+                        } else {
+                            ui.spinner();
+                        }
+                    }
+                });
+            });
+
+        if request_finish {
+            self.add_promise = None;
+            self.show_create_window = false;
+            self.create_data = T::Draft::default();
+            if let Some(data) = &mut self.data {
+                data.total_count += 1;
+                if data.items.len() < PAGE_SIZE {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// 详情窗口，编辑全部字段，关闭后未保存的修改保留在草稿中
+    fn render_detail_window(&mut self, ctx: &egui::Context, api: &ApiClient) {
+        let Some(id) = self.detail else {
+            return;
+        };
+        let Some(origin) = self.find_item(id) else {
+            self.detail = None;
+            return;
+        };
+        let pristine = T::to_draft(origin);
+        let form = self.drafts.entry(id).or_insert_with(|| pristine.clone());
+
+        let operation = self.item_operations.get(id);
+        let saving = matches!(operation, Some((Operation::Update(_), None)));
+        let dirty = *form != pristine;
+
+        let mut open = true;
+        let mut need_save = false;
+        let mut need_revert = false;
+        let title = if dirty {
+            format!("{} #{} *", title::<T>(), id)
+        } else {
+            format!("{} #{}", title::<T>(), id)
+        };
+        egui::Window::new(title)
+            .id(egui::Id::new((T::NAME, "detail")))
+            .vscroll(true)
+            .resizable(true)
+            .collapsible(true)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.add_enabled_ui(!saving, |ui| {
+                    self.entity.form(ui, form);
+                });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    let can_save =
+                        dirty && T::is_valid(form) && !matches!(operation, Some((_, None)));
+                    if ui
+                        .add_enabled(can_save, egui::Button::new("💾 Save"))
+                        .clicked()
+                    {
+                        need_save = true;
+                    }
+                    if ui
+                        .add_enabled(dirty && !saving, egui::Button::new("↺ Revert"))
+                        .clicked()
+                    {
+                        need_revert = true;
+                    }
+
+                    match operation {
+                        Some((Operation::Update(_), None)) => {
+                            ui.spinner();
+                        }
+                        Some((Operation::Update(_), Some(Err(err)))) => {
+                            render_api_error(ui, err);
+                        }
+                        _ => {
+                            if dirty {
+                                ui.label("modified");
+                            }
+                        }
+                    }
+                });
+            });
+
+        if need_save {
+            self.update_item(api, id);
+        }
+
+        if need_revert {
+            self.drafts.remove(&id);
+        }
+
+        if !open {
+            self.detail = None;
+            // 没有修改的草稿不需要保留
+            if !dirty {
+                self.drafts.remove(&id);
+            }
+        }
+    }
+
+    /// 提交行中的修改
+    fn update_item(&mut self, api: &ApiClient, id: u32) {
+        let Some(origin) = self.find_item(id) else {
+            return;
+        };
+        let item = match self.drafts.get(&id) {
+            Some(draft) if !T::is_valid(draft) => return,
+            Some(draft) => T::from_draft(draft, origin),
+            None => origin.clone(),
+        };
+        let promise = T::update(api, &item);
+        self.item_operations
+            .start(id, Operation::Update(item), promise);
+    }
+
+    /// 依次提交修改过的行，出错时停止
+    fn process_save_queue(&mut self, api: &ApiClient) {
+        if let Some(id) = self.save_current {
+            match self.item_operations.get(id) {
+                Some((Operation::Update(_), None)) => return,
+                Some((Operation::Update(_), Some(Err(_)))) => self.save_queue.clear(),
+                _ => {}
+            }
+            self.save_current = None;
+        }
+        let modified_ids = self.modified_ids();
+        while let Some(id) = self.save_queue.pop_front() {
+            // 校验不通过的修改留给用户处理
+            if modified_ids.contains(&id)
+                && !self.item_operations.is_pending(id)
+                && self.drafts.get(&id).map_or(false, T::is_valid)
+            {
+                self.update_item(api, id);
+                self.save_current = Some(id);
+                break;
+            }
+        }
+    }
+
+    fn start_bulk(&mut self, (title, items): BulkItems<T>) {
+        if self.bulk.is_none() {
+            self.bulk = Some(BulkJob::new(title, items));
+        }
+    }
+
+    /// 推进批量任务，成功的结果直接写回列表，返回是否需要刷新列表
+    fn process_bulk(&mut self, ctx: &egui::Context, api: &ApiClient) -> bool {
+        let Some(job) = &mut self.bulk else {
+            return false;
+        };
+        let succeeded = job.poll(|request| match request {
+            CrudRequest::Add(draft) => T::add(api, draft),
+            CrudRequest::Update(item) => T::update(api, item),
+            CrudRequest::Remove(id) => T::remove(api, *id),
+        });
+        let running = job.is_running();
+        if !job.show(ctx) {
+            self.bulk = None;
+        }
+
+        for request in succeeded {
+            match request {
+                CrudRequest::Add(_) => {
+                    self.bulk_added = true;
+                    if let Some(data) = &mut self.data {
+                        data.total_count += 1;
+                    }
+                }
+                CrudRequest::Update(item) => self.updated(*item),
+                CrudRequest::Remove(id) => self.removed(id),
+            }
+        }
+
+        // 新增的数据需要刷新后才能显示
+        !running && std::mem::take(&mut self.bulk_added)
+    }
+
+    /// 重新应用请求期间确认的修改
+    fn replay_changes(&mut self) {
+        for change in std::mem::take(&mut self.pending_changes) {
+            match change {
+                PushChange::Updated(item) => self.updated(item),
+                PushChange::Removed(id) => self.removed(id),
+                PushChange::Added => {}
+            }
+        }
+    }
+
+    /// 是否有列表请求正在进行，期间的修改需要记录
+    fn loading(&self) -> bool {
+        self.wait_data_list || self.refresh_promise.is_some()
+    }
+
+    /// 更新成功后写回列表
+    fn updated(&mut self, item: T::Item) {
+        if self.loading() {
+            self.pending_changes.push(PushChange::Updated(item.clone()));
+        }
+        let id = T::id(&item);
+        // 提交后没有再修改过的草稿可以丢弃
+        if self.drafts.get(&id) == Some(&T::to_draft(&item)) {
+            self.drafts.remove(&id);
+        }
+        if let Some(row) = self
+            .local_items
+            .as_mut()
+            .and_then(|items| items.iter_mut().find(|x| T::id(x) == id))
+        {
+            *row = item.clone();
+        }
+        if let Some(row) = self
+            .data
+            .as_mut()
+            .and_then(|data| data.items.iter_mut().find(|x| T::id(x) == id))
+        {
+            *row = item;
+        }
+    }
+
+    /// 删除成功后从列表中移除
+    fn removed(&mut self, id: u32) {
+        if self.loading() {
+            self.pending_changes.push(PushChange::Removed(id));
+        }
+        if let Some(items) = &mut self.local_items {
+            items.retain(|x| T::id(x) != id);
+        }
+        if let Some(data) = &mut self.data {
+            let count = data.items.len();
+            data.items.retain(|x| T::id(x) != id);
+            data.total_count -= count - data.items.len();
+        }
+        if self.detail == Some(id) {
+            self.detail = None;
+        }
+        self.drafts.remove(&id);
+        self.selected.remove(&id);
+    }
+
+    fn find_item(&self, id: u32) -> Option<&T::Item> {
+        self.data
+            .as_ref()
+            .and_then(|data| data.items.iter().find(|x| T::id(x) == id))
+    }
+
+    /// 草稿与服务器数据不同的行
+    fn modified_ids(&self) -> Vec<u32> {
+        let Some(data) = &self.data else {
+            return Vec::new();
+        };
+        data.items
+            .iter()
+            .filter(|x| {
+                self.drafts
+                    .get(&T::id(x))
+                    .map_or(false, |draft| T::from_draft(draft, x) != **x)
+            })
+            .map(T::id)
+            .collect()
+    }
+
+    fn busy(&mut self) -> bool {
+        for (id, operation) in self.item_operations.take_finished() {
+            match operation {
+                Operation::Remove => self.removed(id),
+                Operation::Update(item) => self.updated(item),
+            }
+        }
+
+        is_pending(&self.list_promise) || is_pending(&self.add_promise)
+    }
+}
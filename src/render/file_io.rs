@@ -0,0 +1,82 @@
+//! 读写配置文件，native 使用本地路径，wasm 通过浏览器下载/上传
+
+#[cfg(target_arch = "wasm32")]
+use std::sync::{Arc, Mutex};
+
+/// 上传完成后的文件内容
+#[cfg(target_arch = "wasm32")]
+pub type PendingUpload = Arc<Mutex<Option<Result<String, String>>>>;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_file(path: &str, text: &str) -> Result<(), String> {
+    std::fs::write(path, text).map_err(|err| format!("{}: {}", path, err))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_file(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn js_error(err: wasm_bindgen::JsValue) -> String {
+    err.as_string().unwrap_or_else(|| format!("{:?}", err))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn document() -> Result<web_sys::Document, String> {
+    web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| "no document".to_string())
+}
+
+/// 浏览器下载文本文件
+#[cfg(target_arch = "wasm32")]
+pub fn download(file_name: &str, text: &str) -> Result<(), String> {
+    use wasm_bindgen::JsCast;
+
+    let parts = js_sys::Array::of1(&wasm_bindgen::JsValue::from_str(text));
+    let blob = web_sys::Blob::new_with_str_sequence(&parts).map_err(js_error)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(js_error)?;
+    let anchor: web_sys::HtmlAnchorElement = document()?
+        .create_element("a")
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(|_| "create anchor failed".to_string())?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    web_sys::Url::revoke_object_url(&url).map_err(js_error)
+}
+
+/// 弹出浏览器的文件选择框，读取完成后写入 `pending` 并刷新界面
+#[cfg(target_arch = "wasm32")]
+pub fn upload(ctx: &egui::Context, accept: &str, pending: PendingUpload) -> Result<(), String> {
+    use wasm_bindgen::JsCast;
+
+    let input: web_sys::HtmlInputElement = document()?
+        .create_element("input")
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(|_| "create input failed".to_string())?;
+    input.set_type("file");
+    input.set_accept(accept);
+
+    let ctx = ctx.clone();
+    let file_input = input.clone();
+    let on_change = wasm_bindgen::closure::Closure::once_into_js(move || {
+        let Some(file) = file_input.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = wasm_bindgen_futures::JsFuture::from(file.text())
+                .await
+                .map(|text| text.as_string().unwrap_or_default())
+                .map_err(js_error);
+            *pending.lock().unwrap() = Some(result);
+            ctx.request_repaint();
+        });
+    });
+    input.set_onchange(Some(on_change.unchecked_ref()));
+    input.click();
+    Ok(())
+}
@@ -1,19 +1,35 @@
 use super::password::password;
-use crate::proto;
-use crate::render::RenderUI;
-use crate::resource::ResponseType;
+use crate::api::{is_pending, ApiPromise};
+use crate::auth::{AuthMode, Session};
+use crate::profile::{find_profile, ProfileColor};
+use crate::render::{render_api_error, RenderUI};
+use crate::secret::{SecretBackend, SecretError};
 use crate::TemplateApp;
 use egui::{Align2, Ui};
-use regex::Regex;
 
 pub struct Logic {
-    key_login: String,
+    login_promise: Option<ApiPromise<Session>>,
+    /// 保存配置的名称/颜色/是否记住密码
+    profile_name: String,
+    profile_color: ProfileColor,
+    remember_password: bool,
+    // 上一帧选中的配置，切换后重新填写保存配置的表单
+    shown_profile: Option<Option<usize>>,
+    /// 主密码
+    passphrase: String,
+    secret_error: Option<SecretError>,
 }
 
 impl Logic {
     pub fn new() -> Self {
         Self {
-            key_login: "login".into(),
+            login_promise: None,
+            profile_name: String::new(),
+            profile_color: ProfileColor::None,
+            remember_password: false,
+            shown_profile: None,
+            passphrase: String::new(),
+            secret_error: None,
         }
     }
 }
@@ -36,8 +52,11 @@ impl RenderUI for Logic {
 }
 
 impl Logic {
-    fn render_content(&self, ui: &mut Ui, ctx: &egui::Context, app: &mut TemplateApp) {
+    fn render_content(&mut self, ui: &mut Ui, ctx: &egui::Context, app: &mut TemplateApp) {
         ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+            let pending = is_pending(&self.login_promise);
+            ui.add_enabled_ui(!pending, |ui| self.render_profile_picker(ui, app));
+
             if app.can_modify_api_url {
                 ui.horizontal(|ui| {
                     ui.label("api url:");
@@ -46,84 +65,215 @@ impl Logic {
             }
 
             ui.horizontal(|ui| {
-                ui.label("username:");
-                ui.text_edit_singleline(&mut app.username);
+                ui.label("auth:");
+                for mode in AuthMode::ALL {
+                    ui.radio_value(&mut app.auth_mode, mode, mode.name());
+                }
             });
 
+            // API key 方式只需要 key
+            if app.auth_mode != AuthMode::ApiKey {
+                ui.horizontal(|ui| {
+                    ui.label("username:");
+                    ui.text_edit_singleline(&mut app.username);
+                });
+            }
+
             ui.horizontal(|ui| {
-                ui.label("password:");
+                if app.auth_mode == AuthMode::ApiKey {
+                    ui.label("api key:");
+                } else {
+                    ui.label("password:");
+                }
                 ui.add(password(&mut app.password));
             });
 
+            self.render_remember_me(ui, app);
+            ui.collapsing("save as profile", |ui| self.render_profile_editor(ui, app));
+
             ui.separator();
-            if ui.button("Login").clicked() && app.can_request(&self.key_login) {
-                let req = proto::LoginReq {
-                    username: app.username.clone(),
-                    password: app.password.clone(),
-                };
-                app.http_request(
-                    ctx,
-                    &self.key_login,
-                    None,
-                    serde_json::to_string(&req).unwrap().into(),
-                );
+            if ui.button("Login").clicked() && !is_pending(&self.login_promise) {
+                let req = app.login_req();
+                self.login_promise = Some(app.api(ctx).login(app.auth_mode, &req));
             }
 
-            if let Some(promise) = app.promise_map.get(&self.key_login) {
+            let mut login_session = None;
+            if let Some(promise) = &self.login_promise {
                 if let Some(result) = promise.ready() {
                     match result {
-                        Ok(resource) => match &resource.response_data {
-                            ResponseType::GeneralResponse(_) => {
-                                app.login_success(extract_cookies(&resource.response));
-                            }
-                            ResponseType::Error(err) => {
-                                ui.colored_label(ui.visuals().error_fg_color, err);
-                            }
-                            _ => {
-                                ui.colored_label(ui.visuals().error_fg_color, "Unknown error");
-                            }
-                        },
-                        Err(error) => {
-                            ui.colored_label(
-                                ui.visuals().error_fg_color,
-                                if error.is_empty() {
-                                    "Login failed"
-                                } else {
-                                    error
-                                },
-                            );
+                        Ok(session) => {
+                            login_session = Some(session.clone());
+                        }
+                        Err(err) => {
+                            render_api_error(ui, err);
                         }
                     }
                 } else {
                     ui.spinner();
                 }
             }
+
+            if let Some(session) = login_session {
+                self.login_promise = None;
+                app.login_success(session.shared());
+            }
         });
     }
-}
 
-// 提取响应中的 Set-Cookie 头部
-fn extract_cookies(response: &ehttp::Response) -> Vec<String> {
-    response
-        .headers
-        .headers
-        .iter()
-        .filter_map(|(name, value)| {
-            if name.eq_ignore_ascii_case("Set-Cookie") {
-                // 使用正则表达式查找cookie名称和值
-                // auth-id=mYiQZEtaR9EFh0KUXThIPfpu%2FyWu91D8IxUq2SRX6660xi57K84uv40gVA8YVYImklEngoO4njikpDr5Q6o%3D; HttpOnly; SameSite=Lax; Path=/; Max-Age=3600
-                // 只捕获 auth-id=mYiQZEtaR9EFh0KUXThIPfpu%2FyWu91D8IxUq2SRX6660xi57K84uv40gVA8YVYImklEngoO4njikpDr5Q6o%3D
-                let cookie_re = Regex::new(r"[^;=\s]+=[^;]*").unwrap();
-                for cap in cookie_re.captures_iter(value) {
-                    if cap.len() > 0 {
-                        return Some(cap[0].to_owned());
+    /// 是否保存密码和会话，以及保存方式和主密码
+    fn render_remember_me(&mut self, ui: &mut Ui, app: &mut TemplateApp) {
+        let mut backend = app.secret_backend;
+        ui.horizontal(|ui| {
+            let response = ui
+                .checkbox(&mut app.remember_me, "remember me")
+                .on_hover_text("keep the password and session after restart");
+            if response.changed() {
+                self.secret_error = None;
+                if !app.remember_me {
+                    app.forget_secrets();
+                } else if backend == SecretBackend::Keyring {
+                    self.secret_error = app.open_keyring().err();
+                }
+            }
+
+            let backends = SecretBackend::available();
+            if app.remember_me && backends.len() > 1 {
+                egui::ComboBox::from_id_source("secret_backend")
+                    .selected_text(backend.name())
+                    .show_ui(ui, |ui| {
+                        for x in backends {
+                            ui.selectable_value(&mut backend, *x, x.name());
+                        }
+                    });
+            }
+        });
+        if backend != app.secret_backend {
+            app.forget_secrets();
+            app.secret_backend = backend;
+            self.secret_error = None;
+            if backend == SecretBackend::Keyring {
+                self.secret_error = app.open_keyring().err();
+            }
+        }
+
+        if app.remember_me
+            && app.secret_backend == SecretBackend::Passphrase
+            && !app.secrets_unlocked()
+        {
+            let locked = app.secrets_locked();
+            ui.horizontal(|ui| {
+                ui.label("master passphrase:");
+                // 密码框会占满剩余宽度，按钮放在后面需要限制大小
+                let size = egui::vec2(160.0, ui.spacing().interact_size.y);
+                ui.add_sized(size, password(&mut self.passphrase));
+                let text = if locked {
+                    "🔓 unlock"
+                } else {
+                    "🔒 set passphrase"
+                };
+                if ui
+                    .add_enabled(!self.passphrase.is_empty(), egui::Button::new(text))
+                    .clicked()
+                {
+                    self.secret_error = app.unlock_secrets(&self.passphrase).err();
+                    if self.secret_error.is_none() {
+                        self.passphrase.clear();
+                    }
+                }
+                if locked
+                    && ui
+                        .button("forget")
+                        .on_hover_text("delete the saved credentials")
+                        .clicked()
+                {
+                    app.forget_secrets();
+                    self.secret_error = None;
+                }
+            });
+        }
+
+        if let Some(err) = &self.secret_error {
+            ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+        }
+    }
+
+    /// 选择保存的服务器
+    fn render_profile_picker(&mut self, ui: &mut Ui, app: &mut TemplateApp) {
+        if self.shown_profile != Some(app.active_profile) {
+            self.shown_profile = Some(app.active_profile);
+            if let Some(profile) = app.active_profile.and_then(|x| app.profiles.get(x)) {
+                self.profile_name = profile.name.clone();
+                self.profile_color = profile.color;
+                self.remember_password = profile.password.is_some();
+            }
+        }
+        if app.profiles.is_empty() {
+            return;
+        }
+
+        let mut selected = app.active_profile;
+        let mut remove = None;
+        ui.horizontal(|ui| {
+            ui.label("profile:");
+            let text = match selected.and_then(|x| app.profiles.get(x)) {
+                Some(profile) => profile.title(),
+                None => egui::RichText::new("none"),
+            };
+            egui::ComboBox::from_id_source("login_profile")
+                .selected_text(text)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut selected, None, "none");
+                    for (index, profile) in app.profiles.iter().enumerate() {
+                        ui.selectable_value(&mut selected, Some(index), profile.title())
+                            .on_hover_text(&profile.api_url);
                     }
-                    break;
+                });
+            if let Some(index) = app.active_profile {
+                if ui.button("🗑").on_hover_text("delete profile").clicked() {
+                    remove = Some(index);
                 }
-                None
-            } else {
-                None
             }
-        })
-        .collect()
+        });
+
+        if selected != app.active_profile {
+            app.switch_profile(selected);
+        } else if let Some(index) = remove {
+            app.remove_profile(index);
+        }
+    }
+
+    /// 把当前的地址和用户名保存为配置
+    fn render_profile_editor(&mut self, ui: &mut Ui, app: &mut TemplateApp) {
+        ui.horizontal(|ui| {
+            ui.label("name:");
+            ui.text_edit_singleline(&mut self.profile_name);
+        });
+        ui.horizontal(|ui| {
+            ui.label("color:");
+            for color in ProfileColor::ALL {
+                let text = match color.color() {
+                    Some(x) => egui::RichText::new("●").color(x),
+                    None => egui::RichText::new("○"),
+                };
+                ui.selectable_value(&mut self.profile_color, color, text)
+                    .on_hover_text(color.name());
+            }
+        });
+        ui.checkbox(&mut self.remember_password, "remember password")
+            .on_hover_text("kept after restart only with remember me");
+
+        let name = self.profile_name.trim();
+        let text = if find_profile(&app.profiles, name).is_some() {
+            "💾 update profile"
+        } else {
+            "💾 save profile"
+        };
+        if ui
+            .add_enabled(!name.is_empty(), egui::Button::new(text))
+            .clicked()
+        {
+            app.save_profile(name, self.profile_color, self.remember_password);
+            self.shown_profile = Some(app.active_profile);
+        }
+    }
 }
@@ -0,0 +1,151 @@
+//! 隧道自定义映射(`custom_mapping`)的键值编辑器
+
+use egui::Ui;
+use std::collections::HashMap;
+
+/// 键值编辑器，编辑过程中允许出现空行/重复行，校验通过后才能写回 `HashMap`
+#[derive(Clone, Default, PartialEq)]
+pub struct MappingEditor {
+    rows: Vec<(String, String)>,
+}
+
+impl MappingEditor {
+    pub fn new(map: &HashMap<String, String>) -> Self {
+        let mut rows: Vec<(String, String)> = map
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        rows.sort();
+        Self { rows }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// 每一行的错误信息
+    pub fn errors(&self) -> Vec<Option<&'static str>> {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(index, (key, value))| {
+                let key = key.trim();
+                let value = value.trim();
+                if key.is_empty() {
+                    Some("key is empty")
+                } else if value.is_empty() {
+                    Some("value is empty")
+                } else if key.contains(char::is_whitespace) || value.contains(char::is_whitespace) {
+                    Some("contains whitespace")
+                } else if self.rows[..index].iter().any(|(x, _)| x.trim() == key) {
+                    Some("duplicate key")
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.errors().iter().all(Option::is_none)
+    }
+
+    /// 转换为请求中的映射表，调用前应先通过 [`Self::is_valid`] 校验
+    pub fn to_map(&self) -> HashMap<String, String> {
+        self.rows
+            .iter()
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect()
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let errors = self.errors();
+        let error_color = ui.visuals().error_fg_color;
+        let mut remove_index = None;
+
+        egui::Grid::new(ui.id().with("custom_mapping"))
+            .num_columns(4)
+            .show(ui, |ui| {
+                for (index, (key, value)) in self.rows.iter_mut().enumerate() {
+                    ui.add(egui::TextEdit::singleline(key).hint_text("key"));
+                    ui.add(egui::TextEdit::singleline(value).hint_text("value"));
+                    if ui.button("✖").clicked() {
+                        remove_index = Some(index);
+                    }
+                    if let Some(err) = errors[index] {
+                        ui.colored_label(error_color, err);
+                    } else {
+                        ui.label("");
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some(index) = remove_index {
+            self.rows.remove(index);
+        }
+
+        if ui.button("➕").on_hover_text("add mapping").clicked() {
+            self.rows.push(Default::default());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(rows: &[(&str, &str)]) -> MappingEditor {
+        MappingEditor {
+            rows: rows
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn round_trip_sorted() {
+        let map = HashMap::from([
+            ("b".to_string(), "2".to_string()),
+            ("a".to_string(), "1".to_string()),
+        ]);
+        let editor = MappingEditor::new(&map);
+        assert_eq!(editor.rows[0].0, "a");
+        assert!(editor.is_valid());
+        assert_eq!(editor.to_map(), map);
+    }
+
+    #[test]
+    fn detects_invalid_rows() {
+        let editor = editor(&[
+            ("a", "1"),
+            ("", "1"),
+            ("b", " "),
+            ("c d", "1"),
+            (" a ", "2"),
+        ]);
+        assert_eq!(
+            editor.errors(),
+            vec![
+                None,
+                Some("key is empty"),
+                Some("value is empty"),
+                Some("contains whitespace"),
+                Some("duplicate key"),
+            ]
+        );
+        assert!(!editor.is_valid());
+    }
+
+    #[test]
+    fn trims_values() {
+        let editor = editor(&[(" a ", " 1 ")]);
+        assert!(editor.is_valid());
+        assert_eq!(editor.to_map()["a"], "1");
+    }
+}
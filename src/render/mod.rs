@@ -1,10 +1,23 @@
+use crate::api::ApiError;
+use crate::proto::SortOrder;
 use crate::TemplateApp;
 use egui::Ui;
 
+mod bulk;
+pub mod config;
+mod crud;
+mod file_io;
 pub mod login;
+mod mapping;
+mod operation;
 mod password;
 pub mod player;
+mod player_import;
+mod player_picker;
+pub mod servers;
+pub mod stats;
 pub mod tunnel;
+mod tunnel_form;
 
 pub trait RenderUI {
     fn render(&mut self, ctx: &egui::Context, app: &mut TemplateApp);
@@ -12,11 +25,92 @@ pub trait RenderUI {
     fn reset(&mut self) {}
 }
 
-pub fn render_number_u32(ui: &mut Ui, number: &mut u32) {
-    let mut str = format!("{}", number);
-    if ui.text_edit_singleline(&mut str).changed() {
-        if let Ok(value) = str.parse::<u32>() {
-            *number = value;
+/// 显示请求错误，服务器错误只显示服务器给出的提示
+pub fn render_api_error(ui: &mut Ui, err: &ApiError) {
+    let color = ui.visuals().error_fg_color;
+    match err {
+        ApiError::Server { code, msg } => {
+            ui.colored_label(color, msg)
+                .on_hover_text(format!("code:{}", code));
         }
+        _ => {
+            ui.colored_label(color, err.to_string());
+        }
+    }
+}
+
+/// 标记有未保存修改的单元格
+pub fn highlight_modified(ui: &Ui, rect: egui::Rect) {
+    let color = ui.visuals().warn_fg_color;
+    ui.painter()
+        .rect_stroke(rect.expand(1.0), 2.0, egui::Stroke::new(1.0, color));
+}
+
+/// 刷新/翻页前提示未保存的修改会丢失，返回 `Some(true)` 表示放弃修改
+pub fn render_discard_warning(ui: &mut Ui, modified_count: usize) -> Option<bool> {
+    let mut result = None;
+    ui.horizontal(|ui| {
+        ui.colored_label(
+            ui.visuals().warn_fg_color,
+            format!("{} unsaved change(s) will be lost", modified_count),
+        );
+        if ui.button("discard and reload").clicked() {
+            result = Some(true);
+        }
+        if ui.button("cancel").clicked() {
+            result = Some(false);
+        }
+    });
+    result
+}
+
+/// 可点击排序的表头，依次切换 升序 -> 降序 -> 不排序
+pub fn sort_header<K: Copy + PartialEq>(
+    ui: &mut Ui,
+    text: &str,
+    key: K,
+    sort: &mut Option<(K, SortOrder)>,
+) -> bool {
+    let text = match sort {
+        Some((x, SortOrder::Asc)) if *x == key => format!("{} ⏶", text),
+        Some((x, SortOrder::Desc)) if *x == key => format!("{} ⏷", text),
+        _ => text.to_string(),
+    };
+    let clicked = ui
+        .add(egui::Button::new(egui::RichText::new(text).strong()).frame(false))
+        .on_hover_text("sort")
+        .clicked();
+    if clicked {
+        *sort = match sort {
+            Some((x, SortOrder::Asc)) if *x == key => Some((key, SortOrder::Desc)),
+            Some((x, SortOrder::Desc)) if *x == key => None,
+            _ => Some((key, SortOrder::Asc)),
+        };
     }
+    clicked
+}
+
+/// 过滤条件下拉框，`None` 显示为 all
+pub fn filter_combo<T: Copy + PartialEq>(
+    ui: &mut Ui,
+    id_source: impl std::hash::Hash,
+    value: &mut Option<T>,
+    options: &[(T, &str)],
+) -> bool {
+    let text = |value: Option<T>| {
+        options
+            .iter()
+            .find(|(x, _)| Some(*x) == value)
+            .map_or("all", |(_, text)| *text)
+    };
+    let mut changed = false;
+    egui::ComboBox::from_id_source(id_source)
+        .selected_text(text(*value))
+        .show_ui(ui, |ui| {
+            changed |= ui.selectable_value(value, None, "all").changed();
+            for (option, text) in options {
+                changed |= ui.selectable_value(value, Some(*option), *text).changed();
+            }
+        });
+    changed
 }
@@ -0,0 +1,68 @@
+//! 列表中每一行各自的更新/删除请求，不同行的请求可以同时进行
+
+use crate::api::{ApiError, ApiPromise};
+use crate::proto::GeneralResponse;
+use std::collections::HashMap;
+
+type OperationResult = Result<GeneralResponse, ApiError>;
+
+/// 列表子项上正在进行的操作
+pub enum Operation<T> {
+    // 提交的数据，成功后作为新的原始数据
+    Update(T),
+    Remove,
+}
+
+/// 按行 id 记录正在进行的操作，每一行同时只有一个操作
+pub struct ItemOperations<T> {
+    map: HashMap<u32, (Operation<T>, ApiPromise<GeneralResponse>)>,
+}
+
+impl<T> Default for ItemOperations<T> {
+    fn default() -> Self {
+        Self {
+            map: HashMap::new(),
+        }
+    }
+}
+
+impl<T> ItemOperations<T> {
+    /// 开始新的操作，替换该行之前的操作(例如失败后重试)
+    pub fn start(
+        &mut self,
+        id: u32,
+        operation: Operation<T>,
+        promise: ApiPromise<GeneralResponse>,
+    ) {
+        self.map.insert(id, (operation, promise));
+    }
+
+    /// 该行的操作和请求结果，`None` 表示请求还未完成
+    pub fn get(&self, id: u32) -> Option<(&Operation<T>, Option<&OperationResult>)> {
+        self.map
+            .get(&id)
+            .map(|(operation, promise)| (operation, promise.ready()))
+    }
+
+    /// 该行是否有未完成的请求
+    pub fn is_pending(&self, id: u32) -> bool {
+        matches!(self.get(id), Some((_, None)))
+    }
+
+    /// 取出已经成功的操作，失败的操作保留用于显示错误
+    pub fn take_finished(&mut self) -> Vec<(u32, Operation<T>)> {
+        let ids: Vec<u32> = self
+            .map
+            .iter()
+            .filter(|(_, (_, promise))| matches!(promise.ready(), Some(Ok(_))))
+            .map(|(id, _)| *id)
+            .collect();
+        ids.into_iter()
+            .filter_map(|id| self.map.remove(&id).map(|(operation, _)| (id, operation)))
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+}
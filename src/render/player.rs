@@ -1,543 +1,287 @@
-use crate::proto::PlayerListResponse;
-use crate::render::RenderUI;
-use crate::resource::ResponseType;
-use crate::{proto, TemplateApp};
-use eframe::emath::vec2;
+use crate::api::{ApiClient, ApiPromise};
+use crate::proto::{
+    self, GeneralResponse, PlayerListItem, PlayerListRequest, PlayerListResponse, PlayerSortKey,
+    PushEvent, SortOrder,
+};
+use crate::render::crud::{
+    BulkItems, CrudColumn, CrudEntity, CrudPage, CrudRequest, CrudRow, EditMode, ListPage,
+    PushChange,
+};
+use crate::render::player_import::ImportWindow;
+use crate::render::{filter_combo, highlight_modified};
 use eframe::epaint::Color32;
-use egui::{Rect, Ui};
-use egui_extras::{Column, TableBuilder};
-use std::collections::HashMap;
+use egui::Ui;
 
-static PAGE_SIZE: usize = 20;
-static INVALID_ITEM_ID: u32 = u32::MAX;
+pub type Logic = CrudPage<Players>;
 
-static GRAY: Color32 = Color32::from_rgba_premultiplied(80, 80, 80, 80);
+/// 新建和编辑玩家的表单
+#[derive(Clone, PartialEq, Default)]
+pub struct PlayerDraft {
+    username: String,
+    password: String,
+}
 
-enum OperationResult {
-    None,
-    Wait,
-    Error(String),
+/// 搜索/过滤条件
+#[derive(Default, Clone)]
+pub struct Query {
+    search: String,
+    online: Option<bool>,
 }
 
-struct CreateData {
-    username: String,
-    password: String,
+/// 玩家列表页面
+#[derive(Default)]
+pub struct Players {
+    // 批量重置的新密码
+    reset_password: String,
+    import: Option<ImportWindow>,
 }
 
-pub struct Logic {
-    key_get_list: String,
-    key_remove_item: String,
-    key_add_item: String,
-    key_update_item: String,
+impl CrudEntity for Players {
+    type Item = PlayerListItem;
+    type Draft = PlayerDraft;
+    type Query = Query;
+    type SortKey = PlayerSortKey;
+    type ListRequest = PlayerListRequest;
+    type ListResponse = PlayerListResponse;
 
-    item_operation_map: HashMap<String, (u32, OperationResult)>,
+    const NAME: &'static str = "player";
+    const EDIT_MODE: EditMode = EditMode::Inline;
+    const AUTO_REFRESH: bool = true;
 
-    // 是否正在等待列表数据刷新
-    wait_data_list: bool,
-    data: Option<PlayerListResponse>,
+    fn id(item: &PlayerListItem) -> u32 {
+        item.id
+    }
 
-    show_create_window: bool,
-    create_data: CreateData,
-}
+    fn label(item: &PlayerListItem) -> String {
+        format!("#{} {}", item.id, item.username)
+    }
 
-impl Logic {
-    pub fn new() -> Self {
-        Self {
-            key_get_list: "player_list".into(),
-            key_remove_item: "remove_player".into(),
-            key_add_item: "add_player".into(),
-            key_update_item: "update_player".into(),
-            wait_data_list: false,
-            data: None,
-            item_operation_map: HashMap::new(),
-            show_create_window: false,
-            create_data: CreateData {
-                username: "".into(),
-                password: "".into(),
-            },
+    fn to_draft(item: &PlayerListItem) -> PlayerDraft {
+        PlayerDraft {
+            username: item.username.clone(),
+            password: item.password.clone(),
         }
     }
-}
 
-impl RenderUI for Logic {
-    fn render(&mut self, ctx: &egui::Context, app: &mut TemplateApp) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let need_update_page = if self.busy(app) {
-                self.render_loading(ui);
-                self.render_create_window(ctx, app, false)
-            } else {
-                self.render_create_window(ctx, app, true)
-            };
+    fn from_draft(draft: &PlayerDraft, origin: &PlayerListItem) -> PlayerListItem {
+        PlayerListItem {
+            username: draft.username.clone(),
+            password: draft.password.clone(),
+            ..origin.clone()
+        }
+    }
 
-            self.render_content(ui, ctx, app, need_update_page);
-        });
+    fn list_request(
+        query: &Query,
+        sort: Option<(PlayerSortKey, SortOrder)>,
+        page_number: usize,
+        page_size: usize,
+    ) -> PlayerListRequest {
+        let search = query.search.trim();
+        PlayerListRequest {
+            page_number,
+            page_size,
+            search: (!search.is_empty()).then(|| search.to_string()),
+            online: query.online,
+            sort_by: sort.map(|(key, _)| key),
+            sort_order: sort.map(|(_, order)| order),
+        }
     }
 
-    fn reset(&mut self) {
-        self.data = None;
-        self.wait_data_list = false;
-        self.item_operation_map.clear();
-        self.show_create_window = false;
-        self.create_data = CreateData {
-            username: "".into(),
-            password: "".into(),
-        };
+    fn has_query(req: &PlayerListRequest) -> bool {
+        req.has_query()
     }
-}
 
-impl Logic {
-    fn render_content(
-        &mut self,
-        ui: &mut Ui,
-        ctx: &egui::Context,
-        app: &mut TemplateApp,
-        need_update_page: bool,
-    ) {
-        let mut need_request = need_update_page;
-        let mut cur_page_number: usize = 0;
-        if let Some(promise) = app.promise_map.get_mut(&self.key_get_list) {
-            if let Some(result) = promise.ready_mut() {
-                match result {
-                    Ok(ref resource) => match &resource.response_data {
-                        ResponseType::PlayerListResponse(ref player_list) => {
-                            if self.wait_data_list {
-                                self.wait_data_list = false;
-                                self.data = Some(player_list.clone());
-                                self.item_operation_map.clear();
-                            }
-                        }
-                        ResponseType::Error(err) => {
-                            if ui.button("retry").clicked() {
-                                need_request = true;
-                            }
-                            ui.colored_label(ui.visuals().error_fg_color, err);
-                        }
-                        _ => {
-                            if ui.button("retry").clicked() {
-                                need_request = true;
-                            }
-                            ui.colored_label(ui.visuals().error_fg_color, "Unknown error");
-                        }
-                    },
-                    Err(error) => {
-                        if ui.button("retry").clicked() {
-                            need_request = true;
-                        }
+    fn apply_query(req: &PlayerListRequest, items: &mut Vec<PlayerListItem>) {
+        req.apply_query(items);
+    }
 
-                        ui.colored_label(
-                            ui.visuals().error_fg_color,
-                            if error.is_empty() {
-                                "Request failed"
-                            } else {
-                                error
-                            },
-                        );
-                    }
-                }
-            }
-        } else {
-            need_request = true;
+    fn to_page(response: &PlayerListResponse) -> ListPage<PlayerListItem> {
+        ListPage {
+            items: response.players.clone(),
+            cur_page_number: response.cur_page_number,
+            total_count: response.total_count,
+            filtered: response.filtered,
         }
+    }
 
-        // 玩家列表渲染
-        if let Some(ref mut player_list) = self.data {
-            ui.horizontal(|ui| {
-                // 刷新按钮
-                if ui.button("🔃").clicked() {
-                    need_request = true;
-                }
+    fn list(api: &ApiClient, req: &PlayerListRequest) -> ApiPromise<PlayerListResponse> {
+        api.player_list(req)
+    }
 
-                // 计算页数
-                cur_page_number = player_list.cur_page_number as usize;
-                let mut page_count = if player_list.total_count % PAGE_SIZE == 0 {
-                    player_list.total_count / PAGE_SIZE
-                } else {
-                    player_list.total_count / PAGE_SIZE + 1
-                };
+    fn add(api: &ApiClient, draft: &PlayerDraft) -> ApiPromise<GeneralResponse> {
+        api.add_player(&proto::PlayerAddReq {
+            username: draft.username.clone(),
+            password: draft.password.clone(),
+        })
+    }
 
-                if page_count <= 0 {
-                    page_count = 1;
-                }
+    fn update(api: &ApiClient, item: &PlayerListItem) -> ApiPromise<GeneralResponse> {
+        api.update_player(&proto::PlayerUpdateReq::from(item.clone()))
+    }
 
-                if cur_page_number > 0 && page_count <= cur_page_number {
-                    cur_page_number = page_count - 1;
-                    need_request = true;
-                }
+    fn remove(api: &ApiClient, id: u32) -> ApiPromise<GeneralResponse> {
+        api.remove_player(&proto::PlayerRemoveReq { id })
+    }
 
-                // 页数选择
-                if page_count > 1
-                    && egui::ComboBox::from_label("Page")
-                        .selected_text(format!("{}", cur_page_number + 1))
-                        .show_index(ui, &mut cur_page_number, page_count, |i| {
-                            format!("{}", i + 1)
-                        })
-                        .changed()
-                {
-                    need_request = true;
-                }
-            });
+    fn columns(&self) -> Vec<CrudColumn<PlayerSortKey>> {
+        vec![
+            CrudColumn::new("index"),
+            CrudColumn::new("id").sortable(PlayerSortKey::Id),
+            CrudColumn::new("name").sortable(PlayerSortKey::Username),
+            CrudColumn::new("password"),
+            CrudColumn::new("online").sortable(PlayerSortKey::Online),
+        ]
+    }
 
-            ui.horizontal(|ui| {
-                if ui.button("new player").clicked() {
-                    self.show_create_window = true;
+    fn cell(&self, ui: &mut Ui, column: usize, row: &mut CrudRow<'_, Self>) {
+        let item = row.item;
+        match column {
+            0 => {
+                ui.label(format!("{}", row.index + 1));
+            }
+            1 => {
+                if ui.button("📋").on_hover_text("copy").clicked() {
+                    ui.output_mut(|o| o.copied_text = format!("{}", item.id));
                 }
-                ui.label(format!("total : {}", player_list.total_count));
-            });
-
-            self.render_table(ui, ctx, app);
-        }
-
-        // 请求列表数据
-        if need_request {
-            let req = proto::PlayerListRequest {
-                page_number: cur_page_number,
-                page_size: PAGE_SIZE,
-            };
-            app.http_request(
-                ctx,
-                &self.key_get_list,
-                None,
-                serde_json::to_string(&req).unwrap().into(),
-            );
-            self.wait_data_list = true;
+                ui.label(format!("{}", item.id));
+            }
+            2 => {
+                if ui.button("📋").on_hover_text("copy").clicked() {
+                    ui.output_mut(|o| o.copied_text = item.username.clone());
+                }
+                ui.label(item.username.as_str());
+            }
+            3 => {
+                // ui.add(password(&mut item.password));
+                let response = ui.text_edit_singleline(&mut row.draft.password);
+                if row.draft.password != row.origin.password {
+                    highlight_modified(ui, response.rect);
+                }
+            }
+            _ => {
+                if item.online {
+                    ui.colored_label(Color32::GREEN, "online");
+                } else {
+                    ui.colored_label(ui.visuals().error_fg_color, "offline");
+                }
+            }
         }
     }
 
-    fn render_loading(&self, ui: &mut Ui) {
-        ui.painter().rect_filled(ui.max_rect(), 0.0, GRAY);
-
-        egui::Spinner::new().paint_at(
-            ui,
-            Rect::from_center_size(ui.max_rect().center(), vec2(30.0, 30.0)),
-        );
+    fn form(&self, ui: &mut Ui, draft: &mut PlayerDraft) {
+        ui.horizontal(|ui| {
+            ui.label("username:");
+            ui.text_edit_singleline(&mut draft.username);
+        });
 
-        // 屏蔽下层输入
-        ui.interact(
-            ui.min_rect(),
-            egui::Id::new("Some Id"),
-            egui::Sense::click(),
-        );
+        ui.horizontal(|ui| {
+            ui.label("password:");
+            ui.text_edit_singleline(&mut draft.password);
+        });
     }
 
-    fn render_table(&mut self, ui: &mut Ui, ctx: &egui::Context, app: &mut TemplateApp) {
-        let mut need_update_item_info = None;
-        let mut need_remove_item_info = None;
-
-        let update_item_operation = self.item_operation_map.get(&self.key_update_item);
-        let remove_item_operation = self.item_operation_map.get(&self.key_remove_item);
-
-        let table = TableBuilder::new(ui)
-            .striped(true)
-            .resizable(true)
-            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .min_scrolled_height(0.0);
-        table
-            .header(20.0, |mut header| {
-                header.col(|ui| {
-                    ui.strong("index");
-                });
-                header.col(|ui| {
-                    ui.strong("id");
-                });
-                header.col(|ui| {
-                    ui.strong("name");
-                });
-                header.col(|ui| {
-                    ui.strong("password");
-                });
-                header.col(|ui| {
-                    ui.strong("online");
-                });
-                header.col(|ui| {
-                    ui.strong("update");
-                });
-                header.col(|ui| {
-                    ui.strong("remove");
-                });
-            })
-            .body(|mut body| {
-                if let Some(ref mut item_list) = self.data {
-                    for (index, item) in item_list.players.iter_mut().enumerate() {
-                        body.row(20.0, |mut row| {
-                            row.col(|ui| {
-                                ui.label(format!("{}", index + 1));
-                            });
-                            row.col(|ui| {
-                                if ui.button("📋").on_hover_text("copy").clicked() {
-                                    ui.output_mut(|o| o.copied_text = format!("{}", item.id));
-                                }
-                                ui.label(format!("{}", item.id));
-                            });
-                            row.col(|ui| {
-                                if ui.button("📋").on_hover_text("copy").clicked() {
-                                    ui.output_mut(|o| o.copied_text = item.username.clone());
-                                }
-                                ui.label(item.username.as_str());
-                            });
-                            row.col(|ui| {
-                                // ui.add(password(&mut item.password));
-                                ui.text_edit_singleline(&mut item.password);
-                            });
-                            row.col(|ui| {
-                                if item.online {
-                                    ui.colored_label(Color32::GREEN, "online");
-                                } else {
-                                    ui.colored_label(ui.visuals().error_fg_color, "offline");
-                                }
-                            });
-                            row.col(|ui| {
-                                if update_item_operation.is_none() {
-                                    if ui.button("🔄update").clicked() {
-                                        need_update_item_info = Some(item.clone());
-                                    }
-                                    return;
-                                }
-
-                                let (item_id, operation_result) = &update_item_operation.unwrap();
-
-                                if item_id == &INVALID_ITEM_ID {
-                                    if ui.button("🔄update").clicked() {
-                                        need_update_item_info = Some(item.clone());
-                                    }
-                                } else {
-                                    if item_id != &item.id {
-                                        return;
-                                    } else {
-                                        match operation_result {
-                                            OperationResult::Error(message) => {
-                                                if ui.button("🔄retry").clicked() {
-                                                    need_update_item_info = Some(item.clone());
-                                                }
-                                                ui.colored_label(
-                                                    ui.visuals().error_fg_color,
-                                                    message,
-                                                );
-                                            }
-                                            _ => {
-                                                ui.spinner();
-                                            }
-                                        }
-                                    }
-                                }
-                            });
-                            row.col(|ui| {
-                                // 没有正在删除的元素
-                                if remove_item_operation.is_none()
-                                    || remove_item_operation.unwrap().0 == INVALID_ITEM_ID
-                                {
-                                    if let Some(operation_result) = update_item_operation {
-                                        // 正在更新的元素不是当前元素，显示删除按钮
-                                        if operation_result.0 != item.id && ui.button("✖").clicked()
-                                        {
-                                            need_remove_item_info = Some(item.id);
-                                        }
-                                    } else {
-                                        if ui.button("✖").clicked() {
-                                            need_remove_item_info = Some(item.id);
-                                        }
-                                    }
-                                } else {
-                                    // 正在删除其他元素
-                                    if remove_item_operation.unwrap().0 != item.id {
-                                        return;
-                                    }
-
-                                    match &remove_item_operation.unwrap().1 {
-                                        OperationResult::Error(message) => {
-                                            if ui.button("✖").clicked() {
-                                                need_remove_item_info = Some(item.id);
-                                            }
-                                            ui.colored_label(ui.visuals().error_fg_color, message);
-                                        }
-                                        _ => {
-                                            ui.spinner();
-                                        }
-                                    }
-                                }
-                            });
-                        });
-                    }
-                }
-            });
-
-        // 更新操作
-        if let Some(info) = need_update_item_info {
-            self.item_operation_map.insert(
-                self.key_update_item.clone(),
-                (info.id, OperationResult::Wait),
+    fn render_filters(&mut self, ui: &mut Ui, query: &mut Query) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add(
+                    egui::TextEdit::singleline(&mut query.search)
+                        .hint_text("🔍 username")
+                        .desired_width(150.0),
+                )
+                .changed();
+
+            changed |= filter_combo(
+                ui,
+                "player_online_filter",
+                &mut query.online,
+                &[(true, "online"), (false, "offline")],
             );
-            let req = proto::PlayerUpdateReq {
-                id: info.id,
-                username: info.username,
-                password: info.password,
-            };
-            app.http_request(
-                ctx,
-                &self.key_update_item,
-                None,
-                serde_json::to_string(&req).unwrap().into(),
-            )
-        }
+        });
+        changed
+    }
 
-        // 删除操作
-        if let Some(info) = need_remove_item_info {
-            self.item_operation_map
-                .insert(self.key_remove_item.clone(), (info, OperationResult::Wait));
-            let req = proto::PlayerRemoveReq { id: info };
-            app.http_request(
-                ctx,
-                &self.key_remove_item,
-                None,
-                serde_json::to_string(&req).unwrap().into(),
-            )
+    fn render_toolbar(&mut self, ui: &mut Ui, api: &ApiClient) {
+        if ui
+            .add_enabled(self.import.is_none(), egui::Button::new("📥 import"))
+            .clicked()
+        {
+            self.import = Some(ImportWindow::new(api));
         }
     }
 
-    fn render_create_window(
+    fn render_bulk_actions(
         &mut self,
-        ctx: &egui::Context,
-        app: &mut TemplateApp,
-        enabled: bool,
-    ) -> bool {
-        let mut request_finish = false;
-        egui::Window::new("New Player")
-            .vscroll(true)
-            .hscroll(true)
-            .resizable(true)
-            .collapsible(true)
-            .open(&mut self.show_create_window)
-            .enabled(enabled)
-            .show(ctx, |ui| {
-                ui.horizontal(|ui| {
-                    ui.label("username:");
-                    ui.text_edit_singleline(&mut self.create_data.username);
-                });
-
-                ui.horizontal(|ui| {
-                    ui.label("password:");
-                    ui.text_edit_singleline(&mut self.create_data.password);
-                });
-                ui.separator();
-                ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
-                    if ui.button("   ok   ").clicked() && app.can_request(&self.key_add_item) {
-                        let req = proto::PlayerAddReq {
-                            username: self.create_data.username.clone(),
-                            password: self.create_data.password.clone(),
-                        };
-                        app.http_request(
-                            ctx,
-                            &self.key_add_item,
-                            None,
-                            serde_json::to_string(&req).unwrap().into(),
-                        );
-                    }
+        ui: &mut Ui,
+        selected: &[&PlayerListItem],
+    ) -> Option<BulkItems<Self>> {
+        let password = &mut self.reset_password;
+        ui.add(
+            egui::TextEdit::singleline(password)
+                .hint_text("new password")
+                .desired_width(120.0),
+        );
+        if !ui
+            .add_enabled(!password.is_empty(), egui::Button::new("🔑 reset password"))
+            .clicked()
+        {
+            return None;
+        }
 
-                    if let Some(promise) = app.promise_map.get(&self.key_add_item) {
-                        if let Some(result) = promise.ready() {
-                            match result {
-                                Ok(resource) => match &resource.response_data {
-                                    ResponseType::GeneralResponse(_) => {
-                                        request_finish = true;
-                                        app.promise_map.remove(&self.key_add_item);
-                                    }
-                                    ResponseType::Error(err) => {
-                                        ui.colored_label(ui.visuals().error_fg_color, err);
-                                    }
-                                    _ => {
-                                        ui.colored_label(
-                                            ui.visuals().error_fg_color,
-                                            "Unknown error",
-                                        );
-                                    }
-                                },
-                                Err(error) => {
-                                    ui.colored_label(
-                                        ui.visuals().error_fg_color,
-                                        if error.is_empty() {
-                                            "Login failed"
-                                        } else {
-                                            error
-                                        },
-                                    );
-                                }
-                            }
-                        } else {
-                            ui.spinner();
-                        }
-                    }
-                });
-            });
+        // 重置密码基于服务器上的数据，不提交未保存的修改
+        let password = std::mem::take(password);
+        let items = selected
+            .iter()
+            .map(|x| {
+                let item = PlayerListItem {
+                    password: password.clone(),
+                    ..(*x).clone()
+                };
+                (Self::label(x), CrudRequest::Update(Box::new(item)))
+            })
+            .collect();
+        Some(("Reset passwords".to_string(), items))
+    }
 
-        if request_finish {
-            self.show_create_window = false;
-            self.create_data.username.clear();
-            self.create_data.password.clear();
-            if let Some(data) = &mut self.data {
-                data.total_count = data.total_count + 1;
-                if data.players.len() < PAGE_SIZE {
-                    return true;
-                }
+    fn push_change(
+        event: &PushEvent,
+        items: &[PlayerListItem],
+    ) -> Option<PushChange<PlayerListItem>> {
+        match event {
+            PushEvent::PlayerOnline { id, online } => {
+                let player = items.iter().find(|x| x.id == *id)?;
+                Some(PushChange::Updated(PlayerListItem {
+                    online: *online,
+                    ..player.clone()
+                }))
             }
+            _ => None,
         }
-        false
     }
 
-    fn busy(&mut self, app: &mut TemplateApp) -> bool {
-        let mut removed_id = None;
-        for (key, (id, operation_result)) in &mut self.item_operation_map {
-            if *id != INVALID_ITEM_ID {
-                let promise_option = app.promise_map.get(key);
-                if let Some(promise) = promise_option {
-                    if let Some(result) = promise.ready() {
-                        match result {
-                            Ok(resource) => match &resource.response_data {
-                                ResponseType::GeneralResponse(_) => {
-                                    if key == &self.key_remove_item {
-                                        removed_id = Some(*id);
-                                    }
-                                    *operation_result = OperationResult::None;
-                                    *id = INVALID_ITEM_ID;
-                                }
-                                ResponseType::Error(err) => {
-                                    *operation_result = OperationResult::Error(err.clone());
-                                }
-                                _ => {
-                                    *operation_result =
-                                        OperationResult::Error("Unknown error".into());
-                                }
-                            },
-                            Err(error) => {
-                                *operation_result = OperationResult::Error(if error.is_empty() {
-                                    "Request failed".into()
-                                } else {
-                                    error.to_string()
-                                });
-                            }
-                        }
-                    } else {
-                        *operation_result = OperationResult::Wait;
-                    }
-                } else {
-                    *operation_result = OperationResult::None;
-                    *id = INVALID_ITEM_ID;
-                }
-            }
+    fn render_windows(&mut self, ctx: &egui::Context, idle: bool) -> Option<BulkItems<Self>> {
+        let import = self.import.as_mut()?;
+        let mut open = true;
+        let players = import.show(ctx, &mut open);
+        let mut result = None;
+        if let Some(players) = players.filter(|_| idle) {
+            let items = players
+                .into_iter()
+                .map(|x| {
+                    let draft = PlayerDraft {
+                        username: x.username,
+                        password: x.password,
+                    };
+                    (draft.username.clone(), CrudRequest::Add(draft))
+                })
+                .collect();
+            result = Some(("Import players".to_string(), items));
+            open = false;
         }
-
-        if let Some(removed_id) = removed_id {
-            if let Some(data) = &mut self.data {
-                data.players.retain(|x| x.id != removed_id);
-                data.total_count -= 1;
-            }
+        if !open {
+            self.import = None;
         }
-
-        !app.can_request(&self.key_get_list) || !app.can_request(&self.key_add_item)
+        result
     }
 }
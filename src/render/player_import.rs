@@ -0,0 +1,280 @@
+//! 从 CSV/JSON 导入玩家，导入前预览并检查重复的用户名
+
+use crate::api::{ApiClient, ApiPromise};
+use crate::proto::{PlayerAddReq, PlayerListItem};
+use crate::render::render_api_error;
+use eframe::epaint::Color32;
+use std::collections::HashSet;
+
+/// 导入数据中的一行
+#[derive(Debug, PartialEq)]
+pub struct ImportRow {
+    // 在原文中的行号(JSON 为数组下标)，从1开始
+    pub line: usize,
+    pub username: String,
+    pub password: String,
+}
+
+/// 解析 `[{"username": .., "password": ..}]` 或每行 `username,password` 的 CSV，
+/// CSV 第一行为 `username,password` 时作为表头跳过
+pub fn parse_players(text: &str) -> Result<Vec<ImportRow>, String> {
+    let text = text.trim();
+    if text.starts_with('[') {
+        let players: Vec<PlayerAddReq> =
+            serde_json::from_str(text).map_err(|err| format!("json: {}", err))?;
+        return Ok(players
+            .into_iter()
+            .enumerate()
+            .map(|(index, x)| ImportRow {
+                line: index + 1,
+                username: x.username.trim().to_string(),
+                password: x.password,
+            })
+            .collect());
+    }
+
+    let mut rows = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = split_csv_line(line).map_err(|err| format!("line {}: {}", index + 1, err))?;
+        if rows.is_empty() && fields[0].trim().eq_ignore_ascii_case("username") {
+            continue;
+        }
+        match <[String; 2]>::try_from(fields) {
+            Ok([username, password]) => rows.push(ImportRow {
+                line: index + 1,
+                username: username.trim().to_string(),
+                password,
+            }),
+            Err(_) => {
+                return Err(format!("line {}: expected username,password", index + 1));
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// 按逗号拆分一行，支持双引号包围的字段和 `""` 转义
+fn split_csv_line(line: &str) -> Result<Vec<String>, &'static str> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quote");
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+/// 每一行不能导入的原因
+pub fn check_rows(rows: &[ImportRow], existing: &HashSet<&str>) -> Vec<Option<&'static str>> {
+    rows.iter()
+        .enumerate()
+        .map(|(index, row)| {
+            if row.username.is_empty() {
+                Some("username is empty")
+            } else if existing.contains(row.username.as_str()) {
+                Some("already exists")
+            } else if rows[..index].iter().any(|x| x.username == row.username) {
+                Some("duplicate in file")
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// 导入窗口
+pub struct ImportWindow {
+    text: String,
+    // 当前全部玩家，用于检查重复
+    players_promise: ApiPromise<Vec<PlayerListItem>>,
+}
+
+impl ImportWindow {
+    pub fn new(api: &ApiClient) -> Self {
+        Self {
+            text: String::new(),
+            players_promise: api.all_players(),
+        }
+    }
+
+    /// 返回 `Some` 表示开始导入，`open` 为 false 时窗口关闭
+    pub fn show(&mut self, ctx: &egui::Context, open: &mut bool) -> Option<Vec<PlayerAddReq>> {
+        self.read_dropped_files(ctx);
+
+        let mut result = None;
+        egui::Window::new("Import Players")
+            .vscroll(true)
+            .resizable(true)
+            .collapsible(true)
+            .open(open)
+            .show(ctx, |ui| {
+                ui.label("paste or drop a CSV file (username,password per line) or a JSON array");
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.text)
+                        .code_editor()
+                        .desired_rows(6)
+                        .desired_width(f32::INFINITY)
+                        .hint_text("username,password"),
+                );
+                ui.separator();
+
+                let existing: HashSet<&str> = match self.players_promise.ready() {
+                    Some(Ok(players)) => players.iter().map(|x| x.username.as_str()).collect(),
+                    Some(Err(err)) => {
+                        render_api_error(ui, err);
+                        return;
+                    }
+                    None => {
+                        ui.spinner();
+                        return;
+                    }
+                };
+
+                let rows = match parse_players(&self.text) {
+                    Ok(rows) => rows,
+                    Err(err) => {
+                        ui.colored_label(ui.visuals().error_fg_color, err);
+                        return;
+                    }
+                };
+                let errors = check_rows(&rows, &existing);
+                render_preview(ui, &rows, &errors);
+
+                let players: Vec<PlayerAddReq> = rows
+                    .into_iter()
+                    .zip(errors)
+                    .filter(|(_, err)| err.is_none())
+                    .map(|(row, _)| PlayerAddReq {
+                        username: row.username,
+                        password: row.password,
+                    })
+                    .collect();
+                if ui
+                    .add_enabled(
+                        !players.is_empty(),
+                        egui::Button::new(format!("import {} player(s)", players.len())),
+                    )
+                    .clicked()
+                {
+                    result = Some(players);
+                }
+            });
+        result
+    }
+
+    /// 拖入的文件内容替换输入框
+    fn read_dropped_files(&mut self, ctx: &egui::Context) {
+        let files = ctx.input(|i| i.raw.dropped_files.clone());
+        for file in files {
+            if let Some(bytes) = &file.bytes {
+                self.text = String::from_utf8_lossy(bytes).into_owned();
+            } else {
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(text) = file.path.and_then(|x| std::fs::read_to_string(x).ok()) {
+                    self.text = text;
+                }
+            }
+        }
+    }
+}
+
+fn render_preview(ui: &mut egui::Ui, rows: &[ImportRow], errors: &[Option<&'static str>]) {
+    if rows.is_empty() {
+        return;
+    }
+    let skipped = errors.iter().filter(|x| x.is_some()).count();
+    ui.label(format!("{} row(s), {} skipped", rows.len(), skipped));
+
+    egui::Grid::new("player_import_preview")
+        .num_columns(4)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.strong("line");
+            ui.strong("username");
+            ui.strong("password");
+            ui.strong("status");
+            ui.end_row();
+
+            for (row, err) in rows.iter().zip(errors) {
+                ui.label(format!("{}", row.line));
+                ui.label(&row.username);
+                ui.label(&row.password);
+                match err {
+                    Some(err) => ui.colored_label(ui.visuals().warn_fg_color, *err),
+                    None => ui.colored_label(Color32::GREEN, "new"),
+                };
+                ui.end_row();
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(rows: &[ImportRow]) -> Vec<(&str, &str)> {
+        rows.iter()
+            .map(|x| (x.username.as_str(), x.password.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_csv() {
+        let rows = parse_players("username,password\nalice,1\n\n\"bob, jr\",\"a\"\"b\"\n").unwrap();
+        assert_eq!(names(&rows), [("alice", "1"), ("bob, jr", "a\"b")]);
+        assert_eq!(rows[1].line, 4);
+        assert_eq!(
+            parse_players("alice"),
+            Err("line 1: expected username,password".into())
+        );
+        assert_eq!(
+            parse_players("\"alice,1"),
+            Err("line 1: unterminated quote".into())
+        );
+    }
+
+    #[test]
+    fn parses_json() {
+        let rows = parse_players(r#"[{"username": "alice", "password": "1"}]"#).unwrap();
+        assert_eq!(names(&rows), [("alice", "1")]);
+        assert!(parse_players("[{}]").is_err());
+    }
+
+    #[test]
+    fn detects_duplicates() {
+        let rows = parse_players("alice,1\nbob,2\nbob,3\n,4").unwrap();
+        let existing = HashSet::from(["alice"]);
+        assert_eq!(
+            check_rows(&rows, &existing),
+            [
+                Some("already exists"),
+                None,
+                Some("duplicate in file"),
+                Some("username is empty")
+            ]
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! 按玩家名选择隧道的 sender/receiver
+
+use crate::proto::PlayerListItem;
+use eframe::epaint::Color32;
+use egui::{PopupCloseBehavior, Ui};
+
+/// 玩家 id 对应的显示名称，0 表示服务器
+pub fn player_name(players: &[PlayerListItem], id: u32) -> String {
+    if id == 0 {
+        return "server".to_string();
+    }
+    match players.iter().find(|x| x.id == id) {
+        Some(player) => player.username.clone(),
+        None => format!("#{}", id),
+    }
+}
+
+/// 可搜索的玩家下拉框，显示用户名和在线状态
+pub fn player_picker(
+    ui: &mut Ui,
+    id_source: impl std::hash::Hash,
+    value: &mut u32,
+    players: &[PlayerListItem],
+) -> bool {
+    let id = ui.id().with(id_source);
+    let popup_id = id.with("popup");
+    let search_id = id.with("search");
+    let mut search = ui.data_mut(|d| d.get_temp::<String>(search_id).unwrap_or_default());
+    let mut changed = false;
+
+    // 下拉框点击搜索框也会关闭，所以使用按钮加弹出层
+    let response = ui.button(format!("{} ⏷", player_name(players, *value)));
+    if response.clicked() {
+        ui.memory_mut(|m| m.toggle_popup(popup_id));
+    }
+
+    egui::popup_below_widget(
+        ui,
+        popup_id,
+        &response,
+        PopupCloseBehavior::CloseOnClickOutside,
+        |ui| {
+            ui.set_min_width(160.0);
+            ui.add(egui::TextEdit::singleline(&mut search).hint_text("🔍 search"))
+                .request_focus();
+
+            let keyword = search.trim().to_lowercase();
+            if "server".contains(&keyword) && ui.selectable_label(*value == 0, "server").clicked() {
+                *value = 0;
+                changed = true;
+            }
+
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    let matched = players.iter().filter(|x| {
+                        x.username.to_lowercase().contains(&keyword) || x.id.to_string() == keyword
+                    });
+                    for player in matched {
+                        ui.horizontal(|ui| {
+                            if player.online {
+                                ui.colored_label(Color32::GREEN, "●");
+                            } else {
+                                ui.colored_label(ui.visuals().weak_text_color(), "●");
+                            }
+                            let text = format!("{} (#{})", player.username, player.id);
+                            if ui.selectable_label(*value == player.id, text).clicked() {
+                                *value = player.id;
+                                changed = true;
+                            }
+                        });
+                    }
+                });
+
+            // 列表中没有的玩家可以直接输入 id
+            if let Ok(player_id) = keyword.parse::<u32>() {
+                if player_id != 0
+                    && !players.iter().any(|x| x.id == player_id)
+                    && ui
+                        .selectable_label(false, format!("#{}", player_id))
+                        .clicked()
+                {
+                    *value = player_id;
+                    changed = true;
+                }
+            }
+        },
+    );
+
+    if changed {
+        search.clear();
+        ui.memory_mut(|m| m.close_popup());
+    }
+    ui.data_mut(|d| d.insert_temp(search_id, search));
+    changed
+}
+
+/// 按玩家过滤，未勾选时不过滤
+pub fn player_filter(
+    ui: &mut Ui,
+    label: &str,
+    value: &mut Option<u32>,
+    players: &[PlayerListItem],
+) -> bool {
+    let mut enabled = value.is_some();
+    let mut changed = ui.checkbox(&mut enabled, label).changed();
+    if enabled {
+        let mut id = value.unwrap_or_default();
+        changed |= player_picker(ui, label, &mut id, players);
+        *value = Some(id);
+    } else {
+        *value = None;
+    }
+    changed
+}
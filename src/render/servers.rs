@@ -0,0 +1,479 @@
+//! 所有保存的服务器：同时保持每个服务器的会话，汇总显示玩家和隧道，
+//! 可以把隧道复制到另一个服务器
+
+use crate::api::{is_pending, ApiClient, ApiError, ApiPromise};
+use crate::auth::Session;
+use crate::config::{name_to_player, player_to_name, ConfigError};
+use crate::profile::ServerProfile;
+use crate::proto::{self, GeneralResponse, PlayerListItem, TunnelListItem};
+use crate::render::config::LiveLoader;
+use crate::render::password::password;
+use crate::render::player_picker::player_name;
+use crate::render::{render_api_error, RenderUI};
+use crate::TemplateApp;
+use eframe::epaint::Color32;
+use egui::Ui;
+use egui_extras::{Column, TableBuilder};
+use std::collections::HashMap;
+
+/// 一个服务器的登录状态和数据
+#[derive(Default)]
+struct ServerData {
+    // 数据对应的地址，配置修改后重新加载
+    api_url: String,
+    password: String,
+    login_promise: Option<ApiPromise<Session>>,
+    // 分页加载的全部玩家和隧道
+    loader: Option<LiveLoader>,
+    // 加载的结果，None 表示还在加载
+    result: Option<Result<(), ApiError>>,
+}
+
+impl ServerData {
+    fn players(&self) -> &[PlayerListItem] {
+        self.loader.as_ref().map_or(&[], |x| &x.players)
+    }
+
+    /// 是否已加载全部数据，复制隧道时需要完整的玩家列表对应 sender/receiver
+    fn loaded(&self) -> bool {
+        matches!(self.result, Some(Ok(())))
+    }
+
+    fn tunnels(&self) -> &[TunnelListItem] {
+        self.loader.as_ref().map_or(&[], |x| &x.tunnels)
+    }
+
+    /// 加载失败的错误
+    fn error(&self) -> Option<&ApiError> {
+        self.result.as_ref().and_then(|x| x.as_ref().err())
+    }
+
+    /// 重新加载全部数据
+    fn reload(&mut self) {
+        self.loader = None;
+        self.result = None;
+    }
+}
+
+/// 复制隧道的请求
+struct CopyTask {
+    // 目标服务器的名称
+    target: String,
+    label: String,
+    promise: ApiPromise<GeneralResponse>,
+    // 成功后是否已经重新加载目标服务器的隧道
+    reloaded: bool,
+}
+
+pub struct Logic {
+    // 按配置名称保存
+    servers: HashMap<String, ServerData>,
+    copy: Option<CopyTask>,
+    // 无法复制的原因，例如目标服务器上没有对应的玩家
+    copy_error: Option<String>,
+}
+
+impl Logic {
+    pub fn new() -> Self {
+        Self {
+            servers: HashMap::new(),
+            copy: None,
+            copy_error: None,
+        }
+    }
+}
+
+impl RenderUI for Logic {
+    fn render(&mut self, ctx: &egui::Context, app: &mut TemplateApp) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                self.render_content(ui, ctx, app);
+            });
+        });
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Logic {
+    fn render_content(&mut self, ui: &mut Ui, ctx: &egui::Context, app: &mut TemplateApp) {
+        if app.profiles.is_empty() {
+            ui.label("no saved servers, save a profile on the login window first");
+            return;
+        }
+
+        let mut need_reload = false;
+        ui.horizontal(|ui| {
+            need_reload = ui.button("🔃").on_hover_text("reload all").clicked();
+            self.render_copy_status(ui);
+        });
+
+        // 只保留现有配置的数据
+        self.servers
+            .retain(|name, _| app.profiles.iter().any(|x| x.name == *name));
+        for (index, profile) in app.profiles.iter().enumerate() {
+            let data = self.servers.entry(profile.name.clone()).or_default();
+            if data.api_url != profile.api_url || need_reload {
+                data.api_url = profile.api_url.clone();
+                data.reload();
+            }
+            match app.profile_api(index, ctx) {
+                Some(api) => {
+                    let loader = data.loader.get_or_insert_with(|| LiveLoader::new(&api));
+                    if data.result.is_none() {
+                        data.result = loader.poll(&api);
+                    }
+                }
+                None => data.reload(),
+            }
+        }
+
+        ui.strong("servers");
+        let logins = self.render_servers(ui, app);
+        for (index, session) in logins {
+            app.profile_login_success(index, session);
+        }
+
+        ui.add_space(8.0);
+        ui.strong("players");
+        self.render_players(ui, &app.profiles);
+
+        ui.add_space(8.0);
+        ui.strong("tunnels");
+        if let Some((item, from, to)) = self.render_tunnels(ui, &app.profiles) {
+            self.copy_tunnel(ctx, app, item, from, to);
+        }
+    }
+
+    /// 每个服务器的登录状态，返回刚登录成功的服务器
+    fn render_servers(&mut self, ui: &mut Ui, app: &TemplateApp) -> Vec<(usize, Session)> {
+        let mut logins = Vec::new();
+        egui::Grid::new("servers_grid")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for (index, profile) in app.profiles.iter().enumerate() {
+                    let data = self.servers.get_mut(&profile.name).unwrap();
+                    ui.label(profile.title());
+                    ui.label(&profile.api_url);
+                    ui.horizontal(|ui| {
+                        if app.profile_api(index, ui.ctx()).is_some() {
+                            ui.colored_label(Color32::GREEN, "● logged in");
+                            if let Some(err) = data.error() {
+                                render_api_error(ui, err);
+                            } else if data.result.is_none() {
+                                ui.spinner();
+                            }
+                            return;
+                        }
+
+                        if profile.password.is_none() {
+                            let size = egui::vec2(160.0, ui.spacing().interact_size.y);
+                            ui.add_sized(size, password(&mut data.password));
+                        }
+                        let pending = is_pending(&data.login_promise);
+                        if ui
+                            .add_enabled(!pending, egui::Button::new("Login"))
+                            .clicked()
+                        {
+                            let req = proto::LoginReq {
+                                username: profile.username.clone(),
+                                password: profile
+                                    .password
+                                    .clone()
+                                    .unwrap_or_else(|| data.password.clone()),
+                            };
+                            let api = ApiClient::new(profile.api_url.clone())
+                                .with_context(ui.ctx().clone());
+                            data.login_promise = Some(api.login(profile.auth_mode, &req));
+                        }
+                        match data.login_promise.as_ref().and_then(|x| x.ready()) {
+                            Some(Ok(session)) => logins.push((index, session.clone())),
+                            Some(Err(err)) => render_api_error(ui, err),
+                            None if pending => {
+                                ui.spinner();
+                            }
+                            None => {}
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        for (index, _) in &logins {
+            let data = self.servers.get_mut(&app.profiles[*index].name).unwrap();
+            data.login_promise = None;
+            data.password.clear();
+        }
+        logins
+    }
+
+    fn render_players(&self, ui: &mut Ui, profiles: &[ServerProfile]) {
+        let rows: Vec<(&ServerProfile, &PlayerListItem)> = profiles
+            .iter()
+            .filter_map(|x| Some((x, self.servers.get(&x.name)?)))
+            .flat_map(|(profile, data)| data.players().iter().map(move |x| (profile, x)))
+            .collect();
+        if rows.is_empty() {
+            ui.label("no data");
+            return;
+        }
+
+        ui.push_id("server_players", |ui| {
+            TableBuilder::new(ui)
+                .striped(true)
+                .vscroll(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .column(Column::auto().at_least(80.0))
+                .columns(Column::auto(), 2)
+                .column(Column::remainder())
+                .header(20.0, |mut header| {
+                    for title in ["server", "id", "name", "online"] {
+                        header.col(|ui| {
+                            ui.strong(title);
+                        });
+                    }
+                })
+                .body(|mut body| {
+                    for (profile, item) in rows {
+                        body.row(20.0, |mut row| {
+                            row.col(|ui| {
+                                ui.label(profile.title());
+                            });
+                            row.col(|ui| {
+                                ui.label(format!("{}", item.id));
+                            });
+                            row.col(|ui| {
+                                ui.label(&item.username);
+                            });
+                            row.col(|ui| {
+                                if item.online {
+                                    ui.colored_label(Color32::GREEN, "online");
+                                } else {
+                                    ui.colored_label(ui.visuals().error_fg_color, "offline");
+                                }
+                            });
+                        });
+                    }
+                });
+        });
+    }
+
+    /// 返回要复制的隧道和源/目标服务器
+    fn render_tunnels(
+        &self,
+        ui: &mut Ui,
+        profiles: &[ServerProfile],
+    ) -> Option<(TunnelListItem, usize, usize)> {
+        let rows: Vec<(usize, &ServerData, &TunnelListItem)> = profiles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, x)| Some((index, self.servers.get(&x.name)?)))
+            .flat_map(|(index, data)| data.tunnels().iter().map(move |x| (index, data, x)))
+            .collect();
+        if rows.is_empty() {
+            ui.label("no data");
+            return None;
+        }
+
+        // 可以复制到的服务器，需要玩家列表对应 sender/receiver
+        let targets: Vec<usize> = profiles
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| self.servers.get(&x.name).map_or(false, ServerData::loaded))
+            .map(|(index, _)| index)
+            .collect();
+        let copying = self
+            .copy
+            .as_ref()
+            .map_or(false, |x| x.promise.ready().is_none());
+
+        let mut copy = None;
+        ui.push_id("server_tunnels", |ui| {
+            TableBuilder::new(ui)
+                .striped(true)
+                .vscroll(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .column(Column::auto().at_least(80.0))
+                .column(Column::auto())
+                .column(Column::auto().clip(true))
+                .columns(Column::auto(), 2)
+                .column(Column::remainder().clip(true))
+                .column(Column::auto())
+                .header(20.0, |mut header| {
+                    for title in [
+                        "server",
+                        "id",
+                        "source → endpoint",
+                        "sender → receiver",
+                        "enabled",
+                        "description",
+                        "",
+                    ] {
+                        header.col(|ui| {
+                            ui.strong(title);
+                        });
+                    }
+                })
+                .body(|mut body| {
+                    for (index, data, item) in rows {
+                        body.row(20.0, |mut row| {
+                            row.col(|ui| {
+                                ui.label(profiles[index].title());
+                            });
+                            row.col(|ui| {
+                                ui.label(format!("{}", item.id));
+                            });
+                            row.col(|ui| {
+                                ui.label(format!("{} → {}", item.source, item.endpoint));
+                            });
+                            row.col(|ui| {
+                                let players = data.players();
+                                ui.label(format!(
+                                    "{} → {}",
+                                    player_name(players, item.sender),
+                                    player_name(players, item.receiver)
+                                ));
+                            });
+                            row.col(|ui| {
+                                if item.enabled {
+                                    ui.colored_label(Color32::GREEN, "✔");
+                                } else {
+                                    ui.colored_label(ui.visuals().error_fg_color, "✖");
+                                }
+                            });
+                            row.col(|ui| {
+                                ui.label(&item.description);
+                            });
+                            row.col(|ui| {
+                                ui.add_enabled_ui(!copying && data.loaded(), |ui| {
+                                    ui.menu_button("📋 copy to", |ui| {
+                                        for target in targets.iter().filter(|x| **x != index) {
+                                            if ui.button(profiles[*target].title()).clicked() {
+                                                copy = Some((item.clone(), index, *target));
+                                                ui.close_menu();
+                                            }
+                                        }
+                                    })
+                                    .response
+                                    .on_hover_text("sender/receiver are matched by username");
+                                });
+                            });
+                        });
+                    }
+                });
+        });
+        copy
+    }
+
+    /// 在目标服务器上添加同样的隧道，sender/receiver 按用户名对应
+    fn copy_tunnel(
+        &mut self,
+        ctx: &egui::Context,
+        app: &TemplateApp,
+        item: TunnelListItem,
+        from: usize,
+        to: usize,
+    ) {
+        let Some(api) = app.profile_api(to, ctx) else {
+            return;
+        };
+        let (source, target) = (&app.profiles[from], &app.profiles[to]);
+        let empty = ServerData::default();
+        let from_players = self.servers.get(&source.name).unwrap_or(&empty).players();
+        let to_players = self.servers.get(&target.name).unwrap_or(&empty).players();
+
+        let label = format!("#{} {} → {}", item.id, item.source, target.name);
+        let mut req = proto::TunnelAddReq::from(item);
+        let players = map_player(req.sender, from_players, to_players)
+            .and_then(|sender| Ok((sender, map_player(req.receiver, from_players, to_players)?)));
+        let (sender, receiver) = match players {
+            Ok(players) => players,
+            Err(err) => {
+                self.copy = None;
+                self.copy_error = Some(format!("can't copy {}: {}", label, err));
+                return;
+            }
+        };
+        req.sender = sender;
+        req.receiver = receiver;
+        self.copy_error = None;
+        self.copy = Some(CopyTask {
+            target: target.name.clone(),
+            label,
+            promise: api.add_tunnel(&req),
+            reloaded: false,
+        });
+    }
+
+    /// 复制的结果，成功后重新加载目标服务器的隧道
+    fn render_copy_status(&mut self, ui: &mut Ui) {
+        if let Some(err) = &self.copy_error {
+            ui.colored_label(ui.visuals().error_fg_color, err);
+            if ui.small_button("✖").clicked() {
+                self.copy_error = None;
+            }
+        }
+        let Some(copy) = &mut self.copy else {
+            return;
+        };
+        let Some(result) = copy.promise.ready() else {
+            ui.spinner();
+            ui.label(format!("copying {}", copy.label));
+            return;
+        };
+        match result {
+            Ok(_) => {
+                ui.colored_label(Color32::GREEN, format!("copied {}", copy.label));
+                if !copy.reloaded {
+                    copy.reloaded = true;
+                    if let Some(data) = self.servers.get_mut(&copy.target) {
+                        data.reload();
+                    }
+                }
+            }
+            Err(err) => render_api_error(ui, err),
+        }
+        if ui.small_button("✖").clicked() {
+            self.copy = None;
+        }
+    }
+}
+
+/// 源服务器上的玩家在目标服务器上的 id，按用户名对应
+fn map_player(id: u32, from: &[PlayerListItem], to: &[PlayerListItem]) -> Result<u32, ConfigError> {
+    name_to_player(to, &player_to_name(from, id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(id: u32, username: &str) -> PlayerListItem {
+        PlayerListItem {
+            id,
+            username: username.into(),
+            password: String::new(),
+            online: false,
+        }
+    }
+
+    #[test]
+    fn map_player_by_username() {
+        let from = [player(1, "alice"), player(2, "bob")];
+        let to = [player(7, "bob")];
+        assert_eq!(map_player(0, &from, &to), Ok(0));
+        assert_eq!(map_player(2, &from, &to), Ok(7));
+        // 目标服务器上没有同名玩家
+        assert_eq!(
+            map_player(1, &from, &to),
+            Err(ConfigError::UnknownPlayer("alice".into()))
+        );
+        // 源服务器上找不到的玩家
+        assert_eq!(
+            map_player(9, &from, &to),
+            Err(ConfigError::UnknownPlayer("#9".into()))
+        );
+    }
+}
@@ -0,0 +1,416 @@
+//! 流量统计：按时间范围显示流量和连接数曲线，以及最忙的隧道和玩家
+
+use crate::api::{is_pending, ApiClient, ApiError, ApiPromise};
+use crate::proto::{
+    TrafficPoint, TrafficSeriesRequest, TrafficSeriesResponse, TrafficStats, TrafficStatsRequest,
+    TrafficStatsResponse,
+};
+use crate::render::{render_api_error, RenderUI};
+use crate::TemplateApp;
+use egui::Ui;
+use egui_extras::{Column, TableBuilder};
+use egui_plot::{Legend, Line, Plot, PlotPoints};
+
+/// 表格中默认显示的数量
+static DEFAULT_TOP_N: usize = 10;
+
+/// 统计的时间范围
+#[derive(Clone, Copy, PartialEq)]
+enum TimeWindow {
+    Minutes15,
+    Hour,
+    Hours6,
+    Day,
+    Week,
+}
+
+impl TimeWindow {
+    const ALL: [TimeWindow; 5] = [
+        TimeWindow::Minutes15,
+        TimeWindow::Hour,
+        TimeWindow::Hours6,
+        TimeWindow::Day,
+        TimeWindow::Week,
+    ];
+
+    fn secs(self) -> u64 {
+        match self {
+            TimeWindow::Minutes15 => 15 * 60,
+            TimeWindow::Hour => 3600,
+            TimeWindow::Hours6 => 6 * 3600,
+            TimeWindow::Day => 24 * 3600,
+            TimeWindow::Week => 7 * 24 * 3600,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            TimeWindow::Minutes15 => "15 min",
+            TimeWindow::Hour => "1 h",
+            TimeWindow::Hours6 => "6 h",
+            TimeWindow::Day => "24 h",
+            TimeWindow::Week => "7 d",
+        }
+    }
+
+    /// 曲线横轴的单位(秒)和名称
+    fn unit(self) -> (f64, &'static str) {
+        match self {
+            TimeWindow::Minutes15 | TimeWindow::Hour => (60.0, "min"),
+            TimeWindow::Hours6 | TimeWindow::Day => (3600.0, "h"),
+            TimeWindow::Week => (86400.0, "d"),
+        }
+    }
+}
+
+/// 曲线统计的范围
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    All,
+    Tunnel(u32),
+    Player(u32),
+}
+
+pub struct Logic {
+    window: TimeWindow,
+    target: Target,
+    // 表格中显示的数量
+    top_n: usize,
+    tunnels_promise: Option<ApiPromise<TrafficStatsResponse>>,
+    players_promise: Option<ApiPromise<TrafficStatsResponse>>,
+    series_promise: Option<ApiPromise<TrafficSeriesResponse>>,
+}
+
+impl Logic {
+    pub fn new() -> Self {
+        Self {
+            window: TimeWindow::Hour,
+            target: Target::All,
+            top_n: DEFAULT_TOP_N,
+            tunnels_promise: None,
+            players_promise: None,
+            series_promise: None,
+        }
+    }
+}
+
+impl RenderUI for Logic {
+    fn render(&mut self, ctx: &egui::Context, app: &mut TemplateApp) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let api = app.api(ctx);
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                self.render_content(ui, &api);
+            });
+        });
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Logic {
+    fn render_content(&mut self, ui: &mut Ui, api: &ApiClient) {
+        let mut need_reload = self.series_promise.is_none();
+        let mut need_series = false;
+
+        ui.horizontal(|ui| {
+            if ui.button("🔃").clicked() {
+                need_reload = true;
+            }
+            ui.label("window:");
+            for window in TimeWindow::ALL {
+                if ui
+                    .selectable_value(&mut self.window, window, window.label())
+                    .changed()
+                {
+                    need_reload = true;
+                }
+            }
+            if self.is_loading() {
+                ui.spinner();
+            }
+        });
+
+        if let Some(err) = self.error() {
+            match err {
+                // 旧版本服务器没有统计接口
+                ApiError::HttpStatus { status: 404, .. } => {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "this server does not provide traffic statistics",
+                    );
+                }
+                err => render_api_error(ui, err),
+            }
+        }
+
+        let tunnels = stats_items(&self.tunnels_promise);
+        let players = stats_items(&self.players_promise);
+        ui.horizontal(|ui| {
+            ui.label("show:");
+            egui::ComboBox::from_id_source("stats_target")
+                .selected_text(target_name(self.target, tunnels, players))
+                .show_ui(ui, |ui| {
+                    for target in std::iter::once(Target::All)
+                        .chain(tunnels.iter().map(|x| Target::Tunnel(x.id)))
+                        .chain(players.iter().map(|x| Target::Player(x.id)))
+                    {
+                        let text = target_name(target, tunnels, players);
+                        need_series |= ui
+                            .selectable_value(&mut self.target, target, text)
+                            .changed();
+                    }
+                });
+        });
+
+        if let Some(Ok(series)) = self.series_promise.as_ref().and_then(|x| x.ready()) {
+            render_plots(ui, series, self.window);
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("top:");
+            ui.add(egui::DragValue::new(&mut self.top_n).range(1..=100));
+        });
+
+        let selected = |target| match (self.target, target) {
+            (Target::Tunnel(id), Target::Tunnel(_)) | (Target::Player(id), Target::Player(_)) => {
+                Some(id)
+            }
+            _ => None,
+        };
+        let mut clicked = None;
+        if let Some(Ok(response)) = self.tunnels_promise.as_ref().and_then(|x| x.ready()) {
+            ui.strong("busiest tunnels");
+            let selected = selected(Target::Tunnel(0));
+            if let Some(id) = render_top_table(ui, "tunnel", response, self.top_n, selected) {
+                clicked = Some(Target::Tunnel(id));
+            }
+        }
+        if let Some(Ok(response)) = self.players_promise.as_ref().and_then(|x| x.ready()) {
+            ui.add_space(8.0);
+            ui.strong("busiest players");
+            let selected = selected(Target::Player(0));
+            if let Some(id) = render_top_table(ui, "player", response, self.top_n, selected) {
+                clicked = Some(Target::Player(id));
+            }
+        }
+        if let Some(target) = clicked {
+            self.target = target;
+            need_series = true;
+        }
+
+        if need_reload {
+            let req = TrafficStatsRequest {
+                window_secs: self.window.secs(),
+            };
+            self.tunnels_promise = Some(api.tunnel_stats(&req));
+            self.players_promise = Some(api.player_stats(&req));
+            need_series = true;
+        }
+        if need_series {
+            let (tunnel, player) = match self.target {
+                Target::All => (None, None),
+                Target::Tunnel(id) => (Some(id), None),
+                Target::Player(id) => (None, Some(id)),
+            };
+            self.series_promise = Some(api.traffic_series(&TrafficSeriesRequest {
+                window_secs: self.window.secs(),
+                tunnel,
+                player,
+            }));
+        }
+    }
+
+    fn is_loading(&self) -> bool {
+        is_pending(&self.tunnels_promise)
+            || is_pending(&self.players_promise)
+            || is_pending(&self.series_promise)
+    }
+
+    /// 第一个出错的请求
+    fn error(&self) -> Option<&ApiError> {
+        promise_error(&self.tunnels_promise)
+            .or_else(|| promise_error(&self.players_promise))
+            .or_else(|| promise_error(&self.series_promise))
+    }
+}
+
+fn promise_error<T: Send + 'static>(promise: &Option<ApiPromise<T>>) -> Option<&ApiError> {
+    promise.as_ref()?.ready()?.as_ref().err()
+}
+
+fn stats_items(promise: &Option<ApiPromise<TrafficStatsResponse>>) -> &[TrafficStats] {
+    match promise.as_ref().and_then(|x| x.ready()) {
+        Some(Ok(response)) => &response.items,
+        _ => &[],
+    }
+}
+
+fn target_name(target: Target, tunnels: &[TrafficStats], players: &[TrafficStats]) -> String {
+    let name = |items: &[TrafficStats], id: u32| match items.iter().find(|x| x.id == id) {
+        Some(item) => item.name.clone(),
+        None => format!("#{}", id),
+    };
+    match target {
+        Target::All => "all tunnels".to_string(),
+        Target::Tunnel(id) => format!("tunnel {}", name(tunnels, id)),
+        Target::Player(id) => format!("player {}", name(players, id)),
+    }
+}
+
+/// 流量(每秒)和连接数曲线，横轴为距现在的时间
+fn render_plots(ui: &mut Ui, series: &TrafficSeriesResponse, window: TimeWindow) {
+    let (unit, unit_name) = window.unit();
+    let step = series.step_secs.max(1) as f64;
+    let points = |value: &dyn Fn(&TrafficPoint) -> f64| -> PlotPoints {
+        series
+            .points
+            .iter()
+            .map(|x| [(x.time as f64 - series.now as f64) / unit, value(x)])
+            .collect()
+    };
+    let time_label = format!("time ({})", unit_name);
+
+    ui.strong("traffic");
+    Plot::new("traffic_plot")
+        .height(200.0)
+        .legend(Legend::default())
+        .allow_scroll(false)
+        .include_y(0.0)
+        .x_axis_label(time_label.clone())
+        .y_axis_formatter(|mark, _| format!("{}/s", format_bytes(mark.value.max(0.0) as u64)))
+        .label_formatter(|name, value| {
+            if name.is_empty() {
+                return String::new();
+            }
+            let rate = format_bytes(value.y.max(0.0) as u64);
+            format!("{}: {}/s", name, rate)
+        })
+        .show(ui, |plot_ui| {
+            plot_ui.line(Line::new(points(&|x| x.bytes_in as f64 / step)).name("in"));
+            plot_ui.line(Line::new(points(&|x| x.bytes_out as f64 / step)).name("out"));
+        });
+
+    ui.strong("connections");
+    Plot::new("connections_plot")
+        .height(120.0)
+        .allow_scroll(false)
+        .include_y(0.0)
+        .x_axis_label(time_label)
+        .show(ui, |plot_ui| {
+            plot_ui.line(Line::new(points(&|x| x.connections as f64)).name("connections"));
+        });
+}
+
+/// 按流量从大到小显示前 `top_n` 项，返回点击的行
+fn render_top_table(
+    ui: &mut Ui,
+    kind: &str,
+    response: &TrafficStatsResponse,
+    top_n: usize,
+    selected: Option<u32>,
+) -> Option<u32> {
+    let mut items: Vec<&TrafficStats> = response.items.iter().collect();
+    items.sort_by_key(|x| std::cmp::Reverse(x.bytes_in + x.bytes_out));
+    items.truncate(top_n);
+    if items.is_empty() {
+        ui.label("no data");
+        return None;
+    }
+
+    let mut clicked = None;
+    ui.push_id(kind, |ui| {
+        TableBuilder::new(ui)
+            .striped(true)
+            .vscroll(false)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::auto())
+            .column(Column::auto().at_least(120.0).clip(true))
+            .columns(Column::auto(), 4)
+            .header(20.0, |mut header| {
+                for title in ["#", kind, "in", "out", "connections", "last activity"] {
+                    header.col(|ui| {
+                        ui.strong(title);
+                    });
+                }
+            })
+            .body(|mut body| {
+                for (index, item) in items.iter().enumerate() {
+                    body.row(20.0, |mut row| {
+                        row.col(|ui| {
+                            ui.label(format!("{}", index + 1));
+                        });
+                        row.col(|ui| {
+                            if ui
+                                .selectable_label(selected == Some(item.id), &item.name)
+                                .on_hover_text("show in plot")
+                                .clicked()
+                            {
+                                clicked = Some(item.id);
+                            }
+                        });
+                        row.col(|ui| {
+                            ui.label(format_bytes(item.bytes_in));
+                        });
+                        row.col(|ui| {
+                            ui.label(format_bytes(item.bytes_out));
+                        });
+                        row.col(|ui| {
+                            ui.label(format!("{}", item.connections));
+                        });
+                        row.col(|ui| {
+                            ui.label(format_ago(response.now, item.last_active));
+                        });
+                    });
+                }
+            });
+    });
+    clicked
+}
+
+/// 以 B/KiB/MiB/GiB 显示字节数
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// 最后活动时间距现在多久
+fn format_ago(now: u64, time: Option<u64>) -> String {
+    let Some(time) = time else {
+        return "never".to_string();
+    };
+    let secs = now.saturating_sub(time);
+    match secs {
+        0..=59 => format!("{} s ago", secs),
+        60..=3599 => format!("{} min ago", secs / 60),
+        3600..=86399 => format!("{} h ago", secs / 3600),
+        _ => format!("{} d ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_and_ago() {
+        assert_eq!(format_bytes(1000), "1000 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+        assert_eq!(format_ago(100, None), "never");
+        assert_eq!(format_ago(100, Some(70)), "30 s ago");
+        assert_eq!(format_ago(10000, Some(2800)), "2 h ago");
+    }
+}
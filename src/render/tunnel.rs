@@ -1,755 +1,316 @@
-use crate::proto::TunnelListResponse;
-use crate::render::{render_number_u32, RenderUI};
-use crate::resource::ResponseType;
-use crate::{proto, TemplateApp};
-use eframe::emath::vec2;
+use crate::api::{ApiClient, ApiPromise};
+use crate::proto::{
+    self, GeneralResponse, PlayerListItem, PushEvent, SortOrder, TunnelListItem, TunnelListRequest,
+    TunnelListResponse, TunnelSortKey, TUNNEL_ENCRYPTION_METHOD, TUNNEL_TYPE_OPTION,
+};
+use crate::render::crud::{
+    BulkItems, CrudColumn, CrudEntity, CrudPage, CrudRequest, CrudRow, EditMode, ListPage,
+    PushChange,
+};
+use crate::render::player_picker::{player_filter, player_name};
+use crate::render::tunnel_form::TunnelForm;
+use crate::render::{filter_combo, highlight_modified};
 use eframe::epaint::Color32;
-use egui::{ComboBox, Rect, Ui};
-use egui_extras::{Column, TableBuilder};
-use std::collections::HashMap;
-use std::string::String;
-
-static PAGE_SIZE: usize = 20;
-static INVALID_ITEM_ID: u32 = u32::MAX;
-
-static GRAY: Color32 = Color32::from_rgba_premultiplied(80, 80, 80, 80);
+use egui::Ui;
+use egui_extras::Column;
+
+pub type Logic = CrudPage<Tunnels>;
+
+/// 搜索/过滤条件，刷新列表时保持不变
+#[derive(Default, Clone)]
+pub struct Query {
+    search: String,
+    tunnel_type: Option<u32>,
+    enabled: Option<bool>,
+    sender: Option<u32>,
+    receiver: Option<u32>,
+    encryption_method: Option<&'static str>,
+}
 
-enum OperationResult {
-    None,
-    Wait,
-    Error(String),
+/// 对选中隧道的批量修改，删除由列表页面处理
+#[derive(Clone, Copy)]
+enum BulkAction {
+    Enable,
+    Disable,
+    ToggleCompression,
+    Encryption(&'static str),
 }
 
-struct CreateData {
-    source: String,
-    endpoint: String,
-    sender: u32,
-    receiver: u32,
-    description: String,
-    tunnel_type: u32,
-    password: String,
-    username: String,
-    is_compressed: bool,
-    encryption_method: String,
-    custom_mapping: HashMap<String, String>,
+/// 隧道列表页面
+#[derive(Default)]
+pub struct Tunnels {
+    // 用于显示 sender/receiver 的玩家列表
+    players_promise: Option<ApiPromise<Vec<PlayerListItem>>>,
 }
 
-impl Default for CreateData {
-    fn default() -> Self {
-        Self {
-            source: "".to_string(),
-            endpoint: "".to_string(),
-            sender: 0,
-            receiver: 0,
-            description: "".to_string(),
-            tunnel_type: 0,
-            password: "".to_string(),
-            username: "".to_string(),
-            is_compressed: false,
-            encryption_method: "".to_string(),
-            custom_mapping: HashMap::new(),
+impl Tunnels {
+    /// 已加载的玩家列表，加载失败时 sender/receiver 只显示 id
+    fn players(&self) -> &[PlayerListItem] {
+        match self.players_promise.as_ref().and_then(|x| x.ready()) {
+            Some(Ok(players)) => players,
+            _ => &[],
         }
     }
 }
 
-const TUNNEL_TYPE_OPTION: [&str; 4] = ["TCP", "UDP", "SOCKS5", "UNKNOWN"];
-const TUNNEL_ENCRYPTION_METHOD: [&str; 3] = ["None", "Aes128", "Xor"];
+impl CrudEntity for Tunnels {
+    type Item = TunnelListItem;
+    type Draft = TunnelForm;
+    type Query = Query;
+    type SortKey = TunnelSortKey;
+    type ListRequest = TunnelListRequest;
+    type ListResponse = TunnelListResponse;
 
-pub struct Logic {
-    key_get_list: String,
-    key_remove_item: String,
-    key_add_item: String,
-    key_update_item: String,
+    const NAME: &'static str = "tunnel";
+    const EDIT_MODE: EditMode = EditMode::Window;
+    const AUTO_REFRESH: bool = true;
+
+    fn id(item: &TunnelListItem) -> u32 {
+        item.id
+    }
 
-    item_operation_map: HashMap<String, (u32, OperationResult)>,
+    fn label(item: &TunnelListItem) -> String {
+        format!("#{} {}", item.id, item.source)
+    }
 
-    // 是否正在等待列表数据刷新
-    wait_data_list: bool,
-    data: Option<TunnelListResponse>,
+    fn to_draft(item: &TunnelListItem) -> TunnelForm {
+        TunnelForm::from(item)
+    }
 
-    show_create_window: bool,
-    create_data: CreateData,
-}
+    fn from_draft(draft: &TunnelForm, origin: &TunnelListItem) -> TunnelListItem {
+        draft.to_item(origin.id)
+    }
 
-impl Logic {
-    pub fn new() -> Self {
-        Self {
-            key_get_list: "tunnel_list".into(),
-            key_remove_item: "remove_tunnel".into(),
-            key_add_item: "add_tunnel".into(),
-            key_update_item: "update_tunnel".into(),
-            wait_data_list: false,
-            data: None,
-            item_operation_map: HashMap::new(),
-            show_create_window: false,
-            create_data: CreateData::default(),
+    fn is_valid(draft: &TunnelForm) -> bool {
+        draft.is_valid()
+    }
+
+    fn list_request(
+        query: &Query,
+        sort: Option<(TunnelSortKey, SortOrder)>,
+        page_number: usize,
+        page_size: usize,
+    ) -> TunnelListRequest {
+        let search = query.search.trim();
+        TunnelListRequest {
+            page_number,
+            page_size,
+            search: (!search.is_empty()).then(|| search.to_string()),
+            tunnel_type: query.tunnel_type,
+            enabled: query.enabled,
+            sender: query.sender,
+            receiver: query.receiver,
+            encryption_method: query.encryption_method.map(str::to_string),
+            sort_by: sort.map(|(key, _)| key),
+            sort_order: sort.map(|(_, order)| order),
         }
     }
-}
 
-impl RenderUI for Logic {
-    fn render(&mut self, ctx: &egui::Context, app: &mut TemplateApp) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let need_update_page = if self.busy(app) {
-                self.render_loading(ui);
-                self.render_create_window(ctx, app, false)
-            } else {
-                self.render_create_window(ctx, app, true)
-            };
-
-            self.render_content(ui, ctx, app, need_update_page);
-        });
+    fn has_query(req: &TunnelListRequest) -> bool {
+        req.has_query()
     }
 
-    fn reset(&mut self) {
-        self.data = None;
-        self.wait_data_list = false;
-        self.item_operation_map.clear();
-        self.show_create_window = false;
-        self.create_data = CreateData::default();
+    fn apply_query(req: &TunnelListRequest, items: &mut Vec<TunnelListItem>) {
+        req.apply_query(items);
     }
-}
 
-impl Logic {
-    fn render_content(
-        &mut self,
-        ui: &mut Ui,
-        ctx: &egui::Context,
-        app: &mut TemplateApp,
-        need_update_page: bool,
-    ) {
-        let mut need_request = need_update_page;
-        let mut cur_page_number: usize = 0;
-        if let Some(promise) = app.promise_map.get_mut(&self.key_get_list) {
-            if let Some(result) = promise.ready_mut() {
-                match result {
-                    Ok(ref resource) => match &resource.response_data {
-                        ResponseType::TunnelListResponse(ref tunnel_list) => {
-                            if self.wait_data_list {
-                                self.wait_data_list = false;
-                                self.data = Some(tunnel_list.clone());
-                                self.item_operation_map.clear();
-                            }
-                        }
-                        ResponseType::Error(err) => {
-                            if ui.button("retry").clicked() {
-                                need_request = true;
-                            }
-                            ui.colored_label(ui.visuals().error_fg_color, err);
-                        }
-                        _ => {
-                            if ui.button("retry").clicked() {
-                                need_request = true;
-                            }
-                            ui.colored_label(ui.visuals().error_fg_color, "Unknown error");
-                        }
-                    },
-                    Err(error) => {
-                        if ui.button("retry").clicked() {
-                            need_request = true;
-                        }
-
-                        ui.colored_label(
-                            ui.visuals().error_fg_color,
-                            if error.is_empty() {
-                                "Request failed"
-                            } else {
-                                error
-                            },
-                        );
-                    }
-                }
-            }
-        } else {
-            need_request = true;
+    fn to_page(response: &TunnelListResponse) -> ListPage<TunnelListItem> {
+        ListPage {
+            items: response.tunnels.clone(),
+            cur_page_number: response.cur_page_number,
+            total_count: response.total_count,
+            filtered: response.filtered,
         }
+    }
 
-        // 玩家列表渲染
-        if let Some(ref mut tunnel_list) = self.data {
-            ui.horizontal(|ui| {
-                // 刷新按钮
-                if ui.button("🔃").clicked() {
-                    need_request = true;
-                }
+    fn list(api: &ApiClient, req: &TunnelListRequest) -> ApiPromise<TunnelListResponse> {
+        api.tunnel_list(req)
+    }
 
-                // 计算页数
-                cur_page_number = tunnel_list.cur_page_number as usize;
-                let mut page_count = if tunnel_list.total_count % PAGE_SIZE == 0 {
-                    tunnel_list.total_count / PAGE_SIZE
-                } else {
-                    tunnel_list.total_count / PAGE_SIZE + 1
-                };
+    fn add(api: &ApiClient, draft: &TunnelForm) -> ApiPromise<GeneralResponse> {
+        api.add_tunnel(&draft.to_add_req())
+    }
 
-                if page_count <= 0 {
-                    page_count = 1;
-                }
+    fn update(api: &ApiClient, item: &TunnelListItem) -> ApiPromise<GeneralResponse> {
+        api.update_tunnel(&proto::TunnelUpdateReq::from(item.clone()))
+    }
 
-                if cur_page_number > 0 && page_count <= cur_page_number {
-                    cur_page_number = page_count - 1;
-                    need_request = true;
-                }
+    fn remove(api: &ApiClient, id: u32) -> ApiPromise<GeneralResponse> {
+        api.remove_tunnel(&proto::TunnelRemoveReq { id })
+    }
 
-                // 页数选择
-                if page_count > 1
-                    && ComboBox::from_label("Page")
-                        .selected_text(format!("{}", cur_page_number + 1))
-                        .show_index(ui, &mut cur_page_number, page_count, |i| {
-                            format!("{}", i + 1)
-                        })
-                        .changed()
-                {
-                    need_request = true;
-                }
-            });
+    fn columns(&self) -> Vec<CrudColumn<TunnelSortKey>> {
+        vec![
+            CrudColumn::new("id").sortable(TunnelSortKey::Id),
+            CrudColumn::new("type").sortable(TunnelSortKey::TunnelType),
+            CrudColumn::new("source → endpoint")
+                .sortable(TunnelSortKey::Source)
+                .width(Column::auto().clip(true)),
+            CrudColumn::new("sender → receiver").sortable(TunnelSortKey::Sender),
+            CrudColumn::new("enabled").sortable(TunnelSortKey::Enabled),
+            CrudColumn::new("description")
+                .sortable(TunnelSortKey::Description)
+                .width(Column::remainder().clip(true)),
+        ]
+    }
 
-            ui.horizontal(|ui| {
-                if ui.button("new tunnel").clicked() {
-                    self.show_create_window = true;
+    fn cell(&self, ui: &mut Ui, column: usize, row: &mut CrudRow<'_, Self>) {
+        let (item, origin) = (row.item, row.origin);
+        match column {
+            0 => {
+                ui.label(format!("{}", item.id));
+            }
+            1 => {
+                let index = (item.tunnel_type as usize).min(TUNNEL_TYPE_OPTION.len() - 1);
+                let response = ui.label(TUNNEL_TYPE_OPTION[index]);
+                if item.tunnel_type != origin.tunnel_type {
+                    highlight_modified(ui, response.rect);
                 }
-                ui.label(format!("total : {}", tunnel_list.total_count));
-            });
-
-            self.render_table(ui, ctx, app);
+            }
+            2 => {
+                let response = ui.label(format!("{} → {}", item.source, item.endpoint));
+                if item.source != origin.source || item.endpoint != origin.endpoint {
+                    highlight_modified(ui, response.rect);
+                }
+            }
+            3 => {
+                let players = self.players();
+                let response = ui.label(format!(
+                    "{} → {}",
+                    player_name(players, item.sender),
+                    player_name(players, item.receiver)
+                ));
+                if item.sender != origin.sender || item.receiver != origin.receiver {
+                    highlight_modified(ui, response.rect);
+                }
+            }
+            4 => {
+                let response = if item.enabled {
+                    ui.colored_label(Color32::GREEN, "✔")
+                } else {
+                    ui.colored_label(ui.visuals().error_fg_color, "✖")
+                };
+                if item.enabled != origin.enabled {
+                    highlight_modified(ui, response.rect);
+                }
+            }
+            _ => {
+                let response = ui.label(&item.description);
+                if item.description != origin.description {
+                    highlight_modified(ui, response.rect);
+                }
+            }
         }
+    }
 
-        // 请求列表数据
-        if need_request {
-            let req = proto::TunnelListRequest {
-                page_number: cur_page_number,
-                page_size: PAGE_SIZE,
-            };
-            app.http_request(
-                ctx,
-                &self.key_get_list,
-                None,
-                serde_json::to_string(&req).unwrap().into(),
-            );
-            self.wait_data_list = true;
-        }
+    fn form(&self, ui: &mut Ui, draft: &mut TunnelForm) {
+        draft.ui(ui, self.players());
     }
 
-    fn render_loading(&self, ui: &mut Ui) {
-        ui.painter().rect_filled(ui.max_rect(), 0.0, GRAY);
+    fn render_filters(&mut self, ui: &mut Ui, query: &mut Query) -> bool {
+        let players = self.players();
+        let mut changed = false;
+        ui.horizontal_wrapped(|ui| {
+            changed |= ui
+                .add(
+                    egui::TextEdit::singleline(&mut query.search)
+                        .hint_text("🔍 source/endpoint/description")
+                        .desired_width(200.0),
+                )
+                .changed();
+
+            let types: Vec<(u32, &str)> = TUNNEL_TYPE_OPTION[..TUNNEL_TYPE_OPTION.len() - 1]
+                .iter()
+                .enumerate()
+                .map(|(index, text)| (index as u32, *text))
+                .collect();
+            ui.label("type:");
+            changed |= filter_combo(ui, "tunnel_type_filter", &mut query.tunnel_type, &types);
+
+            ui.label("status:");
+            changed |= filter_combo(
+                ui,
+                "tunnel_enabled_filter",
+                &mut query.enabled,
+                &[(true, "enabled"), (false, "disabled")],
+            );
 
-        egui::Spinner::new().paint_at(
-            ui,
-            Rect::from_center_size(ui.max_rect().center(), vec2(30.0, 30.0)),
-        );
+            let methods: Vec<(&'static str, &str)> =
+                TUNNEL_ENCRYPTION_METHOD.iter().map(|x| (*x, *x)).collect();
+            ui.label("encryption:");
+            changed |= filter_combo(
+                ui,
+                "tunnel_encryption_filter",
+                &mut query.encryption_method,
+                &methods,
+            );
 
-        // 屏蔽下层输入
-        ui.interact(
-            ui.min_rect(),
-            egui::Id::new("Some Id"),
-            egui::Sense::click(),
-        );
+            changed |= player_filter(ui, "sender", &mut query.sender, players);
+            changed |= player_filter(ui, "receiver", &mut query.receiver, players);
+        });
+        changed
     }
 
-    fn render_table(&mut self, ui: &mut Ui, ctx: &egui::Context, app: &mut TemplateApp) {
-        let mut need_update_item_info = None;
-        let mut need_remove_item_info = None;
-
-        let update_item_operation = self.item_operation_map.get(&self.key_update_item);
-        let remove_item_operation = self.item_operation_map.get(&self.key_remove_item);
-
-        let table = TableBuilder::new(ui)
-            .striped(true)
-            .resizable(true)
-            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .column(Column::auto())
-            .min_scrolled_height(0.0);
-        table
-            .header(20.0, |mut header| {
-                header.col(|ui| {
-                    ui.strong("index");
-                });
-                header.col(|ui| {
-                    ui.strong("id");
-                });
-                header.col(|ui| {
-                    ui.strong("source(listen)");
-                });
-                header.col(|ui| {
-                    ui.strong("endpoint");
-                });
-                header.col(|ui| {
-                    ui.strong("enabled");
-                });
-                header.col(|ui| {
-                    ui.strong("compressed");
-                });
-                header.col(|ui| {
-                    ui.strong("sender(client)");
-                });
-                header.col(|ui| {
-                    ui.strong("receiver(server)");
-                });
-                header.col(|ui| {
-                    ui.strong("description");
-                });
-                header.col(|ui| {
-                    ui.strong("tunnel_type");
-                });
-                header.col(|ui| {
-                    ui.strong("password");
-                });
-                header.col(|ui| {
-                    ui.strong("username");
-                });
-                header.col(|ui| {
-                    ui.strong("encryption_method");
-                });
-                header.col(|ui| {
-                    ui.strong("custom_mapping");
-                });
-                header.col(|ui| {
-                    ui.strong("update");
-                });
-                header.col(|ui| {
-                    ui.strong("remove");
-                });
-            })
-            .body(|mut body| {
-                if let Some(ref mut item_list) = self.data {
-                    for (index, item) in item_list.tunnels.iter_mut().enumerate() {
-                        body.row(20.0, |mut row| {
-                            row.col(|ui| {
-                                ui.label(format!("{}", index + 1));
-                            });
-                            row.col(|ui| {
-                                ui.label(format!("{}", item.id));
-                            });
-                            row.col(|ui| {
-                                ui.text_edit_singleline(&mut item.source);
-                            });
-                            row.col(|ui| {
-                                ui.text_edit_singleline(&mut item.endpoint);
-                            });
-                            row.col(|ui| {
-                                ui.checkbox(&mut item.enabled, "enabled");
-                            });
-                            row.col(|ui| {
-                                ui.checkbox(&mut item.is_compressed, "compressed");
-                            });
-                            row.col(|ui| {
-                                render_number_u32(ui, &mut item.sender);
-                            });
-                            row.col(|ui| {
-                                render_number_u32(ui, &mut item.receiver);
-                            });
-                            row.col(|ui| {
-                                ui.text_edit_singleline(&mut item.description);
-                            });
-                            row.col(|ui| {
-                                let mut cur_index = item.tunnel_type as usize;
-                                if cur_index + 1 >= TUNNEL_TYPE_OPTION.len() {
-                                    cur_index = TUNNEL_TYPE_OPTION.len() - 1;
-                                }
-
-                                if ComboBox::from_label("Type")
-                                    .selected_text(TUNNEL_TYPE_OPTION[cur_index])
-                                    .show_index(
-                                        ui,
-                                        &mut cur_index,
-                                        TUNNEL_TYPE_OPTION.len() - 1,
-                                        |i| TUNNEL_TYPE_OPTION[i],
-                                    )
-                                    .changed()
-                                {
-                                    item.tunnel_type = cur_index as u32;
-                                }
-                            });
-                            row.col(|ui| {
-                                if tunnel_has_password(item.tunnel_type) {
-                                    ui.text_edit_singleline(&mut item.password);
-                                } else {
-                                    ui.label(&item.password);
-                                }
-                            });
-                            row.col(|ui| {
-                                if tunnel_has_user_name(item.tunnel_type) {
-                                    ui.text_edit_singleline(&mut item.username);
-                                } else {
-                                    ui.label(&item.username);
-                                }
-                            });
-                            row.col(|ui| {
-                                let mut index: usize = if let Some(i) = TUNNEL_ENCRYPTION_METHOD
-                                    .iter()
-                                    .position(|x| x == &item.encryption_method)
-                                {
-                                    i
-                                } else {
-                                    0
-                                };
-
-                                if ComboBox::from_label("Type")
-                                    .selected_text(TUNNEL_ENCRYPTION_METHOD[index])
-                                    .show_index(ui, &mut index, TUNNEL_ENCRYPTION_METHOD.len(), |i| {
-                                        TUNNEL_ENCRYPTION_METHOD[i]
-                                    })
-                                    .changed()
-                                {
-                                    item.encryption_method =
-                                        TUNNEL_ENCRYPTION_METHOD[index].to_owned();
-                                }
-                            });
-                            row.col(|ui| {
-                                ui.label("not support");
-                            });
-                            row.col(|ui| {
-                                if update_item_operation.is_none() {
-                                    if ui.button("🔄update").clicked() {
-                                        need_update_item_info = Some(item.clone());
-                                    }
-                                    return;
-                                }
-
-                                let (item_id, operation_result) = &update_item_operation.unwrap();
-
-                                if item_id == &INVALID_ITEM_ID {
-                                    if ui.button("🔄update").clicked() {
-                                        need_update_item_info = Some(item.clone());
-                                    }
-                                } else {
-                                    if item_id != &item.id {
-                                        return;
-                                    } else {
-                                        match operation_result {
-                                            OperationResult::Error(message) => {
-                                                if ui.button("🔄retry").clicked() {
-                                                    need_update_item_info = Some(item.clone());
-                                                }
-                                                ui.colored_label(
-                                                    ui.visuals().error_fg_color,
-                                                    message,
-                                                );
-                                            }
-                                            _ => {
-                                                ui.spinner();
-                                            }
-                                        }
-                                    }
-                                }
-                            });
-                            row.col(|ui| {
-                                // 没有正在删除的元素
-                                if remove_item_operation.is_none()
-                                    || remove_item_operation.unwrap().0 == INVALID_ITEM_ID
-                                {
-                                    if let Some(operation_result) = update_item_operation {
-                                        // 正在更新的元素不是当前元素，显示删除按钮
-                                        if operation_result.0 != item.id && ui.button("✖").clicked()
-                                        {
-                                            need_remove_item_info = Some(item.id);
-                                        }
-                                    } else {
-                                        if ui.button("✖").clicked() {
-                                            need_remove_item_info = Some(item.id);
-                                        }
-                                    }
-                                } else {
-                                    // 正在删除其他元素
-                                    if remove_item_operation.unwrap().0 != item.id {
-                                        return;
-                                    }
-
-                                    match &remove_item_operation.unwrap().1 {
-                                        OperationResult::Error(message) => {
-                                            if ui.button("✖").clicked() {
-                                                need_remove_item_info = Some(item.id);
-                                            }
-                                            ui.colored_label(ui.visuals().error_fg_color, message);
-                                        }
-                                        _ => {
-                                            ui.spinner();
-                                        }
-                                    }
-                                }
-                            });
-                        });
-                    }
-                }
-            });
-
-        // 更新操作
-        if let Some(info) = need_update_item_info {
-            self.item_operation_map.insert(
-                self.key_update_item.clone(),
-                (info.id, OperationResult::Wait),
-            );
-            let req = proto::TunnelUpdateReq {
-                id: info.id,
-                source: info.source,
-                endpoint: info.endpoint,
-                enabled: info.enabled as u8,
-                sender: info.sender,
-                receiver: info.receiver,
-                description: info.description,
-                tunnel_type: info.tunnel_type,
-                password: info.password,
-                username: info.username,
-                is_compressed: info.is_compressed as u8,
-                encryption_method: info.encryption_method,
-                custom_mapping: info.custom_mapping,
-            };
-            app.http_request(
-                ctx,
-                &self.key_update_item,
-                None,
-                serde_json::to_string(&req).unwrap().into(),
-            )
-        }
+    fn on_list_request(&mut self, api: &ApiClient) {
+        self.players_promise = Some(api.all_players());
+    }
 
-        // 删除操作
-        if let Some(info) = need_remove_item_info {
-            self.item_operation_map
-                .insert(self.key_remove_item.clone(), (info, OperationResult::Wait));
-            let req = proto::TunnelRemoveReq { id: info };
-            app.http_request(
-                ctx,
-                &self.key_remove_item,
-                None,
-                serde_json::to_string(&req).unwrap().into(),
-            )
+    fn push_change(
+        event: &PushEvent,
+        _items: &[TunnelListItem],
+    ) -> Option<PushChange<TunnelListItem>> {
+        match event {
+            PushEvent::TunnelAdded { .. } => Some(PushChange::Added),
+            PushEvent::TunnelUpdated { tunnel } => Some(PushChange::Updated(tunnel.clone())),
+            PushEvent::TunnelRemoved { id } => Some(PushChange::Removed(*id)),
+            _ => None,
         }
     }
 
-    fn render_create_window(
+    fn render_bulk_actions(
         &mut self,
-        ctx: &egui::Context,
-        app: &mut TemplateApp,
-        enabled: bool,
-    ) -> bool {
-        let mut request_finish = false;
-        egui::Window::new("New Tunnel")
-            .vscroll(true)
-            .hscroll(true)
-            .resizable(true)
-            .collapsible(true)
-            .open(&mut self.show_create_window)
-            .enabled(enabled)
-            .show(ctx, |ui| {
-                ui.horizontal(|ui| {
-                    ui.label("source(listen)     :");
-                    ui.text_edit_singleline(&mut self.create_data.source);
-                });
-
-                ui.horizontal(|ui| {
-                    ui.label("endpoint   :");
-                    ui.text_edit_singleline(&mut self.create_data.endpoint);
-                });
-                ui.horizontal(|ui| {
-                    ui.label("sender(client)     :");
-                    render_number_u32(ui, &mut self.create_data.sender);
-                });
-                ui.horizontal(|ui| {
-                    ui.label("receiver(server)   :");
-                    render_number_u32(ui, &mut self.create_data.receiver);
-                });
-                ui.horizontal(|ui| {
-                    ui.label("description:");
-                    ui.text_edit_singleline(&mut self.create_data.description);
-                });
-                ui.horizontal(|ui| {
-                    ui.checkbox(&mut self.create_data.is_compressed, "compressed");
-                });
-                ui.horizontal(|ui| {
-                    ui.label("encryption_method:");
-                    let mut index: usize = if let Some(i) = TUNNEL_ENCRYPTION_METHOD
-                        .iter()
-                        .position(|x| x == &self.create_data.encryption_method)
-                    {
-                        i
-                    } else {
-                        0
-                    };
-
-                    if ComboBox::from_label("Type")
-                        .selected_text(TUNNEL_ENCRYPTION_METHOD[index])
-                        .show_index(ui, &mut index, TUNNEL_ENCRYPTION_METHOD.len(), |i| {
-                            TUNNEL_ENCRYPTION_METHOD[i]
-                        })
-                        .changed()
-                    {
-                        self.create_data.encryption_method =
-                            TUNNEL_ENCRYPTION_METHOD[index].to_owned();
-                    }
-                });
-                ui.horizontal(|ui| {
-                    ui.label("type:");
-                    let mut cur_index = self.create_data.tunnel_type as usize;
-                    if cur_index + 1 >= TUNNEL_TYPE_OPTION.len() {
-                        cur_index = TUNNEL_TYPE_OPTION.len() - 1;
-                    }
-
-                    if ComboBox::from_label("")
-                        .selected_text(TUNNEL_TYPE_OPTION[cur_index])
-                        .show_index(ui, &mut cur_index, TUNNEL_TYPE_OPTION.len() - 1, |i| {
-                            TUNNEL_TYPE_OPTION[i]
-                        })
-                        .changed()
-                    {
-                        self.create_data.tunnel_type = cur_index as u32;
-                    }
-                });
-
-                if tunnel_has_user_name(self.create_data.tunnel_type) {
-                    ui.horizontal(|ui| {
-                        ui.label("username:");
-                        ui.text_edit_singleline(&mut self.create_data.username);
-                    });
-                }
-                if tunnel_has_password(self.create_data.tunnel_type) {
-                    ui.horizontal(|ui| {
-                        ui.label("password:");
-                        ui.text_edit_singleline(&mut self.create_data.password);
-                    });
-                }
-                ui.separator();
-                ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
-                    if ui.button("   ok   ").clicked() && app.can_request(&self.key_add_item) {
-                        let req = proto::TunnelAddReq {
-                            source: self.create_data.source.clone(),
-                            endpoint: self.create_data.endpoint.clone(),
-                            enabled: 1,
-                            sender: self.create_data.sender,
-                            receiver: self.create_data.receiver,
-                            description: self.create_data.description.clone(),
-                            tunnel_type: self.create_data.tunnel_type,
-                            password: self.create_data.password.clone(),
-                            username: self.create_data.username.clone(),
-                            is_compressed: self.create_data.is_compressed as u8,
-                            encryption_method: self.create_data.encryption_method.clone(),
-                            custom_mapping: self.create_data.custom_mapping.clone(),
-                        };
-                        app.http_request(
-                            ctx,
-                            &self.key_add_item,
-                            None,
-                            serde_json::to_string(&req).unwrap().into(),
-                        );
-                    }
-
-                    if let Some(promise) = app.promise_map.get(&self.key_add_item) {
-                        if let Some(result) = promise.ready() {
-                            match result {
-                                Ok(resource) => match &resource.response_data {
-                                    ResponseType::GeneralResponse(_) => {
-                                        request_finish = true;
-                                        app.promise_map.remove(&self.key_add_item);
-                                    }
-                                    ResponseType::Error(err) => {
-                                        ui.colored_label(ui.visuals().error_fg_color, err);
-                                    }
-                                    _ => {
-                                        ui.colored_label(
-                                            ui.visuals().error_fg_color,
-                                            "Unknown error",
-                                        );
-                                    }
-                                },
-                                Err(error) => {
-                                    ui.colored_label(
-                                        ui.visuals().error_fg_color,
-                                        if error.is_empty() {
-                                            "Login failed"
-                                        } else {
-                                            error
-                                        },
-                                    );
-                                }
-                            }
-                        } else {
-                            ui.spinner();
-                        }
-                    }
-                });
-            });
-
-        if request_finish {
-            self.show_create_window = false;
-            if let Some(data) = &mut self.data {
-                data.total_count = data.total_count + 1;
-                if data.tunnels.len() < PAGE_SIZE {
-                    return true;
-                }
-            }
+        ui: &mut Ui,
+        selected: &[&TunnelListItem],
+    ) -> Option<BulkItems<Self>> {
+        let mut action = None;
+        if ui.button("enable").clicked() {
+            action = Some(BulkAction::Enable);
         }
-        false
-    }
-
-    fn busy(&mut self, app: &mut TemplateApp) -> bool {
-        let mut removed_id = None;
-        for (key, (id, operation_result)) in &mut self.item_operation_map {
-            if *id != INVALID_ITEM_ID {
-                let promise_option = app.promise_map.get(key);
-                if let Some(promise) = promise_option {
-                    if let Some(result) = promise.ready() {
-                        match result {
-                            Ok(resource) => match &resource.response_data {
-                                ResponseType::GeneralResponse(_) => {
-                                    if key == &self.key_remove_item {
-                                        removed_id = Some(*id);
-                                    }
-                                    *operation_result = OperationResult::None;
-                                    *id = INVALID_ITEM_ID;
-                                }
-                                ResponseType::Error(err) => {
-                                    *operation_result = OperationResult::Error(err.clone());
-                                }
-                                _ => {
-                                    *operation_result =
-                                        OperationResult::Error("Unknown error".into());
-                                }
-                            },
-                            Err(error) => {
-                                *operation_result = OperationResult::Error(if error.is_empty() {
-                                    "Request failed".into()
-                                } else {
-                                    error.to_string()
-                                });
-                            }
-                        }
-                    } else {
-                        *operation_result = OperationResult::Wait;
-                    }
-                } else {
-                    *operation_result = OperationResult::None;
-                    *id = INVALID_ITEM_ID;
-                }
-            }
+        if ui.button("disable").clicked() {
+            action = Some(BulkAction::Disable);
         }
-
-        if let Some(removed_id) = removed_id {
-            if let Some(data) = &mut self.data {
-                data.tunnels.retain(|x| x.id != removed_id);
-                data.total_count -= 1;
-            }
+        if ui.button("toggle compression").clicked() {
+            action = Some(BulkAction::ToggleCompression);
         }
+        ui.menu_button("encryption ⏷", |ui| {
+            for method in TUNNEL_ENCRYPTION_METHOD {
+                if ui.button(method).clicked() {
+                    action = Some(BulkAction::Encryption(method));
+                    ui.close_menu();
+                }
+            }
+        });
 
-        !app.can_request(&self.key_get_list) || !app.can_request(&self.key_add_item)
+        let action = action?;
+        let items = selected
+            .iter()
+            .map(|x| {
+                let mut item = (*x).clone();
+                match action {
+                    BulkAction::Enable => item.enabled = true,
+                    BulkAction::Disable => item.enabled = false,
+                    BulkAction::ToggleCompression => item.is_compressed = !item.is_compressed,
+                    BulkAction::Encryption(method) => item.encryption_method = method.to_string(),
+                }
+                (Self::label(x), CrudRequest::Update(Box::new(item)))
+            })
+            .collect();
+        let title = match action {
+            BulkAction::Enable => "Enable tunnels".to_string(),
+            BulkAction::Disable => "Disable tunnels".to_string(),
+            BulkAction::ToggleCompression => "Toggle compression".to_string(),
+            BulkAction::Encryption(method) => format!("Set encryption to {}", method),
+        };
+        Some((title, items))
     }
 }
-
-fn tunnel_has_user_name(tunnel_type: u32) -> bool {
-    tunnel_type == 2
-}
-
-fn tunnel_has_password(tunnel_type: u32) -> bool {
-    tunnel_type == 2
-}
@@ -0,0 +1,199 @@
+//! 隧道编辑表单，新建窗口和详情窗口共用
+
+use crate::proto::{
+    self, PlayerListItem, TunnelListItem, TUNNEL_ENCRYPTION_METHOD, TUNNEL_TYPE_OPTION,
+    TUNNEL_TYPE_SOCKS5,
+};
+use crate::render::mapping::MappingEditor;
+use crate::render::player_picker::player_picker;
+use crate::validation::{check_endpoint, check_source, AddressError};
+use egui::{ComboBox, Ui};
+
+/// 表单中正在编辑的隧道数据
+#[derive(Clone, PartialEq)]
+pub struct TunnelForm {
+    pub source: String,
+    pub endpoint: String,
+    pub enabled: bool,
+    pub sender: u32,
+    pub receiver: u32,
+    pub description: String,
+    pub tunnel_type: u32,
+    pub password: String,
+    pub username: String,
+    pub is_compressed: bool,
+    pub encryption_method: String,
+    pub custom_mapping: MappingEditor,
+}
+
+impl Default for TunnelForm {
+    fn default() -> Self {
+        Self {
+            source: "".to_string(),
+            endpoint: "".to_string(),
+            enabled: true,
+            sender: 0,
+            receiver: 0,
+            description: "".to_string(),
+            tunnel_type: 0,
+            password: "".to_string(),
+            username: "".to_string(),
+            is_compressed: false,
+            encryption_method: TUNNEL_ENCRYPTION_METHOD[0].to_string(),
+            custom_mapping: MappingEditor::default(),
+        }
+    }
+}
+
+impl From<&TunnelListItem> for TunnelForm {
+    fn from(item: &TunnelListItem) -> Self {
+        Self {
+            source: item.source.clone(),
+            endpoint: item.endpoint.clone(),
+            enabled: item.enabled,
+            sender: item.sender,
+            receiver: item.receiver,
+            description: item.description.clone(),
+            tunnel_type: item.tunnel_type,
+            password: item.password.clone(),
+            username: item.username.clone(),
+            is_compressed: item.is_compressed,
+            encryption_method: item.encryption_method.clone(),
+            custom_mapping: MappingEditor::new(&item.custom_mapping),
+        }
+    }
+}
+
+impl TunnelForm {
+    pub fn is_valid(&self) -> bool {
+        check_source(&self.source).is_ok()
+            && check_endpoint(self.tunnel_type, &self.endpoint).is_ok()
+            && self.custom_mapping.is_valid()
+    }
+
+    pub fn to_item(&self, id: u32) -> TunnelListItem {
+        TunnelListItem {
+            id,
+            source: self.source.clone(),
+            endpoint: self.endpoint.clone(),
+            enabled: self.enabled,
+            sender: self.sender,
+            receiver: self.receiver,
+            description: self.description.clone(),
+            tunnel_type: self.tunnel_type,
+            password: self.password.clone(),
+            username: self.username.clone(),
+            is_compressed: self.is_compressed,
+            encryption_method: self.encryption_method.clone(),
+            custom_mapping: self.custom_mapping.to_map(),
+        }
+    }
+
+    pub fn to_add_req(&self) -> proto::TunnelAddReq {
+        proto::TunnelAddReq::from(self.to_item(0))
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, players: &[PlayerListItem]) {
+        egui::Grid::new(ui.id().with("tunnel_form"))
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("source(listen):");
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.source);
+                    render_address_error(ui, check_source(&self.source));
+                });
+                ui.end_row();
+
+                ui.label("endpoint:");
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.endpoint);
+                    render_address_error(ui, check_endpoint(self.tunnel_type, &self.endpoint));
+                });
+                ui.end_row();
+
+                ui.label("sender(client):");
+                player_picker(ui, "sender", &mut self.sender, players);
+                ui.end_row();
+
+                ui.label("receiver(server):");
+                player_picker(ui, "receiver", &mut self.receiver, players);
+                ui.end_row();
+
+                ui.label("description:");
+                ui.text_edit_singleline(&mut self.description);
+                ui.end_row();
+
+                ui.label("");
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.enabled, "enabled");
+                    ui.checkbox(&mut self.is_compressed, "compressed");
+                });
+                ui.end_row();
+
+                ui.label("encryption_method:");
+                let mut index: usize = TUNNEL_ENCRYPTION_METHOD
+                    .iter()
+                    .position(|x| x == &self.encryption_method)
+                    .unwrap_or_default();
+                if ComboBox::from_id_source(ui.id().with("encryption_method"))
+                    .selected_text(TUNNEL_ENCRYPTION_METHOD[index])
+                    .show_index(ui, &mut index, TUNNEL_ENCRYPTION_METHOD.len(), |i| {
+                        TUNNEL_ENCRYPTION_METHOD[i]
+                    })
+                    .changed()
+                {
+                    self.encryption_method = TUNNEL_ENCRYPTION_METHOD[index].to_owned();
+                }
+                ui.end_row();
+
+                ui.label("type:");
+                let mut cur_index = self.tunnel_type as usize;
+                if cur_index + 1 >= TUNNEL_TYPE_OPTION.len() {
+                    cur_index = TUNNEL_TYPE_OPTION.len() - 1;
+                }
+                if ComboBox::from_id_source(ui.id().with("tunnel_type"))
+                    .selected_text(TUNNEL_TYPE_OPTION[cur_index])
+                    .show_index(ui, &mut cur_index, TUNNEL_TYPE_OPTION.len() - 1, |i| {
+                        TUNNEL_TYPE_OPTION[i]
+                    })
+                    .changed()
+                {
+                    self.tunnel_type = cur_index as u32;
+                }
+                ui.end_row();
+
+                if tunnel_has_user_name(self.tunnel_type) {
+                    ui.label("username:");
+                    ui.text_edit_singleline(&mut self.username);
+                    ui.end_row();
+                }
+                if tunnel_has_password(self.tunnel_type) {
+                    ui.label("password:");
+                    ui.text_edit_singleline(&mut self.password);
+                    ui.end_row();
+                }
+            });
+
+        egui::CollapsingHeader::new(format!("custom_mapping ({})", self.custom_mapping.len()))
+            .id_source(ui.id().with("custom_mapping"))
+            .show(ui, |ui| {
+                self.custom_mapping.ui(ui);
+            });
+    }
+}
+
+/// 在输入框后显示地址错误
+fn render_address_error(ui: &mut Ui, result: Result<(), AddressError>) {
+    if let Err(err) = result {
+        ui.colored_label(ui.visuals().error_fg_color, "⚠")
+            .on_hover_text(err.to_string());
+    }
+}
+
+pub fn tunnel_has_user_name(tunnel_type: u32) -> bool {
+    tunnel_type == TUNNEL_TYPE_SOCKS5
+}
+
+pub fn tunnel_has_password(tunnel_type: u32) -> bool {
+    tunnel_type == TUNNEL_TYPE_SOCKS5
+}
@@ -0,0 +1,313 @@
+//! 密码和会话的保存。默认不保存，勾选 "remember me" 后通过 [`SecretStore`] 保存：
+//! 用主密码加密后放在 eframe 存储中，或者放在系统钥匙串中(`keyring` feature)。
+
+use crate::auth::Session;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// 密文格式的版本
+static SEALED_VERSION: u8 = 1;
+static SALT_LEN: usize = 16;
+static NONCE_LEN: usize = 12;
+/// PBKDF2 迭代次数
+#[cfg(not(test))]
+static PBKDF2_ROUNDS: u32 = 100_000;
+// 测试中没有优化，减少次数
+#[cfg(test)]
+static PBKDF2_ROUNDS: u32 = 1_000;
+
+/// 需要保护的数据
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct Secrets {
+    pub password: String,
+    /// 当前服务器的会话，None 表示未登录
+    pub session: Option<Session>,
+    /// 按配置名称保存
+    pub profiles: BTreeMap<String, ProfileSecrets>,
+}
+
+/// 一个服务器配置的密码和会话
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct ProfileSecrets {
+    pub password: Option<String>,
+    pub session: Option<Session>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum SecretError {
+    /// 主密码错误
+    WrongPassphrase,
+    /// 密文格式错误
+    Invalid(String),
+    /// 钥匙串等外部存储出错
+    Backend(String),
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretError::WrongPassphrase => write!(f, "wrong passphrase"),
+            SecretError::Invalid(msg) => write!(f, "invalid saved credentials: {}", msg),
+            SecretError::Backend(msg) => write!(f, "secret store: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SecretError {}
+
+/// 保存密码和会话的位置
+pub trait SecretStore {
+    /// 读取保存的数据，`sealed` 是 app 存储中的密文，没有保存过时返回 None
+    fn load(&self, sealed: Option<&str>) -> Result<Option<Secrets>, SecretError>;
+
+    /// 保存数据，返回需要放在 app 存储中的密文
+    fn save(&self, secrets: &Secrets) -> Result<Option<String>, SecretError>;
+
+    /// 删除保存的数据
+    fn clear(&self) -> Result<(), SecretError> {
+        Ok(())
+    }
+}
+
+/// 保存方式
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SecretBackend {
+    /// 主密码加密
+    #[default]
+    Passphrase,
+    /// 系统钥匙串
+    Keyring,
+}
+
+impl SecretBackend {
+    /// 当前平台可用的保存方式
+    pub fn available() -> &'static [SecretBackend] {
+        if cfg!(all(feature = "keyring", not(target_arch = "wasm32"))) {
+            &[SecretBackend::Passphrase, SecretBackend::Keyring]
+        } else {
+            &[SecretBackend::Passphrase]
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SecretBackend::Passphrase => "passphrase",
+            SecretBackend::Keyring => "OS keyring",
+        }
+    }
+}
+
+/// 用主密码(PBKDF2-SHA256)加密(ChaCha20-Poly1305)，密文放在 app 存储中
+pub struct PassphraseStore {
+    salt: Vec<u8>,
+    key: [u8; 32],
+}
+
+impl PassphraseStore {
+    /// 设置新的主密码
+    pub fn new(passphrase: &str) -> Result<Self, SecretError> {
+        let salt = random_bytes(SALT_LEN)?;
+        let key = derive_key(passphrase, &salt);
+        Ok(Self { salt, key })
+    }
+
+    /// 用主密码打开已有的密文
+    pub fn unlock(passphrase: &str, sealed: &str) -> Result<(Self, Secrets), SecretError> {
+        let salt = Sealed::parse(sealed)?.salt;
+        let store = Self {
+            key: derive_key(passphrase, &salt),
+            salt,
+        };
+        let secrets = store.open(sealed)?;
+        Ok((store, secrets))
+    }
+
+    fn open(&self, sealed: &str) -> Result<Secrets, SecretError> {
+        let sealed = Sealed::parse(sealed)?;
+        // 密文使用其他主密码加密
+        if sealed.salt != self.salt {
+            return Err(SecretError::WrongPassphrase);
+        }
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&self.key));
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&sealed.nonce),
+                sealed.ciphertext.as_slice(),
+            )
+            .map_err(|_| SecretError::WrongPassphrase)?;
+        serde_json::from_slice(&plaintext).map_err(|err| SecretError::Invalid(err.to_string()))
+    }
+}
+
+impl SecretStore for PassphraseStore {
+    fn load(&self, sealed: Option<&str>) -> Result<Option<Secrets>, SecretError> {
+        sealed.map(|x| self.open(x)).transpose()
+    }
+
+    fn save(&self, secrets: &Secrets) -> Result<Option<String>, SecretError> {
+        let plaintext =
+            serde_json::to_vec(secrets).map_err(|err| SecretError::Invalid(err.to_string()))?;
+        let nonce = random_bytes(NONCE_LEN)?;
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&self.key));
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|err| SecretError::Invalid(err.to_string()))?;
+
+        let mut data = vec![SEALED_VERSION];
+        data.extend_from_slice(&self.salt);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        Ok(Some(STANDARD.encode(data)))
+    }
+}
+
+/// 系统钥匙串，不需要主密码
+#[cfg(all(feature = "keyring", not(target_arch = "wasm32")))]
+pub struct KeyringStore {
+    entry: keyring::Entry,
+}
+
+#[cfg(all(feature = "keyring", not(target_arch = "wasm32")))]
+impl KeyringStore {
+    pub fn new() -> Result<Self, SecretError> {
+        let entry = keyring::Entry::new("npipe_web", "credentials")
+            .map_err(|err| SecretError::Backend(err.to_string()))?;
+        Ok(Self { entry })
+    }
+}
+
+#[cfg(all(feature = "keyring", not(target_arch = "wasm32")))]
+impl SecretStore for KeyringStore {
+    fn load(&self, _sealed: Option<&str>) -> Result<Option<Secrets>, SecretError> {
+        match self.entry.get_password() {
+            Ok(data) => serde_json::from_str(&data)
+                .map(Some)
+                .map_err(|err| SecretError::Invalid(err.to_string())),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(SecretError::Backend(err.to_string())),
+        }
+    }
+
+    fn save(&self, secrets: &Secrets) -> Result<Option<String>, SecretError> {
+        let data =
+            serde_json::to_string(secrets).map_err(|err| SecretError::Invalid(err.to_string()))?;
+        self.entry
+            .set_password(&data)
+            .map_err(|err| SecretError::Backend(err.to_string()))?;
+        Ok(None)
+    }
+
+    fn clear(&self) -> Result<(), SecretError> {
+        match self.entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(err) => Err(SecretError::Backend(err.to_string())),
+        }
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+fn random_bytes(len: usize) -> Result<Vec<u8>, SecretError> {
+    let mut bytes = vec![0u8; len];
+    getrandom::getrandom(&mut bytes).map_err(|err| SecretError::Backend(err.to_string()))?;
+    Ok(bytes)
+}
+
+/// 密文格式: base64(版本 | salt | nonce | 密文)
+struct Sealed {
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+impl Sealed {
+    fn parse(sealed: &str) -> Result<Self, SecretError> {
+        let data = STANDARD
+            .decode(sealed.trim())
+            .map_err(|err| SecretError::Invalid(err.to_string()))?;
+        if data.len() <= 1 + SALT_LEN + NONCE_LEN || data[0] != SEALED_VERSION {
+            return Err(SecretError::Invalid("unknown format".into()));
+        }
+        let (salt, rest) = data[1..].split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        Ok(Self {
+            salt: salt.to_vec(),
+            nonce: nonce.to_vec(),
+            ciphertext: ciphertext.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Token;
+    use crate::cookie::CookieJar;
+
+    fn secrets() -> Secrets {
+        Secrets {
+            password: "123".into(),
+            session: Some(Session {
+                cookies: CookieJar::from_response(
+                    "http://127.0.0.1:8120/api/login",
+                    ["auth-id=abc; Path=/; Max-Age=3600"],
+                    0,
+                ),
+                token: None,
+            }),
+            profiles: BTreeMap::from([(
+                "prod".to_string(),
+                ProfileSecrets {
+                    password: Some("456".into()),
+                    session: Some(Session::with_token(Token::Bearer("xyz".into()))),
+                },
+            )]),
+        }
+    }
+
+    #[test]
+    fn seal_and_unlock() {
+        let store = PassphraseStore::new("correct horse").unwrap();
+        let sealed = store.save(&secrets()).unwrap().unwrap();
+        assert!(!sealed.contains("auth-id"));
+        assert_eq!(store.load(Some(&sealed)).unwrap(), Some(secrets()));
+
+        let (store, loaded) = PassphraseStore::unlock("correct horse", &sealed).unwrap();
+        assert_eq!(loaded, secrets());
+        // 每次保存使用新的 nonce
+        assert_ne!(store.save(&loaded).unwrap().unwrap(), sealed);
+    }
+
+    #[test]
+    fn wrong_passphrase_or_damaged_data() {
+        let store = PassphraseStore::new("correct horse").unwrap();
+        let sealed = store.save(&secrets()).unwrap().unwrap();
+        assert_eq!(
+            PassphraseStore::unlock("battery staple", &sealed).err(),
+            Some(SecretError::WrongPassphrase)
+        );
+
+        let mut data = STANDARD.decode(&sealed).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            store.load(Some(&STANDARD.encode(data))),
+            Err(SecretError::WrongPassphrase)
+        );
+        assert!(matches!(
+            PassphraseStore::unlock("correct horse", "AAAA"),
+            Err(SecretError::Invalid(_))
+        ));
+    }
+}
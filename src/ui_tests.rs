@@ -0,0 +1,1329 @@
+//! 无窗口驱动 `TemplateApp` 的界面测试，HTTP 请求发往内存中的 [`MockServer`]。
+//!
+//! 控件通过 accesskit 输出按名称查找，点击/输入通过模拟输入事件完成。
+
+use crate::api::ApiClient;
+use crate::auth::{AuthMode, Token};
+use crate::cookie::SESSION_COOKIE;
+use crate::mock::{Fault, MockServer};
+use crate::profile::{ProfileColor, ServerProfile};
+use crate::proto;
+use crate::secret::SecretError;
+use crate::TemplateApp;
+use egui::accesskit::{Checked, Node, Role};
+use egui::{Event, Modifiers, MouseWheelUnit, PointerButton, Pos2, RawInput, Rect};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+const SCREEN_SIZE: egui::Vec2 = egui::vec2(4000.0, 2000.0);
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Harness {
+    ctx: egui::Context,
+    app: TemplateApp,
+    server: MockServer,
+    events: Vec<Event>,
+    nodes: Vec<Node>,
+    // 模拟时钟
+    time: f64,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let server = MockServer::start("127.0.0.1:0", "admin", "123").unwrap();
+        let ctx = egui::Context::default();
+        ctx.enable_accesskit();
+
+        let mut app = TemplateApp::default();
+        app.api_url = server.api_url();
+        app.password = "123".into();
+
+        let mut harness = Self {
+            ctx,
+            app,
+            server,
+            events: Vec::new(),
+            nodes: Vec::new(),
+            time: 0.0,
+        };
+        harness.settle();
+        harness
+    }
+
+    fn logged_in() -> Self {
+        let mut harness = Self::new();
+        harness.login();
+        harness
+    }
+
+    /// 当前会话 cookie 的值
+    fn session(&self) -> Option<String> {
+        let session = self.app.session.lock().unwrap();
+        session.cookies.get(SESSION_COOKIE).map(|x| x.value.clone())
+    }
+
+    fn login(&mut self) {
+        self.click("Login");
+        self.run_until(|harness| harness.app.logged_in);
+        // 等待玩家列表第一次加载完成
+        self.run_until(|harness| !harness.find_all(&[Role::Button], "🔃").is_empty());
+    }
+
+    fn step(&mut self) {
+        self.time += 0.1;
+        let raw_input = RawInput {
+            time: Some(self.time),
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, SCREEN_SIZE)),
+            events: std::mem::take(&mut self.events),
+            ..Default::default()
+        };
+        let app = &mut self.app;
+        let output = self.ctx.run(raw_input, |ctx| app.ui(ctx));
+        if let Some(update) = output.platform_output.accesskit_update {
+            self.nodes = update.nodes.into_iter().map(|(_, node)| node).collect();
+        }
+    }
+
+    /// 新打开的窗口/表格前几帧会计算大小和位置，等待布局稳定
+    fn settle(&mut self) {
+        for _ in 0..5 {
+            self.step();
+        }
+    }
+
+    /// 一直刷新直到满足条件，请求在后台线程完成。
+    /// 新数据到达后表格列宽会重新计算，所以之后再等待布局稳定
+    #[track_caller]
+    fn run_until(&mut self, condition: impl Fn(&Self) -> bool) {
+        let start = Instant::now();
+        while !condition(self) {
+            assert!(start.elapsed() < TIMEOUT, "timed out");
+            std::thread::sleep(Duration::from_millis(10));
+            self.step();
+        }
+        self.settle();
+    }
+
+    /// 按名称查找控件，多个同名控件按从上到下排序
+    fn find_all(&self, roles: &[Role], name: &str) -> Vec<Rect> {
+        let mut rects: Vec<Rect> = self
+            .nodes
+            .iter()
+            .filter(|node| node.name() == Some(name))
+            .filter(|node| roles.contains(&node.role()))
+            .filter_map(|node| node.bounds())
+            .map(|rect| {
+                Rect::from_min_max(
+                    egui::pos2(rect.x0 as f32, rect.y0 as f32),
+                    egui::pos2(rect.x1 as f32, rect.y1 as f32),
+                )
+            })
+            .collect();
+        rects.sort_by(|a, b| a.top().total_cmp(&b.top()));
+        rects
+    }
+
+    /// 是否有可以点击的按钮
+    fn has_enabled_button(&self, name: &str) -> bool {
+        self.nodes.iter().any(|node| {
+            node.role() == Role::Button && node.name() == Some(name) && !node.is_disabled()
+        })
+    }
+
+    /// 是否有内容为 `value` 的输入框
+    fn has_text_input(&self, value: &str) -> bool {
+        self.nodes
+            .iter()
+            .any(|node| node.role() == Role::TextInput && node.value() == Some(value))
+    }
+
+    /// 选中状态的开关按钮
+    fn is_selected(&self, name: &str) -> bool {
+        self.nodes.iter().any(|node| {
+            node.role() == Role::ToggleButton
+                && node.name() == Some(name)
+                && node.checked() == Some(Checked::True)
+        })
+    }
+
+    fn has_label(&self, text: &str) -> bool {
+        !self.find_all(&[Role::StaticText], text).is_empty()
+    }
+
+    fn click_at(&mut self, pos: Pos2) {
+        self.events.push(Event::PointerMoved(pos));
+        self.events.push(Event::PointerButton {
+            pos,
+            button: PointerButton::Primary,
+            pressed: true,
+            modifiers: Modifiers::NONE,
+        });
+        self.step();
+        self.events.push(Event::PointerButton {
+            pos,
+            button: PointerButton::Primary,
+            pressed: false,
+            modifiers: Modifiers::NONE,
+        });
+        self.step();
+        // 相邻两次点击间隔足够长，避免被识别为双击
+        self.time += 1.0;
+    }
+
+    /// 在指定位置滚动鼠标滚轮，向下滚动时 `delta` 为负
+    fn scroll_at(&mut self, pos: Pos2, delta: f32) {
+        self.events.push(Event::PointerMoved(pos));
+        self.events.push(Event::MouseWheel {
+            unit: MouseWheelUnit::Point,
+            delta: egui::vec2(0.0, delta),
+            modifiers: Modifiers::NONE,
+        });
+        self.settle();
+    }
+
+    fn click_nth(&mut self, name: &str, index: usize) {
+        let rects = self.find_all(
+            &[
+                Role::Button,
+                Role::ToggleButton,
+                Role::CheckBox,
+                Role::RadioButton,
+            ],
+            name,
+        );
+        let rect = rects
+            .get(index)
+            .unwrap_or_else(|| panic!("button {} #{} not found", name, index));
+        self.click_at(rect.center());
+    }
+
+    /// 点击从上往下第 `index` 个复选框
+    fn click_checkbox(&mut self, index: usize) {
+        let mut rects: Vec<Rect> = self
+            .nodes
+            .iter()
+            .filter(|node| node.role() == Role::CheckBox)
+            .filter_map(|node| node.bounds())
+            .map(|rect| {
+                Rect::from_min_max(
+                    egui::pos2(rect.x0 as f32, rect.y0 as f32),
+                    egui::pos2(rect.x1 as f32, rect.y1 as f32),
+                )
+            })
+            .collect();
+        rects.sort_by(|a, b| a.top().total_cmp(&b.top()));
+        let rect = rects
+            .get(index)
+            .unwrap_or_else(|| panic!("checkbox #{} not found", index));
+        self.click_at(rect.center());
+    }
+
+    fn click(&mut self, name: &str) {
+        self.click_nth(name, 0);
+    }
+
+    /// 在当前内容为 `value` 的输入框中输入文字
+    fn type_into_input(&mut self, value: &str, text: &str) {
+        self.type_into(Role::TextInput, value, text);
+    }
+
+    /// 在当前内容为 `value` 的多行输入框中输入文字
+    fn type_into_multiline(&mut self, value: &str, text: &str) {
+        self.type_into(Role::MultilineTextInput, value, text);
+    }
+
+    fn type_into(&mut self, role: Role, value: &str, text: &str) {
+        let input = self
+            .nodes
+            .iter()
+            .find(|node| node.role() == role && node.value() == Some(value))
+            .and_then(|node| node.bounds())
+            .unwrap_or_else(|| panic!("no text input with {:?}", value));
+        let input = Rect::from_min_max(
+            egui::pos2(input.x0 as f32, input.y0 as f32),
+            egui::pos2(input.x1 as f32, input.y1 as f32),
+        );
+        self.click_at(input.center());
+        self.events.push(Event::Text(text.to_string()));
+        self.step();
+        self.step();
+    }
+
+    /// 在标签右侧的输入框(包括密码框)中输入文字
+    fn type_after_label(&mut self, label: &str, text: &str) {
+        let label = self.find_all(&[Role::StaticText], label)[0];
+        let input = self
+            .nodes
+            .iter()
+            .filter(|node| matches!(node.role(), Role::TextInput | Role::PasswordInput))
+            .filter_map(|node| node.bounds())
+            .map(|rect| {
+                Rect::from_min_max(
+                    egui::pos2(rect.x0 as f32, rect.y0 as f32),
+                    egui::pos2(rect.x1 as f32, rect.y1 as f32),
+                )
+            })
+            .filter(|rect| {
+                rect.left() >= label.right() && rect.y_range().contains(label.center().y)
+            })
+            .min_by(|a, b| a.left().total_cmp(&b.left()))
+            .unwrap_or_else(|| panic!("no text input after {:?}", label));
+        self.click_at(input.center());
+        self.events.push(Event::Text(text.to_string()));
+        self.step();
+        // 依赖输入内容的控件下一帧才会更新
+        self.step();
+    }
+}
+
+fn add_tunnel(store: &mut crate::mock::Store, description: &str) -> u32 {
+    store.add_tunnel(proto::TunnelAddReq {
+        source: "0.0.0.0:8000".into(),
+        endpoint: "127.0.0.1:9000".into(),
+        enabled: 1,
+        sender: 0,
+        receiver: 0,
+        description: description.into(),
+        tunnel_type: 0,
+        password: String::new(),
+        username: String::new(),
+        is_compressed: 0,
+        encryption_method: "None".into(),
+        custom_mapping: HashMap::new(),
+    })
+}
+
+#[test]
+fn login_flips_logged_in() {
+    let mut harness = Harness::new();
+    assert!(!harness.app.logged_in);
+
+    harness.click("Login");
+    harness.run_until(|harness| harness.app.logged_in);
+    assert!(!harness.app.session.lock().unwrap().is_empty());
+}
+
+#[test]
+fn bearer_token_refreshed_on_expiry() {
+    let mut harness = Harness::new();
+    harness
+        .server
+        .with_store(|store| store.add_player("alice", "1"));
+    harness.click("bearer token");
+    harness.login();
+    let token = harness.app.session.lock().unwrap().token.clone();
+    assert_eq!(token, Some(Token::Bearer("token1".into())));
+    assert_eq!(harness.session(), None);
+
+    // 服务器回复 401 后用密码重新获取 token，不需要重新登录
+    harness.server.expire_sessions();
+    harness.click("🔃");
+    harness.run_until(|harness| {
+        let token = harness.app.session.lock().unwrap().token.clone();
+        token != Some(Token::Bearer("token1".into()))
+    });
+    harness.run_until(|harness| harness.has_label("alice"));
+    assert!(harness.app.logged_in);
+}
+
+#[test]
+fn api_key_login() {
+    let mut harness = Harness::new();
+    harness.server.add_api_key("key1");
+    harness.click("API key");
+    harness.settle();
+    assert!(!harness.has_label("username:"));
+    assert!(harness.has_label("api key:"));
+    harness.app.password = "key1".into();
+    harness.login();
+    let token = harness.app.session.lock().unwrap().token.clone();
+    assert_eq!(token, Some(Token::ApiKey("key1".into())));
+
+    // 认证方式按配置保存
+    harness.app.save_profile("keyed", ProfileColor::None, false);
+    assert_eq!(harness.app.profiles[0].auth_mode, AuthMode::ApiKey);
+}
+
+#[test]
+fn session_renewed_before_expiry() {
+    let mut harness = Harness::new();
+    harness.server.set_session_max_age(30);
+    let session = ApiClient::new(harness.server.api_url())
+        .login(AuthMode::Cookie, &harness.app.login_req())
+        .block_and_take()
+        .unwrap();
+    harness.server.set_session_max_age(3600);
+    harness.app.login_success(session.shared());
+
+    // 会话在 60 秒内过期，使用密码重新登录
+    harness.run_until(|harness| harness.session().as_deref() != Some("session1"));
+    assert!(harness.app.logged_in);
+    assert_eq!(harness.session().as_deref(), Some("session2"));
+}
+
+#[test]
+fn session_cookie_expiry_logs_out() {
+    let mut harness = Harness::new();
+    harness.server.set_session_max_age(1);
+    harness.login();
+    // 没有密码时无法重新登录，过期后登出
+    harness.app.password.clear();
+    harness.run_until(|harness| !harness.app.logged_in);
+    assert_eq!(harness.session(), None);
+}
+
+#[test]
+fn switch_server_profiles() {
+    let mut harness = Harness::new();
+    harness
+        .server
+        .with_store(|store| store.add_player("alice", "1"));
+    let prod = MockServer::start("127.0.0.1:0", "admin", "456").unwrap();
+    prod.with_store(|store| store.add_player("bob", "1"));
+
+    harness.click("save as profile");
+    harness.type_after_label("name:", "staging");
+    harness.click("💾 save profile");
+    assert_eq!(harness.app.profiles[0].name, "staging");
+    assert_eq!(harness.app.profiles[0].password, None);
+    harness.login();
+    harness.run_until(|harness| harness.has_label("alice"));
+
+    harness.app.profiles.push(ServerProfile {
+        name: "prod".into(),
+        api_url: prod.api_url(),
+        username: "admin".into(),
+        password: Some("456".into()),
+        ..Default::default()
+    });
+    harness.click("staging");
+    harness.settle();
+    harness.click("prod");
+    harness.run_until(|harness| !harness.app.logged_in);
+    harness.login();
+    harness.run_until(|harness| harness.has_label("bob"));
+
+    // 切换回来时继续使用之前的会话
+    harness.click("prod");
+    harness.settle();
+    harness.click("staging");
+    harness.run_until(|harness| harness.has_label("alice"));
+    assert!(harness.app.logged_in);
+    assert!(harness.app.profiles[1].session.is_some());
+}
+
+#[test]
+fn copy_tunnel_between_servers() {
+    let mut harness = Harness::new();
+    let carol_tunnel = harness.server.with_store(|store| {
+        let alice = store.add_player("alice", "1");
+        add_tunnel(store, "web");
+        store.tunnels[0].receiver = alice;
+        let carol = store.add_player("carol", "1");
+        let id = add_tunnel(store, "to carol");
+        store.tunnels[1].receiver = carol;
+        id
+    });
+    let prod = MockServer::start("127.0.0.1:0", "admin", "456").unwrap();
+    let alice = prod.with_store(|store| {
+        store.add_player("bob", "1");
+        store.add_player("alice", "1")
+    });
+    harness
+        .app
+        .save_profile("staging", ProfileColor::None, false);
+    harness.app.profiles.push(ServerProfile {
+        name: "prod".into(),
+        api_url: prod.api_url(),
+        username: "admin".into(),
+        password: Some("456".into()),
+        ..Default::default()
+    });
+    harness.login();
+
+    harness.click("🌐servers");
+    harness.run_until(|harness| harness.has_label("web"));
+    assert!(!harness.has_label("bob"));
+    harness.click("Login");
+    harness.run_until(|harness| harness.has_label("bob"));
+    assert!(harness.app.profiles[1].session.is_some());
+
+    harness.click("📋 copy to");
+    harness.settle();
+    harness.click("prod");
+    harness.run_until(|harness| harness.find_all(&[Role::StaticText], "web").len() == 2);
+    let tunnels = prod.with_store(|store| store.tunnels.clone());
+    assert_eq!(tunnels.len(), 1);
+    // 接收者按用户名对应到目标服务器的玩家
+    assert_eq!(tunnels[0].receiver, alice);
+
+    // 目标服务器上没有同名玩家时不复制
+    harness.click_nth("📋 copy to", 1);
+    harness.settle();
+    harness.click("prod");
+    harness.run_until(|harness| {
+        harness.has_label(&format!(
+            "can't copy #{} 0.0.0.0:8000 → prod: unknown player 'carol'",
+            carol_tunnel
+        ))
+    });
+    assert_eq!(prod.with_store(|store| store.tunnels.len()), 1);
+}
+
+#[test]
+fn servers_page_loads_every_page() {
+    let mut harness = Harness::new();
+    harness.server.with_store(|store| {
+        for i in 0..150 {
+            store.add_player(&format!("player{}", i), "1");
+        }
+        add_tunnel(store, "web");
+        store.tunnels[0].receiver = store.players[149].id;
+    });
+    let prod = MockServer::start("127.0.0.1:0", "admin", "456").unwrap();
+    let receiver = prod.with_store(|store| store.add_player("player149", "1"));
+    harness
+        .app
+        .save_profile("staging", ProfileColor::None, false);
+    harness.app.profiles.push(ServerProfile {
+        name: "prod".into(),
+        api_url: prod.api_url(),
+        username: "admin".into(),
+        password: Some("456".into()),
+        ..Default::default()
+    });
+    harness.login();
+
+    harness.click("🌐servers");
+    harness.run_until(|harness| harness.has_label("player149"));
+    harness.click("Login");
+    harness.run_until(|harness| harness.find_all(&[Role::Button], "Login").is_empty());
+    harness.run_until(|harness| harness.has_label("server → player149"));
+
+    // 第二页的玩家同样可以对应，隧道表格在玩家列表下方
+    harness.scroll_at(SCREEN_SIZE.to_pos2() / 2.0, -SCREEN_SIZE.y * 2.0);
+    harness.click("📋 copy to");
+    harness.settle();
+    harness.click("prod");
+    harness.run_until(|harness| harness.find_all(&[Role::StaticText], "web").len() == 2);
+    let tunnels = prod.with_store(|store| store.tunnels.clone());
+    assert_eq!(tunnels[0].receiver, receiver);
+}
+
+#[test]
+fn credentials_not_saved_by_default() {
+    let mut harness = Harness::logged_in();
+    harness.app.save_secrets();
+
+    let saved = serde_json::to_string(&harness.app).unwrap();
+    let app: TemplateApp = serde_json::from_str(&saved).unwrap();
+    assert!(!app.logged_in);
+    assert!(app.password.is_empty() && app.session.lock().unwrap().is_empty());
+    assert!(!app.secrets_locked());
+}
+
+#[test]
+fn remember_me_encrypts_credentials() {
+    let mut harness = Harness::new();
+    harness.click("remember me");
+    harness.settle();
+    harness.type_after_label("master passphrase:", "correct horse");
+    harness.click("🔒 set passphrase");
+    assert!(harness.app.secrets_unlocked());
+    harness.settle();
+    harness.login();
+    harness.app.save_secrets();
+
+    let saved = serde_json::to_string(&harness.app).unwrap();
+    assert!(!saved.contains(&harness.session().unwrap()));
+
+    // 重新启动后需要主密码才能恢复密码和会话
+    let mut app: TemplateApp = serde_json::from_str(&saved).unwrap();
+    assert!(!app.logged_in && app.password.is_empty());
+    assert!(app.secrets_locked());
+    assert_eq!(
+        app.unlock_secrets("battery staple"),
+        Err(SecretError::WrongPassphrase)
+    );
+    app.unlock_secrets("correct horse").unwrap();
+    assert!(app.logged_in);
+    assert_eq!(app.password, "123");
+    let session = app
+        .session
+        .lock()
+        .unwrap()
+        .cookies
+        .get(SESSION_COOKIE)
+        .cloned();
+    assert_eq!(session.map(|x| x.value), harness.session());
+}
+
+#[test]
+fn wrong_password_stays_logged_out() {
+    let mut harness = Harness::new();
+    harness.app.password = "456".into();
+
+    harness.click("Login");
+    harness.run_until(|harness| harness.has_label("wrong username or password"));
+    assert!(!harness.app.logged_in);
+}
+
+#[test]
+fn unauthorized_response_logs_out() {
+    let mut harness = Harness::logged_in();
+
+    harness.server.inject(None, Fault::Unauthorized);
+    harness.click("🔃");
+    harness.run_until(|harness| !harness.app.logged_in);
+    assert!(harness.app.session.lock().unwrap().is_empty());
+}
+
+#[test]
+fn session_expired_code_logs_out() {
+    let mut harness = Harness::logged_in();
+
+    harness
+        .server
+        .inject(Some("player_list"), Fault::SessionExpired);
+    harness.click("🔃");
+    harness.run_until(|harness| !harness.app.logged_in);
+}
+
+#[test]
+fn add_player_bumps_total_count() {
+    let mut harness = Harness::logged_in();
+
+    harness.click("new player");
+    harness.settle();
+    harness.type_after_label("username:", "alice");
+    harness.type_after_label("password:", "secret");
+    harness.click("   ok   ");
+
+    harness.run_until(|harness| harness.has_label("total : 1"));
+    harness.run_until(|harness| harness.has_label("alice"));
+    let players = harness.server.with_store(|store| store.players.clone());
+    assert_eq!(players.len(), 1);
+    assert_eq!(players[0].username, "alice");
+    assert_eq!(players[0].password, "secret");
+}
+
+#[test]
+fn remove_tunnel_drops_row() {
+    let mut harness = Harness::logged_in();
+    harness.server.with_store(|store| {
+        add_tunnel(store, "first");
+        add_tunnel(store, "second");
+    });
+
+    harness.click("🔀tunnel");
+    harness.run_until(|harness| harness.has_label("total : 2"));
+    assert_eq!(harness.find_all(&[Role::Button], "✖").len(), 2);
+
+    harness.click_nth("✖", 0);
+    harness.run_until(|harness| harness.has_label("total : 1"));
+    assert_eq!(harness.find_all(&[Role::Button], "✖").len(), 1);
+    let tunnels = harness.server.with_store(|store| store.tunnels.clone());
+    assert_eq!(tunnels.len(), 1);
+    assert_eq!(tunnels[0].description, "second");
+}
+
+#[test]
+fn slow_row_operations_run_in_parallel() {
+    let mut harness = Harness::logged_in();
+    harness.server.with_store(|store| {
+        store.add_player("alice", "1");
+        store.add_player("bob", "2");
+        store.add_player("carol", "3");
+    });
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 3"));
+
+    for path in ["update_player", "remove_player"] {
+        harness
+            .server
+            .inject(Some(path), Fault::Delay(Duration::from_millis(300)));
+    }
+    // 更新前两行，同时删除第三行
+    harness.click_nth("🔄update", 0);
+    harness.step();
+    assert_eq!(harness.find_all(&[Role::Button], "🔄update").len(), 2);
+    harness.click_nth("🔄update", 0);
+    harness.step();
+    assert_eq!(harness.find_all(&[Role::Button], "✖").len(), 1);
+    harness.click_nth("✖", 0);
+    harness.step();
+    assert!(harness.find_all(&[Role::Button], "🔄update").is_empty());
+    assert!(harness.find_all(&[Role::Button], "✖").is_empty());
+
+    harness.run_until(|harness| {
+        harness.has_label("total : 2") && harness.find_all(&[Role::Button], "🔄update").len() == 2
+    });
+    let players = harness.server.with_store(|store| store.players.clone());
+    assert_eq!(players.len(), 2);
+    assert_eq!(players[1].username, "bob");
+}
+
+#[test]
+fn tunnel_detail_save_and_revert() {
+    let mut harness = Harness::logged_in();
+    harness.server.with_store(|store| {
+        add_tunnel(store, "first");
+    });
+
+    harness.click("🔀tunnel");
+    harness.run_until(|harness| harness.has_label("total : 1"));
+    harness.click("✏");
+    harness.settle();
+
+    harness.type_after_label("description:", " edited");
+    assert!(harness.has_label("modified"));
+    harness.click("↺ Revert");
+    harness.step();
+    assert!(!harness.has_label("modified"));
+
+    harness.type_after_label("description:", " edited");
+    // 未保存的修改直接显示在列表中
+    assert!(harness.has_label("first edited"));
+    harness.click("💾 Save");
+    harness.run_until(|harness| !harness.has_label("modified"));
+    let tunnels = harness.server.with_store(|store| store.tunnels.clone());
+    assert_eq!(tunnels[0].description, "first edited");
+}
+
+#[test]
+fn player_edits_survive_refresh_until_discarded() {
+    let mut harness = Harness::logged_in();
+    harness.server.with_store(|store| {
+        store.add_player("alice", "1");
+        store.add_player("bob", "2");
+    });
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 2"));
+
+    harness.type_into_input("1", "x");
+    assert!(!harness
+        .find_all(&[Role::Button], "💾 save all modified (1)")
+        .is_empty());
+
+    // 刷新前需要确认，取消后修改仍然保留
+    harness.click("🔃");
+    harness.step();
+    assert!(harness.has_label("1 unsaved change(s) will be lost"));
+    harness.click("cancel");
+    harness.step();
+    assert!(!harness.has_label("1 unsaved change(s) will be lost"));
+
+    harness.click("💾 save all modified (1)");
+    harness.run_until(|harness| {
+        !harness
+            .find_all(&[Role::Button], "💾 save all modified (0)")
+            .is_empty()
+    });
+    let players = harness.server.with_store(|store| store.players.clone());
+    assert_eq!(players[0].password, "1x");
+    assert_eq!(players[1].password, "2");
+}
+
+#[test]
+fn auto_refresh_keeps_player_edits() {
+    let mut harness = Harness::new();
+    // 没有推送接口时自动打开定时刷新
+    harness.server.set_push_support(false);
+    harness.login();
+    harness.server.with_store(|store| {
+        store.add_player("alice", "1");
+        store.add_player("bob", "2");
+    });
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 2"));
+    assert_eq!(harness.find_all(&[Role::StaticText], "offline").len(), 2);
+
+    harness.type_into_input("1", "x");
+    harness.server.with_store(|store| {
+        store.players[1].online = true;
+        store.players[1].password = "3".into();
+    });
+
+    // 后台刷新不需要确认，也不会覆盖正在编辑的行
+    harness.run_until(|harness| harness.has_label("online"));
+    assert!(harness.has_label("last updated 0 s ago"));
+    assert!(!harness.has_label("1 unsaved change(s) will be lost"));
+    assert!(harness.has_enabled_button("💾 save all modified (1)"));
+    harness.type_into_input("1x", "y");
+    harness.type_into_input("3", "4");
+    harness.click("💾 save all modified (2)");
+    harness.run_until(|harness| {
+        !harness
+            .find_all(&[Role::Button], "💾 save all modified (0)")
+            .is_empty()
+    });
+    let players = harness.server.with_store(|store| store.players.clone());
+    assert_eq!(players[0].password, "1xy");
+    assert_eq!(players[1].password, "34");
+}
+
+#[test]
+fn refresh_started_before_save_keeps_saved_row() {
+    let mut harness = Harness::new();
+    harness.server.set_push_support(false);
+    harness.login();
+    harness.server.with_store(|store| {
+        store.add_player("alice", "1");
+    });
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 1"));
+    harness.type_into_input("1", "x");
+
+    // 后台刷新的回复是保存之前的数据，在保存完成后才到达
+    harness.server.inject(
+        Some("player_list"),
+        Fault::HoldReply(Duration::from_secs(1)),
+    );
+    let count = harness.server.request_count("player_list");
+    harness.run_until(|harness| harness.server.request_count("player_list") > count);
+    harness.server.clear_faults();
+    harness.click("💾 save all modified (1)");
+    harness.run_until(|harness| {
+        !harness
+            .find_all(&[Role::Button], "💾 save all modified (0)")
+            .is_empty()
+    });
+    assert_eq!(
+        harness
+            .server
+            .with_store(|store| store.players[0].password.clone()),
+        "1x"
+    );
+
+    harness.run_until(|harness| harness.has_label("last updated 0 s ago"));
+    assert!(harness.has_text_input("1x"));
+    assert!(!harness.has_text_input("1"));
+}
+
+#[test]
+fn tunnel_shows_players_past_first_page() {
+    let mut harness = Harness::logged_in();
+    harness.server.with_store(|store| {
+        for i in 0..150 {
+            store.add_player(&format!("player{}", i), "1");
+        }
+        add_tunnel(store, "web");
+        store.tunnels[0].sender = store.players[149].id;
+    });
+
+    harness.click("🔀tunnel");
+    harness.run_until(|harness| harness.has_label("total : 1"));
+    harness.run_until(|harness| harness.has_label("player149 → server"));
+}
+
+#[test]
+fn push_updates_players_and_tunnels() {
+    let mut harness = Harness::logged_in();
+    let (alice, bob) = harness
+        .server
+        .with_store(|store| (store.add_player("alice", "1"), store.add_player("bob", "2")));
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 2") && harness.has_label("● live"));
+    assert!(!harness.has_label("online"));
+
+    harness.type_into_input("2", "x");
+    harness.server.set_online(alice, true);
+    harness.server.set_online(bob, true);
+    harness.run_until(|harness| harness.find_all(&[Role::StaticText], "online").len() == 2);
+    assert!(!harness
+        .find_all(&[Role::Button], "💾 save all modified (1)")
+        .is_empty());
+
+    harness.click("🔀tunnel");
+    harness.run_until(|harness| harness.has_label("total : 0"));
+    let id = harness
+        .server
+        .with_store(|store| add_tunnel(store, "pushed"));
+    let tunnel = harness.server.with_store(|store| store.tunnels[0].clone());
+    harness
+        .server
+        .push(proto::PushEvent::TunnelAdded { tunnel });
+    harness.run_until(|harness| harness.has_label("pushed"));
+    assert!(harness.has_label("total : 1"));
+
+    harness.server.with_store(|store| store.tunnels.clear());
+    harness.server.push(proto::PushEvent::TunnelRemoved { id });
+    harness.run_until(|harness| harness.has_label("total : 0"));
+    assert!(!harness.has_label("pushed"));
+}
+
+#[test]
+fn push_event_survives_held_refresh() {
+    let mut harness = Harness::logged_in();
+    let id = harness.server.with_store(|store| add_tunnel(store, "web"));
+    harness.click("🔀tunnel");
+    harness.run_until(|harness| harness.has_label("total : 1") && harness.has_label("● live"));
+
+    // 新增的隧道触发后台刷新，回复中是推送更新之前的数据
+    harness.server.inject(
+        Some("tunnel_list"),
+        Fault::HoldReply(Duration::from_secs(1)),
+    );
+    let count = harness.server.request_count("tunnel_list");
+    let tunnel = harness.server.with_store(|store| {
+        add_tunnel(store, "dns");
+        store.tunnels[1].clone()
+    });
+    harness
+        .server
+        .push(proto::PushEvent::TunnelAdded { tunnel });
+    harness.run_until(|harness| harness.server.request_count("tunnel_list") > count);
+    harness.server.clear_faults();
+
+    let tunnel = harness.server.with_store(|store| {
+        let tunnel = store.tunnels.iter_mut().find(|x| x.id == id).unwrap();
+        tunnel.description = "web2".into();
+        tunnel.clone()
+    });
+    harness
+        .server
+        .push(proto::PushEvent::TunnelUpdated { tunnel });
+    harness.run_until(|harness| harness.has_label("web2"));
+
+    harness.run_until(|harness| harness.has_label("total : 2"));
+    assert!(harness.has_label("dns"));
+    assert!(harness.has_label("web2"));
+    assert!(!harness.has_label("web"));
+}
+
+#[test]
+fn traffic_stats_page() {
+    let mut harness = Harness::logged_in();
+    harness.server.with_store(|store| {
+        add_tunnel(store, "web");
+        let id = add_tunnel(store, "idle");
+        store
+            .tunnels
+            .iter_mut()
+            .find(|x| x.id == id)
+            .unwrap()
+            .enabled = false;
+    });
+
+    harness.click("📈stats");
+    harness.run_until(|harness| {
+        harness.has_label("never") && !harness.find_all(&[Role::ToggleButton], "web").is_empty()
+    });
+    assert!(!harness.is_selected("web"));
+
+    // 点击表格中的名称切换曲线
+    harness.click("web");
+    harness.run_until(|harness| harness.is_selected("web"));
+    assert!(!harness.is_selected("idle"));
+}
+
+#[test]
+fn pick_sender_by_username() {
+    let mut harness = Harness::logged_in();
+    let (alice, bob) = harness.server.with_store(|store| {
+        let bob = store.add_player("bob", "1");
+        let alice = store.add_player("alice", "1");
+        add_tunnel(store, "first");
+        (alice, bob)
+    });
+
+    harness.click("🔀tunnel");
+    harness.run_until(|harness| harness.has_label("server → server"));
+    harness.click("✏");
+    harness.settle();
+
+    harness.click_nth("server ⏷", 0);
+    harness.events.push(Event::Text("ali".into()));
+    harness.step();
+    harness.step();
+    assert!(harness
+        .find_all(&[Role::ToggleButton], &format!("bob (#{})", bob))
+        .is_empty());
+    harness.click(&format!("alice (#{})", alice));
+    harness.step();
+    assert!(harness.has_label("alice → server"));
+
+    harness.click("💾 Save");
+    harness.run_until(|harness| !harness.has_label("modified"));
+    let tunnels = harness.server.with_store(|store| store.tunnels.clone());
+    assert_eq!(tunnels[0].sender, alice);
+}
+
+fn search_players(harness: &mut Harness) {
+    harness.server.with_store(|store| {
+        store.add_player("alice", "1");
+        store.add_player("bob", "2");
+        store.add_player("alina", "3");
+    });
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 3"));
+
+    harness.type_into_input("", "ali");
+    harness.run_until(|harness| harness.has_label("total : 2"));
+    assert!(harness.has_label("alice"));
+    assert!(!harness.has_label("bob"));
+
+    // 按用户名降序
+    harness.click("name");
+    harness.run_until(|harness| !harness.find_all(&[Role::Button], "name ⏶").is_empty());
+    harness.click("name ⏶");
+    harness.run_until(|harness| !harness.find_all(&[Role::Button], "name ⏷").is_empty());
+    harness.run_until(|harness| {
+        let alice = harness.find_all(&[Role::StaticText], "alice")[0];
+        let alina = harness.find_all(&[Role::StaticText], "alina")[0];
+        alina.top() < alice.top()
+    });
+}
+
+#[test]
+fn search_and_sort_players() {
+    let mut harness = Harness::logged_in();
+    search_players(&mut harness);
+}
+
+#[test]
+fn search_players_without_server_support() {
+    let mut harness = Harness::logged_in();
+    // 服务器忽略查询参数时由客户端过滤
+    harness.server.set_list_query_support(false);
+    search_players(&mut harness);
+}
+
+#[test]
+fn player_search_waits_for_typing() {
+    let mut harness = Harness::logged_in();
+    harness.server.with_store(|store| {
+        store.add_player("alice", "1");
+        store.add_player("bob", "2");
+        store.add_player("alina", "3");
+    });
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 3"));
+    let count = harness.server.request_count("player_list");
+
+    // 连续输入时不发送请求
+    harness.type_into_input("", "a");
+    for text in ["l", "i"] {
+        harness.events.push(Event::Text(text.into()));
+        harness.step();
+    }
+    assert_eq!(harness.server.request_count("player_list"), count);
+
+    harness.run_until(|harness| harness.has_label("total : 2"));
+    assert_eq!(harness.server.request_count("player_list"), count + 1);
+}
+
+#[test]
+fn cancel_discard_keeps_edits_and_filter() {
+    let mut harness = Harness::logged_in();
+    harness.server.with_store(|store| {
+        store.add_player("alice", "1");
+        store.add_player("bob", "2");
+    });
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 2"));
+    harness.type_into_input("1", "x");
+
+    harness.type_into_input("", "bo");
+    harness.run_until(|harness| harness.has_label("1 unsaved change(s) will be lost"));
+    harness.click("cancel");
+    // 过滤条件恢复，不会再次提示
+    for _ in 0..10 {
+        harness.step();
+    }
+    assert!(!harness.has_label("1 unsaved change(s) will be lost"));
+    assert!(!harness.has_text_input("bo"));
+    assert!(harness.has_text_input("1x"));
+    assert!(harness.has_label("total : 2"));
+    assert!(harness.has_label("alice"));
+    assert!(harness.has_enabled_button("💾 save all modified (1)"));
+}
+
+#[test]
+fn local_search_loads_every_page() {
+    let mut harness = Harness::logged_in();
+    harness.server.set_list_query_support(false);
+    harness.server.with_store(|store| {
+        for i in 0..130 {
+            store.add_player(&format!("player{}", i), "1");
+        }
+    });
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 130"));
+
+    // player12 和第二页的 player120 ~ player129
+    harness.type_into_input("", "player12");
+    harness.run_until(|harness| harness.has_label("total : 11"));
+    assert!(harness.has_label("player129"));
+}
+
+#[test]
+fn local_search_reuses_loaded_players() {
+    let mut harness = Harness::logged_in();
+    harness.server.set_list_query_support(false);
+    harness.server.with_store(|store| {
+        store.add_player("alice", "1");
+        store.add_player("bob", "2");
+        store.add_player("alina", "3");
+    });
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 3"));
+    harness.type_into_input("", "ali");
+    harness.run_until(|harness| harness.has_label("total : 2"));
+
+    // 之后修改过滤条件直接在已加载的数据中查找
+    let count = harness.server.request_count("player_list");
+    harness.type_into_input("ali", "c");
+    harness.run_until(|harness| harness.has_label("total : 1"));
+    assert!(harness.has_label("alice"));
+    assert_eq!(harness.server.request_count("player_list"), count);
+
+    // 手动刷新重新加载
+    harness.server.with_store(|store| {
+        store.add_player("alice2", "4");
+    });
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 2"));
+    assert_eq!(harness.server.request_count("player_list"), count + 1);
+}
+
+#[test]
+fn tunnel_filter_survives_refresh() {
+    let mut harness = Harness::logged_in();
+    harness.server.with_store(|store| {
+        add_tunnel(store, "web");
+        add_tunnel(store, "dns");
+        add_tunnel(store, "website");
+    });
+
+    harness.click("🔀tunnel");
+    harness.run_until(|harness| harness.has_label("total : 3"));
+    harness.type_into_input("", "web");
+    harness.run_until(|harness| harness.has_label("total : 2"));
+    assert!(!harness.has_label("dns"));
+
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 2"));
+    harness.server.with_store(|store| {
+        add_tunnel(store, "webhook");
+    });
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 3"));
+    assert!(!harness.has_label("dns"));
+
+    harness.click("description");
+    harness.run_until(|harness| {
+        !harness
+            .find_all(&[Role::Button], "description ⏶")
+            .is_empty()
+    });
+    harness.click("description ⏶");
+    harness.run_until(|harness| {
+        !harness
+            .find_all(&[Role::Button], "description ⏷")
+            .is_empty()
+    });
+    harness.run_until(|harness| {
+        let web = harness.find_all(&[Role::StaticText], "web")[0];
+        let website = harness.find_all(&[Role::StaticText], "website")[0];
+        website.top() < web.top()
+    });
+}
+
+#[test]
+fn bulk_disable_tunnels_with_retry() {
+    let mut harness = Harness::logged_in();
+    harness.server.with_store(|store| {
+        add_tunnel(store, "first");
+        add_tunnel(store, "second");
+        add_tunnel(store, "third");
+    });
+
+    harness.click("🔀tunnel");
+    harness.run_until(|harness| harness.has_label("total : 3"));
+    // 前两个是 sender/receiver 过滤，之后是全选
+    // 选中后工具栏出现，表格位置会变化
+    harness.click_checkbox(3);
+    harness.settle();
+    harness.click_checkbox(4);
+    harness.step();
+    assert!(harness.has_label("2 selected"));
+
+    harness.server.inject(Some("update_tunnel"), Fault::Error);
+    harness.click("disable");
+    harness.run_until(|harness| harness.has_label("2 failed"));
+
+    harness.server.clear_faults();
+    harness.click("retry failed (2)");
+    harness.run_until(|harness| {
+        harness
+            .server
+            .with_store(|store| store.tunnels.iter().filter(|x| x.enabled).count() == 1)
+    });
+    let tunnels = harness.server.with_store(|store| store.tunnels.clone());
+    assert!(tunnels[2].enabled);
+
+    harness.click("close");
+    harness.step();
+    harness.click_checkbox(2);
+    harness.step();
+    assert!(harness.has_label("3 selected"));
+    harness.click("🗑 delete");
+    harness.step();
+    harness.click("yes");
+    harness.run_until(|harness| harness.has_label("total : 0"));
+    assert!(harness.server.with_store(|store| store.tunnels.is_empty()));
+}
+
+#[test]
+fn import_players_skips_duplicates() {
+    let mut harness = Harness::logged_in();
+    harness.server.with_store(|store| {
+        store.add_player("alice", "1");
+    });
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 1"));
+
+    harness.click("📥 import");
+    harness.settle();
+    harness.type_into_multiline("", "alice,1\nbob,2\ncarol,3\nbob,4");
+    harness.run_until(|harness| harness.has_label("4 row(s), 2 skipped"));
+    assert!(harness.has_label("already exists"));
+    assert!(harness.has_label("duplicate in file"));
+
+    harness.click("import 2 player(s)");
+    harness.run_until(|harness| harness.has_label("total : 3"));
+    let names: Vec<_> = harness.server.with_store(|store| {
+        store
+            .players
+            .iter()
+            .map(|x| (x.username.clone(), x.password.clone()))
+            .collect()
+    });
+    assert_eq!(
+        names,
+        [
+            ("alice".to_string(), "1".to_string()),
+            ("bob".to_string(), "2".to_string()),
+            ("carol".to_string(), "3".to_string())
+        ]
+    );
+}
+
+#[test]
+fn bulk_reset_and_delete_players() {
+    let mut harness = Harness::logged_in();
+    harness.server.with_store(|store| {
+        store.add_player("alice", "1");
+        store.add_player("bob", "2");
+        store.add_player("carol", "3");
+    });
+    harness.click("🔃");
+    harness.run_until(|harness| harness.has_label("total : 3"));
+
+    // 第 0 个是全选
+    harness.click_checkbox(1);
+    harness.settle();
+    harness.click_checkbox(2);
+    harness.step();
+    assert!(harness.has_label("2 selected"));
+
+    harness.type_into_input("", "reset");
+    harness.click("🔑 reset password");
+    harness.run_until(|harness| {
+        harness.server.with_store(|store| {
+            store
+                .players
+                .iter()
+                .filter(|x| x.password == "reset")
+                .count()
+                == 2
+        }) && !harness.find_all(&[Role::Button], "close").is_empty()
+    });
+    harness.click("close");
+    harness.step();
+
+    harness.click("🗑 delete");
+    harness.step();
+    harness.click("yes");
+    harness.run_until(|harness| harness.has_label("total : 1"));
+    let players = harness.server.with_store(|store| store.players.clone());
+    assert_eq!(players.len(), 1);
+    assert_eq!(players[0].username, "carol");
+    assert_eq!(players[0].password, "3");
+}
+
+#[test]
+fn export_and_import_config() {
+    let mut harness = Harness::logged_in();
+    harness.server.with_store(|store| {
+        let alice = store.add_player("alice", "1");
+        add_tunnel(store, "first");
+        add_tunnel(store, "second");
+        store.tunnels[0].sender = alice;
+        store.tunnels[1].source = "0.0.0.0:8001".into();
+    });
+    let path = std::env::temp_dir().join(format!("npipe-config-{}.toml", std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+
+    harness.click("🗄config");
+    harness.settle();
+    harness.click("toml");
+    harness.step();
+    // 全选默认路径后替换为临时文件路径
+    harness.type_into_input("npipe-config.toml", "");
+    harness.events.push(Event::Key {
+        key: egui::Key::A,
+        physical_key: None,
+        pressed: true,
+        repeat: false,
+        modifiers: Modifiers::COMMAND,
+    });
+    harness.events.push(Event::Text(path.clone()));
+    harness.step();
+    harness.step();
+
+    harness.click("⬇ export");
+    harness.run_until(|harness| {
+        harness.has_label(&format!("exported 1 player(s), 2 tunnel(s) to {}", path))
+    });
+    let mut document =
+        crate::config::ConfigDocument::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(document.tunnels[0].sender, "alice");
+
+    // 新增玩家 bob 并把第二个隧道改为 bob 发送
+    document.players.push(crate::config::PlayerConfig {
+        username: "bob".into(),
+        password: "2".into(),
+    });
+    document.tunnels[1].sender = "bob".into();
+    document.tunnels.remove(0);
+    let text = document
+        .to_string(crate::config::ConfigFormat::Toml)
+        .unwrap();
+    std::fs::write(&path, text).unwrap();
+
+    harness.click("⬆ import");
+    harness.run_until(|harness| harness.has_label("players: +1 ~0 -0"));
+    assert!(harness.has_label("tunnels: +0 ~1 -1"));
+    harness.click("remove players and tunnels missing from the file");
+    harness.click("apply");
+    // 第二步隧道请求完成后再关闭窗口
+    harness.run_until(|harness| {
+        harness.server.with_store(|store| {
+            let bob = store.players.iter().find(|x| x.username == "bob");
+            bob.is_some_and(|bob| store.tunnels[0].sender == bob.id)
+        }) && harness.has_enabled_button("close")
+    });
+    harness.click("close");
+    harness.run_until(|harness| harness.has_label("nothing to apply"));
+    std::fs::remove_file(&path).unwrap();
+
+    let (players, tunnels) = harness
+        .server
+        .with_store(|store| (store.players.clone(), store.tunnels.clone()));
+    assert_eq!(players.len(), 2);
+    assert_eq!(tunnels.len(), 1);
+    assert_eq!(tunnels[0].description, "second");
+    assert_eq!(tunnels[0].sender, players[1].id);
+}
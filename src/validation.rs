@@ -0,0 +1,212 @@
+//! 隧道地址校验，在请求发送前检查 `source`/`endpoint` 格式
+
+use crate::proto::TUNNEL_TYPE_SOCKS5;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::ops::RangeInclusive;
+
+/// 地址中的主机部分
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Host {
+    Ipv4(Ipv4Addr),
+    Ipv6(Ipv6Addr),
+    Domain(String),
+}
+
+/// 解析后的 `host:port` 或 `host:start-end`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    pub host: Host,
+    pub ports: RangeInclusive<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    Empty,
+    MissingPort,
+    InvalidPort(String),
+    InvalidPortRange(String),
+    InvalidHost(String),
+    InvalidIpv6(String),
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressError::Empty => write!(f, "address is empty"),
+            AddressError::MissingPort => write!(f, "expected host:port"),
+            AddressError::InvalidPort(port) => write!(f, "invalid port '{}'", port),
+            AddressError::InvalidPortRange(range) => write!(f, "invalid port range '{}'", range),
+            AddressError::InvalidHost(host) => write!(f, "invalid host '{}'", host),
+            AddressError::InvalidIpv6(host) => write!(f, "invalid ipv6 address '{}'", host),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+/// 解析 `1.2.3.4:80`、`[::1]:80`、`example.com:8000-8010` 形式的地址
+pub fn parse_address(address: &str) -> Result<Address, AddressError> {
+    let address = address.trim();
+    if address.is_empty() {
+        return Err(AddressError::Empty);
+    }
+
+    let (host, ports) = if let Some(rest) = address.strip_prefix('[') {
+        let (ipv6, ports) = rest
+            .split_once(']')
+            .ok_or_else(|| AddressError::InvalidIpv6(address.to_string()))?;
+        let ipv6 = ipv6
+            .parse::<Ipv6Addr>()
+            .map_err(|_| AddressError::InvalidIpv6(ipv6.to_string()))?;
+        let ports = ports.strip_prefix(':').ok_or(AddressError::MissingPort)?;
+        (Host::Ipv6(ipv6), ports)
+    } else {
+        let (host, ports) = address.rsplit_once(':').ok_or(AddressError::MissingPort)?;
+        // 没有方括号的 ipv6 无法区分端口
+        if host.contains(':') {
+            return Err(AddressError::InvalidIpv6(host.to_string()));
+        }
+        (parse_host(host)?, ports)
+    };
+
+    Ok(Address {
+        host,
+        ports: parse_ports(ports)?,
+    })
+}
+
+fn parse_host(host: &str) -> Result<Host, AddressError> {
+    let invalid = || AddressError::InvalidHost(host.to_string());
+    if host.is_empty() || host.len() > 253 {
+        return Err(invalid());
+    }
+
+    // 全部由数字和点组成时只能是 ipv4
+    if host.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return host
+            .parse::<Ipv4Addr>()
+            .map(Host::Ipv4)
+            .map_err(|_| invalid());
+    }
+
+    let valid_label = |label: &str| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    if host.split('.').all(valid_label) {
+        Ok(Host::Domain(host.to_string()))
+    } else {
+        Err(invalid())
+    }
+}
+
+fn parse_ports(ports: &str) -> Result<RangeInclusive<u16>, AddressError> {
+    let parse_port = |port: &str| match port.parse::<u16>() {
+        Ok(port) if port != 0 => Ok(port),
+        _ => Err(AddressError::InvalidPort(port.to_string())),
+    };
+
+    match ports.split_once('-') {
+        Some((start, end)) => {
+            let start = parse_port(start)?;
+            let end = parse_port(end)?;
+            if start > end {
+                return Err(AddressError::InvalidPortRange(ports.to_string()));
+            }
+            Ok(start..=end)
+        }
+        None => {
+            let port = parse_port(ports)?;
+            Ok(port..=port)
+        }
+    }
+}
+
+/// 检查监听地址
+pub fn check_source(source: &str) -> Result<(), AddressError> {
+    parse_address(source).map(|_| ())
+}
+
+/// 检查目标地址，SOCKS5 隧道的目标由客户端决定，可以为空
+pub fn check_endpoint(tunnel_type: u32, endpoint: &str) -> Result<(), AddressError> {
+    if tunnel_type == TUNNEL_TYPE_SOCKS5 && endpoint.trim().is_empty() {
+        return Ok(());
+    }
+    parse_address(endpoint).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ipv4() {
+        let address = parse_address("127.0.0.1:8080").unwrap();
+        assert_eq!(address.host, Host::Ipv4(Ipv4Addr::LOCALHOST));
+        assert_eq!(address.ports, 8080..=8080);
+    }
+
+    #[test]
+    fn parses_ipv6() {
+        let address = parse_address("[::1]:80").unwrap();
+        assert_eq!(address.host, Host::Ipv6(Ipv6Addr::LOCALHOST));
+        assert_eq!(
+            parse_address("::1:80"),
+            Err(AddressError::InvalidIpv6("::1".into()))
+        );
+        assert_eq!(
+            parse_address("[::g]:80"),
+            Err(AddressError::InvalidIpv6("::g".into()))
+        );
+        assert_eq!(parse_address("[::1]"), Err(AddressError::MissingPort));
+    }
+
+    #[test]
+    fn parses_hostname_and_port_range() {
+        let address = parse_address("example.com:8000-8010").unwrap();
+        assert_eq!(address.host, Host::Domain("example.com".into()));
+        assert_eq!(address.ports, 8000..=8010);
+        assert!(parse_address("localhost:1").is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        assert_eq!(parse_address(" "), Err(AddressError::Empty));
+        assert_eq!(parse_address("127.0.0.1"), Err(AddressError::MissingPort));
+        assert_eq!(
+            parse_address("256.0.0.1:80"),
+            Err(AddressError::InvalidHost("256.0.0.1".into()))
+        );
+        assert_eq!(
+            parse_address("-bad.com:80"),
+            Err(AddressError::InvalidHost("-bad.com".into()))
+        );
+        assert_eq!(
+            parse_address("a_b:80"),
+            Err(AddressError::InvalidHost("a_b".into()))
+        );
+        assert_eq!(
+            parse_address("host:0"),
+            Err(AddressError::InvalidPort("0".into()))
+        );
+        assert_eq!(
+            parse_address("host:65536"),
+            Err(AddressError::InvalidPort("65536".into()))
+        );
+        assert_eq!(
+            parse_address("host:90-80"),
+            Err(AddressError::InvalidPortRange("90-80".into()))
+        );
+    }
+
+    #[test]
+    fn socks5_endpoint_may_be_empty() {
+        assert!(check_endpoint(TUNNEL_TYPE_SOCKS5, "").is_ok());
+        assert!(check_endpoint(0, "").is_err());
+        assert!(check_source("").is_err());
+    }
+}